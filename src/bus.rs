@@ -0,0 +1,34 @@
+use cpu::RamInit;
+
+/// Everything the CPU sees through its address and data lines.
+///
+/// `read` takes `&mut self` because on a real console reading some addresses
+/// has side effects, e.g. $2002 clears the PPU's vblank flag. Debuggers and
+/// snapshots use `peek` instead, which must not change any state.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, val: u8);
+
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Fills the internal RAM at power-on. Buses without RAM of their own
+    /// may ignore it.
+    fn init_ram(&mut self, _ram_init: &RamInit) {}
+
+    /// Whether something, usually the cartridge, is holding the CPU's IRQ
+    /// line low. The line is level triggered, so it stays asserted until
+    /// the program acknowledges the interrupt at its source.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Called after each instruction or interrupt with the cycles it took,
+    /// for whatever on the bus keeps time on the CPU clock, e.g. the IRQ
+    /// counter of a cartridge.
+    ///
+    /// The cycles come in one batch, after all of the instruction's reads
+    /// and writes. A console which also runs a PPU should tick the bus
+    /// itself one cycle at a time, stepping the PPU in between.
+    fn tick(&mut self, _cycles: u32) {}
+}
@@ -0,0 +1,292 @@
+#[cfg(feature = "std")]
+use std::error;
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use opcode;
+use opcode::utils::stack;
+use opcode::Cycle;
+use opcode::OpCode;
+
+use bus::Bus;
+
+use super::{MemoryRangeChange, Registers, RP2A03};
+
+/// Address the routine returns to. Execution stops as soon as PC reaches it
+/// with the stack back where it was before the call.
+pub const DEFAULT_RETURN_ADDR: u16 = 0xffff;
+
+pub const DEFAULT_CYCLE_BUDGET: u32 = 1_000_000;
+
+/// Inputs of a call made through `RP2A03::call`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubroutineCall {
+    pub addr: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    /// Bytes written to memory before the call, e.g. arguments in zero page.
+    pub memory: Vec<(u16, u8)>,
+    pub cycle_budget: u32,
+    pub return_addr: u16,
+}
+
+impl SubroutineCall {
+    pub fn new(addr: u16) -> SubroutineCall {
+        SubroutineCall {
+            addr,
+            a: 0x00,
+            x: 0x00,
+            y: 0x00,
+            memory: Vec::new(),
+            cycle_budget: DEFAULT_CYCLE_BUDGET,
+            return_addr: DEFAULT_RETURN_ADDR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallResult {
+    /// Registers right after the final RTS.
+    pub registers: Registers,
+    /// Every run of bytes the routine changed, in address order.
+    pub memory_changes: Vec<MemoryRangeChange>,
+    /// Cycles spent from the first instruction up to and including the RTS.
+    pub cycles: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallError {
+    CycleBudgetExceeded { budget: u32, pc: u16 },
+    Jammed { pc: u16, opcode: u8 },
+    UnknownOpcode { pc: u16, opcode: u8 },
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CallError::CycleBudgetExceeded { budget, pc } => write!(
+                fmt,
+                "routine did not return within {} cycles (pc: ${:04X})",
+                budget, pc
+            ),
+            CallError::Jammed { pc, opcode } => {
+                write!(fmt, "cpu jammed on opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+            CallError::UnknownOpcode { pc, opcode } => {
+                write!(fmt, "unknown opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for CallError {}
+
+impl<B: Bus> RP2A03<B> {
+    /// Calls the routine at `call.addr` as if by JSR and runs it until the
+    /// matching RTS.
+    ///
+    /// A sentinel return address is pushed onto the current stack, so the
+    /// routine sees the same stack layout as after a real JSR. Memory inputs
+    /// and the sentinel are written before the memory snapshot is taken, so
+    /// only the routine's own writes show up in `memory_changes`.
+    pub fn call(&mut self, call: &SubroutineCall) -> Result<CallResult, CallError> {
+        for &(addr, val) in &call.memory {
+            self.memory.write(addr, val);
+        }
+
+        self.registers.a = call.a;
+        self.registers.x = call.x;
+        self.registers.y = call.y;
+
+        let pushed_addr = call.return_addr.wrapping_sub(1);
+        stack::push(&mut self.registers, &mut self.memory, (pushed_addr >> 8) as u8);
+        stack::push(&mut self.registers, &mut self.memory, pushed_addr as u8);
+
+        let return_sp = self.registers.sp.wrapping_add(2);
+        self.registers.pc = call.addr;
+
+        let before = self.snapshot();
+        let mut cycles = 0;
+
+        while self.registers.pc != call.return_addr || self.registers.sp != return_sp {
+            let pc = self.registers.pc;
+            let opcode = self.memory.peek(pc);
+
+            if cycles >= call.cycle_budget {
+                return Err(CallError::CycleBudgetExceeded {
+                    budget: call.cycle_budget,
+                    pc,
+                });
+            }
+
+            if opcode::is_jam(opcode) {
+                return Err(CallError::Jammed { pc, opcode });
+            }
+
+            if OpCode::decode(opcode).is_none() {
+                return Err(CallError::UnknownOpcode { pc, opcode });
+            }
+
+            let Cycle(cycles_num) = self.execute();
+            cycles += cycles_num;
+        }
+
+        Ok(CallResult {
+            registers: self.registers.clone(),
+            memory_changes: before.diff(&self.snapshot()).memory,
+            cycles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cpu_with_routine(addr: u16, routine: &[u8]) -> RP2A03 {
+        let mut cpu = RP2A03::new();
+        cpu.registers.sp = 0xfd;
+
+        for (i, byte) in routine.iter().enumerate() {
+            cpu.memory.write(addr + i as u16, *byte);
+        }
+
+        cpu
+    }
+
+    #[test]
+    fn call_returns_registers_and_cycles() {
+        // LDA #$41; ADC #$01; RTS
+        let mut cpu = cpu_with_routine(0x8000, &[0xa9, 0x41, 0x69, 0x01, 0x60]);
+
+        let result = cpu.call(&SubroutineCall::new(0x8000)).unwrap();
+
+        assert_eq!(result.registers.a, 0x42);
+        assert_eq!(result.registers.pc, DEFAULT_RETURN_ADDR);
+        assert_eq!(result.registers.sp, 0xfd);
+        assert_eq!(result.cycles, 2 + 2 + 6);
+        assert_eq!(result.memory_changes, vec![]);
+    }
+
+    #[test]
+    fn call_with_register_and_memory_inputs() {
+        // LDA $10,X; ASL $20; RTS
+        let mut cpu = cpu_with_routine(0x8000, &[0xb5, 0x10, 0x06, 0x20, 0x60]);
+        let call = SubroutineCall {
+            x: 0x02,
+            memory: vec![(0x0012, 0x99), (0x0020, 0x21)],
+            ..SubroutineCall::new(0x8000)
+        };
+
+        let result = cpu.call(&call).unwrap();
+
+        assert_eq!(result.registers.a, 0x99);
+        assert_eq!(result.registers.x, 0x02);
+        assert_eq!(
+            result.memory_changes,
+            vec![
+                MemoryRangeChange {
+                    start: 0x0020,
+                    old: vec![0x21],
+                    new: vec![0x42],
+                },
+            ]
+        );
+        assert_eq!(result.cycles, 4 + 5 + 6);
+    }
+
+    #[test]
+    fn call_with_zero_page_index_wrapping() {
+        // LDA $F0,X; RTS
+        let mut cpu = cpu_with_routine(0x8000, &[0xb5, 0xf0, 0x60]);
+        let call = SubroutineCall {
+            x: 0x20,
+            memory: vec![(0x0010, 0x77)],
+            ..SubroutineCall::new(0x8000)
+        };
+
+        let result = cpu.call(&call).unwrap();
+
+        assert_eq!(result.registers.a, 0x77);
+    }
+
+    #[test]
+    fn call_pushes_return_addr_minus_one() {
+        // RTS
+        let mut cpu = cpu_with_routine(0x8000, &[0x60]);
+        let call = SubroutineCall {
+            return_addr: 0x1234,
+            ..SubroutineCall::new(0x8000)
+        };
+
+        cpu.call(&call).unwrap();
+
+        assert_eq!(cpu.memory.read(0x01fd), 0x12);
+        assert_eq!(cpu.memory.read(0x01fc), 0x33);
+    }
+
+    #[test]
+    fn call_returning_to_zero() {
+        // RTS
+        let mut cpu = cpu_with_routine(0x8000, &[0x60]);
+        let call = SubroutineCall {
+            return_addr: 0x0000,
+            ..SubroutineCall::new(0x8000)
+        };
+
+        let result = cpu.call(&call).unwrap();
+
+        assert_eq!(result.registers.pc, 0x0000);
+        assert_eq!(result.cycles, 6);
+    }
+
+    #[test]
+    fn call_exceeding_cycle_budget() {
+        // NOP x 8; RTS
+        let mut routine = vec![0xea; 8];
+        routine.push(0x60);
+        let mut cpu = cpu_with_routine(0x8000, &routine);
+        let call = SubroutineCall {
+            cycle_budget: 10,
+            ..SubroutineCall::new(0x8000)
+        };
+
+        assert_eq!(
+            cpu.call(&call),
+            Err(CallError::CycleBudgetExceeded {
+                budget: 10,
+                pc: 0x8005,
+            })
+        );
+    }
+
+    #[test]
+    fn call_jammed() {
+        // NOP; KIL
+        let mut cpu = cpu_with_routine(0x8000, &[0xea, 0x02]);
+
+        assert_eq!(
+            cpu.call(&SubroutineCall::new(0x8000)),
+            Err(CallError::Jammed {
+                pc: 0x8001,
+                opcode: 0x02,
+            })
+        );
+    }
+
+    #[test]
+    fn call_with_unknown_opcode() {
+        let mut cpu = cpu_with_routine(0x8000, &[0xff]);
+
+        assert_eq!(
+            cpu.call(&SubroutineCall::new(0x8000)),
+            Err(CallError::UnknownOpcode {
+                pc: 0x8000,
+                opcode: 0xff,
+            })
+        );
+    }
+}
@@ -0,0 +1,442 @@
+#[cfg(feature = "alloc")]
+mod call;
+mod ram;
+#[cfg(feature = "alloc")]
+mod snapshot;
+mod status;
+
+#[cfg(feature = "alloc")]
+pub use self::call::{CallError, CallResult, SubroutineCall};
+#[cfg(feature = "alloc")]
+pub use self::call::{DEFAULT_CYCLE_BUDGET, DEFAULT_RETURN_ADDR};
+pub use self::ram::{RamInit, RAM_SIZE};
+#[cfg(feature = "alloc")]
+pub use self::snapshot::{CpuDiff, CpuSnapshot, Flag, FlagChange, MemoryRangeChange, Register,
+                         RegisterChange};
+pub use self::status::StatusFlags;
+
+use opcode::utils::stack;
+use opcode::OpCode;
+use opcode::Cycle;
+
+use bus::Bus;
+
+const MEM_SIZE: usize = 0x10000;
+
+pub const NMI_VECTOR: u16 = 0xfffa;
+pub const RESET_VECTOR: u16 = 0xfffc;
+pub const IRQ_VECTOR: u16 = 0xfffe;
+
+// Cycles spent by the reset sequence before the first opcode is fetched.
+const RESET_CYCLES: u32 = 7;
+
+// Cycles spent pushing PC and P and fetching the vector of an interrupt.
+const INTERRUPT_CYCLES: u32 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub pc: u16,
+    pub sp: u8,
+    p: StatusFlags,
+}
+
+impl Registers {
+    pub fn new() -> Registers {
+        Registers {
+            a: 0x00,
+            x: 0x00,
+            y: 0x00,
+            p: StatusFlags::new(),
+            pc: 0x00,
+            sp: 0x00,
+        }
+    }
+}
+
+impl Default for Registers {
+    fn default() -> Registers {
+        Registers::new()
+    }
+}
+
+macro_rules! bit_flag_getter_setter {
+    ($setter_name: ident, $getter_name: ident, $flag: expr) => {
+        #[inline]
+        pub fn $setter_name(&mut self, flag: bool) {
+            self.p.set($flag, flag);
+        }
+
+        #[inline]
+        pub fn $getter_name(&self) -> bool {
+            self.p.contains($flag)
+        }
+    }
+}
+
+impl Registers {
+    #[inline]
+    pub fn p(&self) -> u8 {
+        self.p.bits()
+    }
+
+    #[inline]
+    pub fn status(&self) -> StatusFlags {
+        self.p
+    }
+
+    #[inline]
+    pub fn set_status(&mut self, status: StatusFlags) {
+        self.p = status;
+    }
+
+    bit_flag_getter_setter!(set_carry_flag, carry_flag, StatusFlags::CARRY);
+    bit_flag_getter_setter!(set_zero_flag, zero_flag, StatusFlags::ZERO);
+    bit_flag_getter_setter!(
+        set_interrupt_disable_flag,
+        interrupt_disable_flag,
+        StatusFlags::INTERRUPT_DISABLE
+    );
+    bit_flag_getter_setter!(set_decimal_mode_flag, decimal_mode_flag, StatusFlags::DECIMAL_MODE);
+    bit_flag_getter_setter!(set_break_command_flag, break_command_flag, StatusFlags::BREAK_COMMAND);
+    bit_flag_getter_setter!(set_overflow_flag, overflow_flag, StatusFlags::OVERFLOW);
+    bit_flag_getter_setter!(set_sign_flag, sign_flag, StatusFlags::SIGN);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory {
+    raw_memory: [u8; MEM_SIZE],
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory { raw_memory: [0x00; MEM_SIZE] }
+    }
+
+    /// Copies the whole address space of `bus` without side effects.
+    pub fn from_bus<B: Bus + ?Sized>(bus: &B) -> Memory {
+        let mut memory = Memory::new();
+
+        for (addr, byte) in memory.raw_memory.iter_mut().enumerate() {
+            *byte = bus.peek(addr as u16);
+        }
+
+        memory
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        self.raw_memory[addr as usize]
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        self.raw_memory[addr as usize] = val;
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
+impl Bus for Memory {
+    #[inline]
+    fn read(&mut self, addr: u16) -> u8 {
+        Memory::read(self, addr)
+    }
+
+    #[inline]
+    fn write(&mut self, addr: u16, val: u8) {
+        Memory::write(self, addr, val)
+    }
+
+    #[inline]
+    fn peek(&self, addr: u16) -> u8 {
+        Memory::read(self, addr)
+    }
+
+    fn init_ram(&mut self, ram_init: &RamInit) {
+        ram_init.fill(&mut self.raw_memory[..RAM_SIZE]);
+    }
+}
+
+fn read_word<B: Bus + ?Sized>(bus: &mut B, addr: u16) -> u16 {
+    let low = bus.read(addr) as u16;
+    let high = bus.read(addr.wrapping_add(1)) as u16;
+
+    (high << 8) | low
+}
+
+/// The 6502 core of the NES, wired to the rest of the console through `B`.
+pub struct RP2A03<B: Bus = Memory> {
+    pub memory: B,
+    pub registers: Registers,
+    current_cycles: u32,
+}
+
+impl RP2A03 {
+    pub fn new() -> RP2A03 {
+        RP2A03::with_bus(Memory::new())
+    }
+}
+
+impl Default for RP2A03 {
+    fn default() -> RP2A03 {
+        RP2A03::new()
+    }
+}
+
+impl<B: Bus> RP2A03<B> {
+    pub fn with_bus(bus: B) -> RP2A03<B> {
+        RP2A03 {
+            memory: bus,
+            registers: Registers::new(),
+            current_cycles: 0,
+        }
+    }
+
+    /// Puts the CPU in the state it has after the console is switched on and
+    /// the reset sequence has run: SP is $FD, only I is set in P, A/X/Y are
+    /// zero, PC comes from the reset vector and RAM is filled according to
+    /// `ram_init`.
+    pub fn power_on(&mut self, ram_init: &RamInit) {
+        self.memory.init_ram(ram_init);
+
+        self.registers = Registers::new();
+        self.registers.sp = 0xfd;
+        self.registers.set_interrupt_disable_flag(true);
+        self.registers.pc = read_word(&mut self.memory, RESET_VECTOR);
+
+        self.current_cycles = RESET_CYCLES;
+    }
+
+    /// Pressing the reset button. The reset sequence goes through the motions
+    /// of pushing PC and P but the writes are suppressed, so only SP moves.
+    /// A/X/Y and RAM are left untouched.
+    pub fn soft_reset(&mut self) {
+        self.registers.sp = self.registers.sp.wrapping_sub(3);
+        self.registers.set_interrupt_disable_flag(true);
+        self.registers.pc = read_word(&mut self.memory, RESET_VECTOR);
+
+        self.current_cycles += RESET_CYCLES;
+    }
+
+    #[inline]
+    pub fn cycles(&self) -> u32 {
+        self.current_cycles
+    }
+
+    /// Runs the next instruction, or enters the IRQ handler instead if the
+    /// bus asserts IRQ and interrupts are enabled.
+    pub fn execute(&mut self) -> Cycle {
+        if self.memory.irq() && !self.registers.interrupt_disable_flag() {
+            return self.interrupt(IRQ_VECTOR);
+        }
+
+        let pc = self.registers.pc;
+
+        let opcode = OpCode::from(self.memory.read(pc));
+        let opcode_fn = opcode.get_fn();
+
+        let Cycle(cycles_num) = opcode_fn(&mut self.registers, &mut self.memory);
+
+        self.registers.pc = self.registers.pc.wrapping_add((1 + opcode.operands_num()) as u16);
+        self.current_cycles += cycles_num;
+        self.memory.tick(cycles_num);
+
+        Cycle(cycles_num)
+    }
+
+    // Pushes PC and P, with B clear, and jumps through the vector.
+    fn interrupt(&mut self, vector: u16) -> Cycle {
+        let pc = self.registers.pc;
+        let p = self.registers.status().to_stack_byte(false);
+
+        stack::push(&mut self.registers, &mut self.memory, (pc >> 8) as u8);
+        stack::push(&mut self.registers, &mut self.memory, pc as u8);
+        stack::push(&mut self.registers, &mut self.memory, p);
+
+        self.registers.set_interrupt_disable_flag(true);
+        self.registers.pc = read_word(&mut self.memory, vector);
+        self.current_cycles += INTERRUPT_CYCLES;
+        self.memory.tick(INTERRUPT_CYCLES);
+
+        Cycle(INTERRUPT_CYCLES)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cpu_with_reset_vector(addr: u16) -> RP2A03 {
+        let mut cpu = RP2A03::new();
+        cpu.memory.write(RESET_VECTOR, addr as u8);
+        cpu.memory.write(RESET_VECTOR + 1, (addr >> 8) as u8);
+
+        cpu
+    }
+
+    #[test]
+    fn power_on_state() {
+        let mut cpu = cpu_with_reset_vector(0x8123);
+        cpu.registers.a = 0x12;
+        cpu.registers.x = 0x34;
+        cpu.registers.y = 0x56;
+        cpu.registers.set_carry_flag(true);
+
+        cpu.power_on(&RamInit::Zeroed);
+
+        assert_eq!(cpu.registers.pc, 0x8123);
+        assert_eq!(cpu.registers.sp, 0xfd);
+        assert_eq!(cpu.registers.status(), StatusFlags::from_bits(0b00100100));
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_eq!(cpu.registers.x, 0x00);
+        assert_eq!(cpu.registers.y, 0x00);
+        assert_eq!(cpu.cycles(), 7);
+    }
+
+    #[test]
+    fn power_on_fills_only_internal_ram() {
+        let mut cpu = cpu_with_reset_vector(0x8000);
+
+        cpu.power_on(&RamInit::Filled);
+
+        assert_eq!(cpu.memory.read(0x0000), 0xff);
+        assert_eq!(cpu.memory.read(0x07ff), 0xff);
+        assert_eq!(cpu.memory.read(0x0800), 0x00);
+    }
+
+    #[test]
+    fn soft_reset_state() {
+        let mut cpu = cpu_with_reset_vector(0xc000);
+        cpu.power_on(&RamInit::Zeroed);
+
+        cpu.registers.a = 0x12;
+        cpu.registers.x = 0x34;
+        cpu.registers.y = 0x56;
+        cpu.registers.pc = 0x1234;
+        cpu.registers.set_interrupt_disable_flag(false);
+        cpu.registers.set_carry_flag(true);
+        cpu.memory.write(0x0042, 0x99);
+
+        cpu.soft_reset();
+
+        assert_eq!(cpu.registers.pc, 0xc000);
+        assert_eq!(cpu.registers.sp, 0xfa);
+        assert!(cpu.registers.interrupt_disable_flag());
+        assert!(cpu.registers.carry_flag());
+        assert_eq!(cpu.registers.a, 0x12);
+        assert_eq!(cpu.registers.x, 0x34);
+        assert_eq!(cpu.registers.y, 0x56);
+        assert_eq!(cpu.memory.read(0x0042), 0x99);
+        assert_eq!(cpu.cycles(), 14);
+    }
+
+    #[test]
+    fn soft_reset_does_not_write_stack() {
+        let mut cpu = cpu_with_reset_vector(0xc000);
+        cpu.power_on(&RamInit::Zeroed);
+        let mem_snapshot = cpu.memory.clone();
+
+        cpu.soft_reset();
+
+        assert_eq!(cpu.memory, mem_snapshot);
+    }
+
+    /// Memory with a switch on the IRQ line, counting the cycles it is
+    /// ticked.
+    struct IrqMemory {
+        memory: Memory,
+        irq: bool,
+        ticks: u32,
+    }
+
+    impl Bus for IrqMemory {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.memory.read(addr)
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.memory.write(addr, val);
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.memory.read(addr)
+        }
+
+        fn irq(&self) -> bool {
+            self.irq
+        }
+
+        fn tick(&mut self, cycles: u32) {
+            self.ticks += cycles;
+        }
+    }
+
+    fn cpu_with_irq() -> RP2A03<IrqMemory> {
+        let mut memory = Memory::new();
+        memory.write(IRQ_VECTOR, 0x34);
+        memory.write(IRQ_VECTOR + 1, 0x92);
+
+        let mut cpu = RP2A03::with_bus(IrqMemory { memory, irq: true, ticks: 0 });
+        cpu.registers.pc = 0x8123;
+        cpu.registers.sp = 0xfd;
+        cpu.registers.set_carry_flag(true);
+
+        cpu
+    }
+
+    #[test]
+    fn irq_enters_handler() {
+        let mut cpu = cpu_with_irq();
+
+        let Cycle(cycles) = cpu.execute();
+
+        assert_eq!(cycles, 7);
+        assert_eq!(cpu.registers.pc, 0x9234);
+        assert_eq!(cpu.registers.sp, 0xfa);
+        assert!(cpu.registers.interrupt_disable_flag());
+        assert_eq!(cpu.memory.peek(0x01fd), 0x81);
+        assert_eq!(cpu.memory.peek(0x01fc), 0x23);
+        assert_eq!(cpu.memory.peek(0x01fb), 0b0010_0001);
+        assert_eq!(cpu.cycles(), 7);
+    }
+
+    #[test]
+    fn irq_is_masked_by_interrupt_disable() {
+        let mut cpu = cpu_with_irq();
+        cpu.registers.set_interrupt_disable_flag(true);
+
+        cpu.execute();
+
+        assert_ne!(cpu.registers.pc, 0x9234);
+        assert_eq!(cpu.registers.sp, 0xfd);
+    }
+
+    #[test]
+    fn bus_is_ticked_with_cycles() {
+        let mut cpu = cpu_with_irq();
+        cpu.memory.memory.write(0x8123, 0xea);
+        cpu.registers.set_interrupt_disable_flag(true);
+
+        cpu.execute();
+        assert_eq!(cpu.memory.ticks, 2);
+
+        cpu.registers.set_interrupt_disable_flag(false);
+        cpu.execute();
+        assert_eq!(cpu.memory.ticks, 2 + 7);
+    }
+
+    #[test]
+    fn soft_reset_wraps_stack_pointer() {
+        let mut cpu = cpu_with_reset_vector(0xc000);
+        cpu.registers.sp = 0x01;
+
+        cpu.soft_reset();
+
+        assert_eq!(cpu.registers.sp, 0xfe);
+    }
+}
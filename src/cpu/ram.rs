@@ -0,0 +1,115 @@
+/// Size of the internal work RAM, mapped at $0000-$07FF.
+pub const RAM_SIZE: usize = 0x0800;
+
+/// Contents of the internal RAM right after power-on.
+///
+/// Real hardware leaves RAM in an indeterminate state and a few games
+/// accidentally depend on what their developers' consoles happened to hold,
+/// so this is configurable instead of always being zero.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RamInit {
+    /// Every byte is $00.
+    Zeroed,
+    /// Every byte is $FF.
+    Filled,
+    /// The given bytes repeated over the whole RAM.
+    Pattern(&'static [u8]),
+    /// Pseudo random bytes, reproducible from the seed.
+    Random(u32),
+}
+
+impl RamInit {
+    pub fn fill(&self, ram: &mut [u8]) {
+        match *self {
+            RamInit::Zeroed => {
+                for byte in ram.iter_mut() {
+                    *byte = 0x00;
+                }
+            }
+            RamInit::Filled => {
+                for byte in ram.iter_mut() {
+                    *byte = 0xff;
+                }
+            }
+            RamInit::Pattern(pattern) => {
+                if pattern.is_empty() {
+                    return RamInit::Zeroed.fill(ram);
+                }
+
+                for (byte, val) in ram.iter_mut().zip(pattern.iter().cycle()) {
+                    *byte = *val;
+                }
+            }
+            RamInit::Random(seed) => {
+                // xorshift32, which gets stuck on zero.
+                let mut state = if seed == 0 { 0x2545_f491 } else { seed };
+
+                for byte in ram.iter_mut() {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+
+                    *byte = (state >> 24) as u8;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zeroed() {
+        let mut ram = [0x42; 16];
+        RamInit::Zeroed.fill(&mut ram);
+
+        assert_eq!(ram, [0x00; 16]);
+    }
+
+    #[test]
+    fn filled() {
+        let mut ram = [0x42; 16];
+        RamInit::Filled.fill(&mut ram);
+
+        assert_eq!(ram, [0xff; 16]);
+    }
+
+    #[test]
+    fn pattern_is_repeated() {
+        let mut ram = [0x42; 6];
+        RamInit::Pattern(&[0x00, 0xff, 0x12, 0x34]).fill(&mut ram);
+
+        assert_eq!(ram, [0x00, 0xff, 0x12, 0x34, 0x00, 0xff]);
+    }
+
+    #[test]
+    fn empty_pattern_is_zeroed() {
+        let mut ram = [0x42; 4];
+        RamInit::Pattern(&[]).fill(&mut ram);
+
+        assert_eq!(ram, [0x00; 4]);
+    }
+
+    #[test]
+    fn random_is_reproducible() {
+        let mut ram_a = [0x00; 64];
+        let mut ram_b = [0x00; 64];
+        RamInit::Random(1234).fill(&mut ram_a);
+        RamInit::Random(1234).fill(&mut ram_b);
+
+        assert_eq!(&ram_a[..], &ram_b[..]);
+        assert!(ram_a.iter().any(|&byte| byte != ram_a[0]));
+    }
+
+    #[test]
+    fn random_with_different_seed() {
+        let mut ram_a = [0x00; 64];
+        let mut ram_b = [0x00; 64];
+        RamInit::Random(1).fill(&mut ram_a);
+        RamInit::Random(2).fill(&mut ram_b);
+
+        assert_ne!(&ram_a[..], &ram_b[..]);
+    }
+}
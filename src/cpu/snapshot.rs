@@ -0,0 +1,423 @@
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use bus::Bus;
+
+use super::{Memory, Registers, StatusFlags, RP2A03};
+
+/// A copy of everything the CPU can observe, taken with `RP2A03::snapshot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuSnapshot {
+    pub registers: Registers,
+    pub cycles: u32,
+    pub memory: Memory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    Pc,
+    Sp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    Carry,
+    Zero,
+    InterruptDisable,
+    DecimalMode,
+    BreakCommand,
+    Overflow,
+    Sign,
+}
+
+impl Flag {
+    const ALL: [Flag; 7] = [
+        Flag::Sign,
+        Flag::Overflow,
+        Flag::BreakCommand,
+        Flag::DecimalMode,
+        Flag::InterruptDisable,
+        Flag::Zero,
+        Flag::Carry,
+    ];
+
+    pub fn mask(self) -> StatusFlags {
+        match self {
+            Flag::Carry => StatusFlags::CARRY,
+            Flag::Zero => StatusFlags::ZERO,
+            Flag::InterruptDisable => StatusFlags::INTERRUPT_DISABLE,
+            Flag::DecimalMode => StatusFlags::DECIMAL_MODE,
+            Flag::BreakCommand => StatusFlags::BREAK_COMMAND,
+            Flag::Overflow => StatusFlags::OVERFLOW,
+            Flag::Sign => StatusFlags::SIGN,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Flag::Carry => 'C',
+            Flag::Zero => 'Z',
+            Flag::InterruptDisable => 'I',
+            Flag::DecimalMode => 'D',
+            Flag::BreakCommand => 'B',
+            Flag::Overflow => 'V',
+            Flag::Sign => 'N',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub old: u16,
+    pub new: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlagChange {
+    pub flag: Flag,
+    pub old: bool,
+    pub new: bool,
+}
+
+/// A run of consecutive bytes which all changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryRangeChange {
+    pub start: u16,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuDiff {
+    pub registers: Vec<RegisterChange>,
+    pub flags: Vec<FlagChange>,
+    pub memory: Vec<MemoryRangeChange>,
+    /// Old and new cycle count, if they differ.
+    pub cycles: Option<(u32, u32)>,
+}
+
+impl<B: Bus> RP2A03<B> {
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            registers: self.registers.clone(),
+            cycles: self.current_cycles,
+            memory: Memory::from_bus(&self.memory),
+        }
+    }
+}
+
+impl CpuSnapshot {
+    /// What changed between `self` and a later snapshot `after`.
+    pub fn diff(&self, after: &CpuSnapshot) -> CpuDiff {
+        let (old, new) = (&self.registers, &after.registers);
+
+        let registers = [
+            (Register::A, old.a as u16, new.a as u16),
+            (Register::X, old.x as u16, new.x as u16),
+            (Register::Y, old.y as u16, new.y as u16),
+            (Register::Pc, old.pc, new.pc),
+            (Register::Sp, old.sp as u16, new.sp as u16),
+        ].iter()
+            .filter(|&&(_, old, new)| old != new)
+            .map(|&(register, old, new)| RegisterChange { register, old, new })
+            .collect();
+
+        let flags = Flag::ALL
+            .iter()
+            .map(|&flag| FlagChange {
+                flag,
+                old: old.status().contains(flag.mask()),
+                new: new.status().contains(flag.mask()),
+            })
+            .filter(|change| change.old != change.new)
+            .collect();
+
+        let cycles = if self.cycles != after.cycles {
+            Some((self.cycles, after.cycles))
+        } else {
+            None
+        };
+
+        CpuDiff {
+            registers,
+            flags,
+            memory: memory_range_changes(&self.memory, &after.memory),
+            cycles,
+        }
+    }
+}
+
+fn memory_range_changes(before: &Memory, after: &Memory) -> Vec<MemoryRangeChange> {
+    let mut changes: Vec<MemoryRangeChange> = Vec::new();
+
+    for addr in 0..=0xffff {
+        let old = before.read(addr);
+        let new = after.read(addr);
+
+        if old == new {
+            continue;
+        }
+
+        let extends_last = match changes.last() {
+            Some(last) => last.start as usize + last.old.len() == addr as usize,
+            None => false,
+        };
+
+        if extends_last {
+            let last = changes.last_mut().unwrap();
+            last.old.push(old);
+            last.new.push(new);
+        } else {
+            let mut change = MemoryRangeChange {
+                start: addr,
+                old: Vec::new(),
+                new: Vec::new(),
+            };
+            change.old.push(old);
+            change.new.push(new);
+
+            changes.push(change);
+        }
+    }
+
+    changes
+}
+
+impl CpuDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.flags.is_empty() && self.memory.is_empty() &&
+            self.cycles.is_none()
+    }
+
+    pub fn changed_registers(&self) -> Vec<Register> {
+        self.registers.iter().map(|change| change.register).collect()
+    }
+
+    pub fn changed_flags(&self) -> Vec<Flag> {
+        self.flags.iter().map(|change| change.flag).collect()
+    }
+
+    /// True if memory is untouched and no register or flag other than the
+    /// given ones changed. The given ones are allowed, not required, to
+    /// change. Cycles are not taken into account.
+    pub fn only_changed(&self, registers: &[Register], flags: &[Flag]) -> bool {
+        self.memory.is_empty() &&
+            self.registers.iter().all(
+                |change| registers.contains(&change.register),
+            ) && self.flags.iter().all(|change| flags.contains(&change.flag))
+    }
+}
+
+impl fmt::Display for CpuDiff {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        let mut separate = |fmt: &mut fmt::Formatter| {
+            if first {
+                first = false;
+                Ok(())
+            } else {
+                write!(fmt, ", ")
+            }
+        };
+
+        for change in &self.registers {
+            separate(fmt)?;
+            match change.register {
+                Register::Pc => {
+                    write!(fmt, "PC: ${:04X} -> ${:04X}", change.old, change.new)?
+                }
+                register => {
+                    let name = match register {
+                        Register::A => "A",
+                        Register::X => "X",
+                        Register::Y => "Y",
+                        _ => "SP",
+                    };
+
+                    write!(fmt, "{}: ${:02X} -> ${:02X}", name, change.old, change.new)?
+                }
+            }
+        }
+
+        for change in &self.flags {
+            separate(fmt)?;
+            write!(
+                fmt,
+                "{}: {} -> {}",
+                change.flag.letter(),
+                change.old as u8,
+                change.new as u8
+            )?;
+        }
+
+        for change in &self.memory {
+            for (i, (old, new)) in change.old.iter().zip(change.new.iter()).enumerate() {
+                separate(fmt)?;
+                write!(
+                    fmt,
+                    "${:04X}: ${:02X} -> ${:02X}",
+                    change.start as usize + i,
+                    old,
+                    new
+                )?;
+            }
+        }
+
+        if let Some((old, new)) = self.cycles {
+            separate(fmt)?;
+            write!(fmt, "cycles: {} -> {}", old, new)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_of_identical_snapshots_is_empty() {
+        let cpu = RP2A03::new();
+
+        let diff = cpu.snapshot().diff(&cpu.snapshot());
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn diff_registers_and_flags() {
+        let mut cpu = RP2A03::new();
+        let before = cpu.snapshot();
+
+        cpu.registers.a = 0x42;
+        cpu.registers.pc = 0x1234;
+        cpu.registers.set_zero_flag(true);
+        cpu.registers.set_sign_flag(true);
+
+        let diff = before.diff(&cpu.snapshot());
+
+        assert_eq!(diff.changed_registers(), vec![Register::A, Register::Pc]);
+        assert_eq!(diff.changed_flags(), vec![Flag::Sign, Flag::Zero]);
+        assert_eq!(
+            diff.registers[0],
+            RegisterChange {
+                register: Register::A,
+                old: 0x00,
+                new: 0x42,
+            }
+        );
+        assert_eq!(
+            diff.flags[1],
+            FlagChange {
+                flag: Flag::Zero,
+                old: false,
+                new: true,
+            }
+        );
+        assert!(diff.memory.is_empty());
+        assert_eq!(diff.cycles, None);
+    }
+
+    #[test]
+    fn diff_coalesces_memory_ranges() {
+        let mut cpu = RP2A03::new();
+        cpu.memory.write(0x0011, 0x99);
+        let before = cpu.snapshot();
+
+        cpu.memory.write(0x0010, 0x01);
+        cpu.memory.write(0x0011, 0x02);
+        cpu.memory.write(0x0012, 0x03);
+        cpu.memory.write(0x0200, 0x04);
+
+        let diff = before.diff(&cpu.snapshot());
+
+        assert_eq!(
+            diff.memory,
+            vec![
+                MemoryRangeChange {
+                    start: 0x0010,
+                    old: vec![0x00, 0x99, 0x00],
+                    new: vec![0x01, 0x02, 0x03],
+                },
+                MemoryRangeChange {
+                    start: 0x0200,
+                    old: vec![0x00],
+                    new: vec![0x04],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_includes_last_address() {
+        let mut cpu = RP2A03::new();
+        let before = cpu.snapshot();
+
+        cpu.memory.write(0xffff, 0x01);
+
+        let diff = before.diff(&cpu.snapshot());
+
+        assert_eq!(diff.memory[0].start, 0xffff);
+    }
+
+    #[test]
+    fn diff_cycles() {
+        let mut cpu = RP2A03::new();
+        let before = cpu.snapshot();
+
+        cpu.memory.write(0, 0xea);
+        cpu.execute();
+
+        assert_eq!(before.diff(&cpu.snapshot()).cycles, Some((0, 2)));
+    }
+
+    #[test]
+    fn only_changed() {
+        let mut cpu = RP2A03::new();
+        let before = cpu.snapshot();
+
+        cpu.registers.a = 0x80;
+        cpu.registers.set_sign_flag(true);
+
+        let diff = before.diff(&cpu.snapshot());
+
+        assert!(diff.only_changed(&[Register::A], &[Flag::Zero, Flag::Sign]));
+        assert!(!diff.only_changed(&[Register::A], &[Flag::Zero]));
+        assert!(!diff.only_changed(&[Register::X], &[Flag::Sign]));
+    }
+
+    #[test]
+    fn only_changed_with_memory_change() {
+        let mut cpu = RP2A03::new();
+        let before = cpu.snapshot();
+
+        cpu.memory.write(0x0000, 0x01);
+
+        assert!(!before.diff(&cpu.snapshot()).only_changed(&[], &[]));
+    }
+
+    #[test]
+    fn display() {
+        let mut cpu = RP2A03::new();
+        let before = cpu.snapshot();
+
+        cpu.registers.a = 0x42;
+        cpu.registers.pc = 0x8000;
+        cpu.registers.set_carry_flag(true);
+        cpu.memory.write(0x0010, 0x01);
+        cpu.memory.write(0x0011, 0x02);
+
+        assert_eq!(
+            before.diff(&cpu.snapshot()).to_string(),
+            "A: $00 -> $42, PC: $0000 -> $8000, C: 0 -> 1, \
+             $0010: $00 -> $01, $0011: $00 -> $02"
+        );
+    }
+}
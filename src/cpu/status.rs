@@ -0,0 +1,272 @@
+use core::fmt;
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+/// The processor status register, better known as P.
+///
+/// Bit layout, from bit 7 down to bit 0, is `NV-BDIZC`. Bit 5 is not backed by
+/// anything on the real chip and always reads as one, and the B flag only
+/// exists in the copy of P pushed onto the stack.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusFlags(u8);
+
+impl StatusFlags {
+    pub const CARRY: StatusFlags = StatusFlags(1 << 0);
+    pub const ZERO: StatusFlags = StatusFlags(1 << 1);
+    pub const INTERRUPT_DISABLE: StatusFlags = StatusFlags(1 << 2);
+    pub const DECIMAL_MODE: StatusFlags = StatusFlags(1 << 3);
+    pub const BREAK_COMMAND: StatusFlags = StatusFlags(1 << 4);
+    pub const UNUSED: StatusFlags = StatusFlags(1 << 5);
+    pub const OVERFLOW: StatusFlags = StatusFlags(1 << 6);
+    pub const SIGN: StatusFlags = StatusFlags(1 << 7);
+
+    pub fn new() -> StatusFlags {
+        // The unused bit should always be logical one.
+        StatusFlags::UNUSED
+    }
+
+    /// Builds P from a raw byte. The unused bit is forced to one, every
+    /// other bit is taken as is.
+    pub fn from_bits(bits: u8) -> StatusFlags {
+        StatusFlags(bits | StatusFlags::UNUSED.0)
+    }
+
+    #[inline]
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// The byte written to the stack by PHP and BRK (`break_command` is
+    /// true) or by an IRQ/NMI (`break_command` is false).
+    pub fn to_stack_byte(self, break_command: bool) -> u8 {
+        let bits = (self.0 & !StatusFlags::BREAK_COMMAND.0) | StatusFlags::UNUSED.0;
+
+        if break_command {
+            bits | StatusFlags::BREAK_COMMAND.0
+        } else {
+            bits
+        }
+    }
+
+    /// P as restored by PLP and RTI: bits 4 and 5 of the pulled byte are
+    /// ignored.
+    pub fn from_stack_byte(byte: u8) -> StatusFlags {
+        StatusFlags::from_bits(byte & !StatusFlags::BREAK_COMMAND.0)
+    }
+
+    #[inline]
+    pub fn contains(self, other: StatusFlags) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, other: StatusFlags) {
+        self.0 |= other.0;
+    }
+
+    #[inline]
+    pub fn remove(&mut self, other: StatusFlags) {
+        self.0 &= !other.0;
+        self.0 |= StatusFlags::UNUSED.0;
+    }
+
+    #[inline]
+    pub fn set(&mut self, other: StatusFlags, flag: bool) {
+        if flag {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl Default for StatusFlags {
+    fn default() -> StatusFlags {
+        StatusFlags::new()
+    }
+}
+
+impl From<u8> for StatusFlags {
+    fn from(bits: u8) -> StatusFlags {
+        StatusFlags::from_bits(bits)
+    }
+}
+
+impl From<StatusFlags> for u8 {
+    fn from(flags: StatusFlags) -> u8 {
+        flags.bits()
+    }
+}
+
+impl BitOr for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitor(self, rhs: StatusFlags) -> StatusFlags {
+        StatusFlags::from_bits(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitand(self, rhs: StatusFlags) -> StatusFlags {
+        StatusFlags::from_bits(self.0 & rhs.0)
+    }
+}
+
+impl BitXor for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitxor(self, rhs: StatusFlags) -> StatusFlags {
+        StatusFlags::from_bits(self.0 ^ rhs.0)
+    }
+}
+
+impl Not for StatusFlags {
+    type Output = StatusFlags;
+
+    fn not(self) -> StatusFlags {
+        StatusFlags::from_bits(!self.0)
+    }
+}
+
+impl BitOrAssign for StatusFlags {
+    fn bitor_assign(&mut self, rhs: StatusFlags) {
+        *self = *self | rhs;
+    }
+}
+
+impl BitAndAssign for StatusFlags {
+    fn bitand_assign(&mut self, rhs: StatusFlags) {
+        *self = *self & rhs;
+    }
+}
+
+impl BitXorAssign for StatusFlags {
+    fn bitxor_assign(&mut self, rhs: StatusFlags) {
+        *self = *self ^ rhs;
+    }
+}
+
+impl fmt::Display for StatusFlags {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [(char, char); 8] = [
+            ('N', 'n'),
+            ('V', 'v'),
+            ('-', '-'),
+            ('B', 'b'),
+            ('D', 'd'),
+            ('I', 'i'),
+            ('Z', 'z'),
+            ('C', 'c'),
+        ];
+
+        for (i, &(set, clear)) in NAMES.iter().enumerate() {
+            let bit_no = 7 - i;
+            let c = if (self.0 >> bit_no) & 1 == 1 { set } else { clear };
+
+            fmt::Write::write_char(fmt, c)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for StatusFlags {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("StatusFlags")
+            .field("p", &self.0)
+            .field("negative", &self.contains(StatusFlags::SIGN))
+            .field("overflow", &self.contains(StatusFlags::OVERFLOW))
+            .field("break command", &self.contains(StatusFlags::BREAK_COMMAND))
+            .field("decimal mode", &self.contains(StatusFlags::DECIMAL_MODE))
+            .field(
+                "interrupt disable",
+                &self.contains(StatusFlags::INTERRUPT_DISABLE),
+            )
+            .field("zero", &self.contains(StatusFlags::ZERO))
+            .field("carry", &self.contains(StatusFlags::CARRY))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_has_only_unused_bit_set() {
+        assert_eq!(StatusFlags::new().bits(), 0b00100000);
+    }
+
+    #[test]
+    fn from_bits_forces_unused_bit() {
+        assert_eq!(StatusFlags::from_bits(0b00000000).bits(), 0b00100000);
+        assert_eq!(StatusFlags::from_bits(0b11011111).bits(), 0b11111111);
+    }
+
+    #[test]
+    fn to_stack_byte_with_break_command() {
+        let flags = StatusFlags::CARRY | StatusFlags::SIGN;
+
+        assert_eq!(flags.to_stack_byte(true), 0b10110001);
+    }
+
+    #[test]
+    fn to_stack_byte_without_break_command() {
+        let flags = StatusFlags::from_bits(0b11111111);
+
+        assert_eq!(flags.to_stack_byte(false), 0b11101111);
+    }
+
+    #[test]
+    fn from_stack_byte_ignores_bit_4_and_5() {
+        assert_eq!(StatusFlags::from_stack_byte(0b00010000), StatusFlags::new());
+        assert_eq!(StatusFlags::from_stack_byte(0b11001111).bits(), 0b11101111);
+    }
+
+    #[test]
+    fn push_then_pull_keeps_flags() {
+        let flags = StatusFlags::OVERFLOW | StatusFlags::ZERO | StatusFlags::DECIMAL_MODE;
+
+        assert_eq!(StatusFlags::from_stack_byte(flags.to_stack_byte(true)), flags);
+    }
+
+    #[test]
+    fn set_and_remove_keep_unused_bit() {
+        let mut flags = StatusFlags::new();
+
+        flags.set(StatusFlags::CARRY, true);
+        assert!(flags.contains(StatusFlags::CARRY));
+
+        flags.set(StatusFlags::CARRY, false);
+        flags.remove(StatusFlags::UNUSED);
+        assert_eq!(flags, StatusFlags::new());
+    }
+
+    #[test]
+    fn bit_operations() {
+        let a = StatusFlags::CARRY | StatusFlags::ZERO;
+        let b = StatusFlags::ZERO | StatusFlags::SIGN;
+
+        assert_eq!((a & b).bits(), 0b00100010);
+        assert_eq!((a ^ b).bits(), 0b10100001);
+        assert_eq!((!a).bits(), 0b11111100);
+    }
+
+    #[test]
+    fn display_all_clear() {
+        assert_eq!(StatusFlags::new().to_string(), "nv-bdizc");
+    }
+
+    #[test]
+    fn display_all_set() {
+        assert_eq!(StatusFlags::from_bits(0xff).to_string(), "NV-BDIZC");
+    }
+
+    #[test]
+    fn display_mixed() {
+        let flags = StatusFlags::SIGN | StatusFlags::INTERRUPT_DISABLE | StatusFlags::CARRY;
+
+        assert_eq!(flags.to_string(), "Nv-bdIzC");
+    }
+}
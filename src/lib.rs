@@ -0,0 +1,34 @@
+//! A NES emulator core.
+//!
+//! The CPU talks to the rest of the console through the `Bus` trait, so it
+//! can be driven by a plain 64 KB `Memory` in tests or by a full console.
+//! ROM images are parsed by the `rom` module, and the boards of their
+//! cartridges are emulated by the `mapper` module.
+//!
+//! Without the default `std` feature the crate is `no_std` and only the CPU
+//! core is built. The `alloc` feature adds back the parts which need `Vec`.
+
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+// The original ROM parser tests match on a single `IResult` variant.
+#![cfg_attr(test, allow(clippy::single_match))]
+
+#[cfg(any(feature = "std", test))]
+extern crate core;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate nom;
+
+pub mod bus;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod mapper;
+pub mod opcode;
+#[cfg(feature = "std")]
+pub mod rom;
+
+pub use bus::Bus;
+pub use cpu::{Memory, Registers, StatusFlags, RP2A03};
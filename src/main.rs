@@ -1,10 +1,169 @@
-#[macro_use]
-extern crate nom;
+extern crate rustnes;
 
-mod cpu;
-mod opcode;
-mod rom;
+use std::env;
+use std::fs;
+use std::process;
+
+use rustnes::rom::{self, Cartridge, Database, Mirroring, TvSystem};
+
+const USAGE: &str = "usage: rustnes <rom>
+       rustnes header <rom> [--nes2] [--fix-from-db] [--output <file>] [<field>=<value>...]
+
+header fields:
+    mapper=<number>
+    submapper=<number>
+    mirroring=horizontal|vertical|four-screen
+    battery=true|false
+    tv=ntsc|pal
+
+environment:
+    RUSTNES_DB=<file>   header database correcting the headers of known roms,
+                        written out by `header` only with --fix-from-db";
 
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.first().map(|arg| &arg[..]) {
+        Some("header") => edit_header(&args[1..]),
+        Some(path) if args.len() == 1 => print_info(path),
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+/// A patch next to the ROM, such as `game.ips` for `game.nes`, is applied.
+fn load(path: &str) -> Result<Cartridge, String> {
+    let (data, patch) = rom::read_patched(path).map_err(|e| format!("{}: {}", path, e))?;
+    if let Some(patch) = patch {
+        eprintln!("{}: applied patch {}", path, patch.display());
+    }
+
+    let mut cartridge = parse(path, &data)?;
+    apply_database(path, &mut cartridge)?;
+
+    Ok(cartridge)
+}
+
+/// Archaic headers are accepted, so their junk can be fixed with `header`.
+fn parse(path: &str, data: &[u8]) -> Result<Cartridge, String> {
+    let (cartridge, warnings) =
+        Cartridge::from_bytes_lenient(data).map_err(|e| format!("{}: {}", path, e))?;
+
+    for warning in warnings {
+        eprintln!("{}: warning: {}", path, warning);
+    }
+
+    Ok(cartridge)
+}
+
+fn apply_database(path: &str, cartridge: &mut Cartridge) -> Result<(), String> {
+    let database_path = match env::var_os("RUSTNES_DB") {
+        Some(database_path) => database_path,
+        None => return Ok(()),
+    };
+    let database = Database::from_path(&database_path)
+        .map_err(|e| format!("{}: {}", database_path.to_string_lossy(), e))?;
+
+    for discrepancy in cartridge.apply_database(&database).unwrap_or_default() {
+        eprintln!("{}: warning: {}, using the database", path, discrepancy);
+    }
+
+    Ok(())
+}
+
+fn print_info(path: &str) -> Result<(), String> {
+    let cartridge = load(path)?;
+
+    println!("mapper:      {}", cartridge.mapper_number());
+    if let Some(submapper) = cartridge.header().submapper() {
+        println!("submapper:   {}", submapper);
+    }
+    println!("mirroring:   {:?}", cartridge.mirroring());
+    println!("battery:     {}", cartridge.has_battery());
+    println!("tv system:   {:?}", cartridge.tv_system());
+    println!("prg rom:     {} bytes", cartridge.prg_rom().len());
+    println!("chr rom:     {} bytes", cartridge.chr_rom().len());
+    println!("prg ram:     {} bytes", cartridge.prg_ram_bytes());
+    println!("crc32:       {:08x}", cartridge.crc32());
+    let sha1: String = cartridge.sha1().iter().map(|byte| format!("{:02x}", byte)).collect();
+    println!("sha1:        {}", sha1);
+    if let Some(nes2) = cartridge.header().nes2() {
+        println!("prg nvram:   {} bytes", nes2.prg_nvram_bytes());
+        println!("chr ram:     {} bytes", nes2.chr_ram_bytes());
+        println!("chr nvram:   {} bytes", nes2.chr_nvram_bytes());
+        println!("timing:      {:?}", nes2.timing());
+        println!("console:     {:?}", cartridge.header().console_type());
+    }
+
+    Ok(())
+}
+
+/// Rewrites the header of a ROM, in place unless `--output` is given. The
+/// ROM is read as is, without its sibling patch, which would otherwise be
+/// baked into the output, and the database is only applied when asked for.
+fn edit_header(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or_else(|| USAGE.to_string())?;
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut cartridge = parse(path, &data)?;
+    let mut output = path.clone();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--nes2" => cartridge.header_mut().upgrade_to_nes2(),
+            "--fix-from-db" => apply_database(path, &mut cartridge)?,
+            "--output" | "-o" => {
+                output = args.next().ok_or_else(|| USAGE.to_string())?.clone();
+            }
+            field => set_header_field(&mut cartridge, field)?,
+        }
+    }
+
+    // The header of a UNIF image is written as iNES, so the input is only
+    // overwritten when it already is an iNES file.
+    if output == *path && !data.starts_with(b"NES\x1a") {
+        return Err(format!("{}: not an iNES file, give --output to convert it", path));
+    }
+
+    fs::write(&output, cartridge.to_bytes()).map_err(|e| format!("{}: {}", output, e))
+}
+
+fn set_header_field(cartridge: &mut Cartridge, field: &str) -> Result<(), String> {
+    let mut parts = field.splitn(2, '=');
+    let (name, value) = match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) => (name, value),
+        _ => return Err(format!("expected <field>=<value>, got `{}`", field)),
+    };
+    let invalid = || format!("invalid value for {}: `{}`", name, value);
+    let header = cartridge.header_mut();
+
+    match name {
+        "mapper" => match value.parse() {
+            Ok(mapper) if mapper <= 0x0fff => header.set_mapper_number(mapper),
+            _ => return Err(invalid()),
+        },
+        "submapper" => match value.parse() {
+            Ok(submapper) if submapper <= 0x0f => header.set_submapper(submapper),
+            _ => return Err(invalid()),
+        },
+        "mirroring" => header.set_mirroring(match value {
+            "horizontal" => Mirroring::Horizontal,
+            "vertical" => Mirroring::Vertical,
+            "four-screen" => Mirroring::FourScreen,
+            _ => return Err(invalid()),
+        }),
+        "battery" => header.set_battery(value.parse().map_err(|_| invalid())?),
+        "tv" => header.set_tv_system(match value {
+            "ntsc" => TvSystem::NTSC,
+            "pal" => TvSystem::PAL,
+            _ => return Err(invalid()),
+        }),
+        _ => return Err(format!("unknown header field `{}`", name)),
+    }
+
+    Ok(())
 }
@@ -0,0 +1,81 @@
+use rom::Cartridge;
+
+use super::{has_bus_conflicts, read_bank, Chr, Mapper, Mirroring};
+
+/// Mapper 7: a switchable 32 KB PRG bank and single-screen mirroring of
+/// either nametable, with 8 KB of CHR RAM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Axrom {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    bus_conflicts: bool,
+    bank: u8,
+}
+
+impl Axrom {
+    pub fn new(cartridge: &Cartridge) -> Axrom {
+        Axrom {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            chr: Chr::new(cartridge),
+            bus_conflicts: has_bus_conflicts(cartridge),
+            bank: 0,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xffff => {
+                read_bank(&self.prg_rom, 0x8000, self.bank as usize & 0x07, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.bank = if self.bus_conflicts {
+                val & self.cpu_peek(addr)
+            } else {
+                val
+            };
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, 0, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.bank & 0x10 != 0 {
+            Mirroring::SingleScreenUpper
+        } else {
+            Mirroring::SingleScreenLower
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn banks_and_mirroring() {
+        let mut axrom = Axrom::new(&cartridge(7, 0, &banked_rom(0x8000, 8), &[]));
+        assert_eq!(axrom.cpu_peek(0xfffc), 0);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+
+        axrom.cpu_write(0x8000, 0x15);
+
+        assert_eq!(axrom.cpu_peek(0x8000), 5);
+        assert_eq!(axrom.cpu_peek(0xffff), 5);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+    }
+}
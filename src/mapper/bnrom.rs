@@ -0,0 +1,174 @@
+use rom::Cartridge;
+
+use super::{prg_ram, read_bank, Chr, Mapper, Mirroring};
+
+/// Mapper 34 as BNROM: a switchable 32 KB PRG bank and 8 KB of CHR RAM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bnrom {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    prg_bank: u8,
+}
+
+impl Bnrom {
+    pub fn new(cartridge: &Cartridge) -> Bnrom {
+        Bnrom {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Bnrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xffff => {
+                read_bank(&self.prg_rom, 0x8000, self.prg_bank as usize, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.prg_bank = val;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, 0, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+/// Mapper 34 as AVE's NINA-001: a 32 KB PRG bank and two 4 KB CHR banks,
+/// with registers at the top of its 8 KB of PRG RAM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nina001 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    prg_bank: u8,
+    chr_banks: [u8; 2],
+}
+
+impl Nina001 {
+    /// NES 2.0 submapper 1 is NINA-001 and 2 is BNROM. Otherwise only
+    /// NINA-001 has CHR ROM, and more than 8 KB of it.
+    pub fn is_nina001(cartridge: &Cartridge) -> bool {
+        match cartridge.header().submapper() {
+            Some(1) => true,
+            Some(2) => false,
+            _ => cartridge.chr_rom().len() > 0x2000,
+        }
+    }
+
+    pub fn new(cartridge: &Cartridge) -> Nina001 {
+        Nina001 {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            prg_bank: 0,
+            chr_banks: [0, 1],
+        }
+    }
+}
+
+impl Mapper for Nina001 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7fff => read_bank(&self.prg_ram, 0x2000, 0, addr as usize),
+            0x8000..=0xffff => {
+                read_bank(&self.prg_rom, 0x8000, self.prg_bank as usize, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        // The registers are written through to the RAM underneath.
+        if let 0x6000..=0x7fff = addr {
+            if !self.prg_ram.is_empty() {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = val;
+            }
+        }
+
+        match addr {
+            0x7ffd => self.prg_bank = val & 0x01,
+            0x7ffe => self.chr_banks[0] = val & 0x0f,
+            0x7fff => self.chr_banks[1] = val & 0x0f,
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        let bank = self.chr_banks[(addr as usize >> 12) & 0x01];
+        self.chr.read(0x1000, bank as usize, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let bank = self.chr_banks[(addr as usize >> 12) & 0x01];
+        self.chr.write(0x1000, bank as usize, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn bnrom_banks() {
+        let cartridge = cartridge(34, 0, &banked_rom(0x8000, 4), &[]);
+        assert!(!Nina001::is_nina001(&cartridge));
+        let mut bnrom = Bnrom::new(&cartridge);
+
+        bnrom.cpu_write(0x8000, 3);
+
+        assert_eq!(bnrom.cpu_peek(0x8000), 3);
+    }
+
+    #[test]
+    fn nina001_banks() {
+        let cartridge = cartridge(34, 0, &banked_rom(0x8000, 2), &banked_rom(0x1000, 8));
+        assert!(Nina001::is_nina001(&cartridge));
+        let mut nina = Nina001::new(&cartridge);
+
+        nina.cpu_write(0x7ffd, 1);
+        nina.cpu_write(0x7ffe, 5);
+        nina.cpu_write(0x7fff, 6);
+
+        assert_eq!(nina.cpu_peek(0x8000), 1);
+        assert_eq!(nina.ppu_peek(0x0000), 5);
+        assert_eq!(nina.ppu_peek(0x1000), 6);
+        assert_eq!(nina.cpu_peek(0x7ffe), 5);
+    }
+
+    #[test]
+    fn nina001_writes_to_rom_are_ignored() {
+        let cartridge = cartridge(34, 0, &banked_rom(0x8000, 2), &banked_rom(0x1000, 8));
+        let mut nina = Nina001::new(&cartridge);
+
+        nina.cpu_write(0x8000, 1);
+
+        assert_eq!(nina.cpu_peek(0x8000), 0);
+    }
+}
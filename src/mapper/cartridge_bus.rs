@@ -0,0 +1,112 @@
+use bus::Bus;
+use cpu::{RamInit, RAM_SIZE};
+
+use super::Mapper;
+
+/// The CPU's side of a console with only RAM and a cartridge: 2 KB of RAM
+/// mirrored up to $1FFF, and the board from $4020. The PPU and APU
+/// registers in between read as 0, and writes to the PPU's only reach
+/// boards which snoop on them.
+///
+/// The board's IRQ line is wired to the CPU, and its M2 pin to the CPU
+/// clock.
+pub struct CartridgeBus {
+    ram: [u8; RAM_SIZE],
+    mapper: Box<dyn Mapper>,
+}
+
+impl CartridgeBus {
+    pub fn new(mapper: Box<dyn Mapper>) -> CartridgeBus {
+        CartridgeBus {
+            ram: [0x00; RAM_SIZE],
+            mapper,
+        }
+    }
+
+    pub fn mapper(&self) -> &dyn Mapper {
+        &*self.mapper
+    }
+
+    /// For the PPU side of the board, and for clocking it.
+    pub fn mapper_mut(&mut self) -> &mut dyn Mapper {
+        &mut *self.mapper
+    }
+}
+
+impl Bus for CartridgeBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1fff => self.ram[addr as usize % RAM_SIZE],
+            0x4020..=0xffff => self.mapper.cpu_read(addr),
+            _ => 0x00,
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1fff => self.ram[addr as usize % RAM_SIZE] = val,
+            0x2000..=0x3fff => self.mapper.ppu_register_write(addr & 0x2007, val),
+            0x4020..=0xffff => self.mapper.cpu_write(addr, val),
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1fff => self.ram[addr as usize % RAM_SIZE],
+            0x4020..=0xffff => self.mapper.cpu_peek(addr),
+            _ => 0x00,
+        }
+    }
+
+    fn init_ram(&mut self, ram_init: &RamInit) {
+        ram_init.fill(&mut self.ram);
+    }
+
+    fn irq(&self) -> bool {
+        self.mapper.irq()
+    }
+
+    // There is no PPU here to step between the cycles, so a board waiting
+    // for its fetches to pause, like the MMC5 at the end of a frame, sees
+    // one in every instruction. Drive the PPU side through `mapper_mut`
+    // and tick one cycle at a time to avoid that.
+    fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.mapper.cpu_clock();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::cartridge;
+    use mapper::Nrom;
+
+    #[test]
+    fn ram_is_mirrored() {
+        let nrom = Nrom::new(&cartridge(0, 0, &[0x00; 0x4000], &[]));
+        let mut bus = CartridgeBus::new(Box::new(nrom));
+
+        bus.write(0x0801, 0x12);
+
+        assert_eq!(bus.read(0x1801), 0x12);
+        assert_eq!(bus.peek(0x0001), 0x12);
+    }
+
+    #[test]
+    fn cartridge_space() {
+        let mut prg_rom = vec![0x00; 0x4000];
+        prg_rom[0x3ffc] = 0x34;
+        let nrom = Nrom::new(&cartridge(0, 0, &prg_rom, &[]));
+        let mut bus = CartridgeBus::new(Box::new(nrom));
+
+        bus.write(0x6000, 0x56);
+
+        assert_eq!(bus.read(0xfffc), 0x34);
+        assert_eq!(bus.read(0x6000), 0x56);
+        assert_eq!(bus.read(0x2002), 0x00);
+    }
+}
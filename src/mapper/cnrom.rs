@@ -0,0 +1,88 @@
+use rom::Cartridge;
+
+use super::{has_bus_conflicts, read_bank, Chr, Mapper, Mirroring};
+
+/// Mapper 3: 16 or 32 KB of PRG ROM as on NROM, and a switchable 8 KB CHR
+/// bank.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cnrom {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(cartridge: &Cartridge) -> Cnrom {
+        Cnrom {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            bus_conflicts: has_bus_conflicts(cartridge),
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xffff => read_bank(&self.prg_rom, 0x8000, 0, addr as usize),
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.chr_bank = if self.bus_conflicts {
+                val & self.cpu_peek(addr)
+            } else {
+                val
+            };
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, self.chr_bank as usize, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, self.chr_bank as usize, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn chr_banks() {
+        let mut cnrom = Cnrom::new(&cartridge(3, 0, &[0xff; 0x8000], &banked_rom(0x2000, 4)));
+        assert_eq!(cnrom.ppu_peek(0x0000), 0);
+
+        cnrom.cpu_write(0x8000, 2);
+
+        assert_eq!(cnrom.ppu_peek(0x0000), 2);
+        assert_eq!(cnrom.ppu_peek(0x1fff), 2);
+    }
+
+    #[test]
+    fn bus_conflicts() {
+        let mut prg_rom = vec![0xff; 0x8000];
+        prg_rom[0x0000] = 0x01;
+        let mut cartridge = cartridge(3, 0, &prg_rom, &banked_rom(0x2000, 4));
+        cartridge.header_mut().set_submapper(2);
+        let mut cnrom = Cnrom::new(&cartridge);
+
+        cnrom.cpu_write(0x8000, 3);
+
+        assert_eq!(cnrom.ppu_peek(0x0000), 1);
+    }
+}
@@ -0,0 +1,74 @@
+use rom::Cartridge;
+
+use super::{read_bank, Chr, Mapper, Mirroring};
+
+/// Mapper 11: Color Dreams' unlicensed board, a 32 KB PRG bank and an 8 KB
+/// CHR bank picked by one register like GxROM, with the fields swapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorDreams {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl ColorDreams {
+    pub fn new(cartridge: &Cartridge) -> ColorDreams {
+        ColorDreams {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for ColorDreams {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xffff => {
+                read_bank(&self.prg_rom, 0x8000, self.prg_bank as usize, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.prg_bank = val & 0x03;
+            self.chr_bank = val >> 4;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, self.chr_bank as usize, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, self.chr_bank as usize, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn banks() {
+        let cartridge = cartridge(11, 0, &banked_rom(0x8000, 4), &banked_rom(0x2000, 16));
+        let mut color_dreams = ColorDreams::new(&cartridge);
+
+        color_dreams.cpu_write(0x8000, 0xc2);
+
+        assert_eq!(color_dreams.cpu_peek(0xffff), 2);
+        assert_eq!(color_dreams.ppu_peek(0x1fff), 12);
+    }
+}
@@ -0,0 +1,74 @@
+use rom::Cartridge;
+
+use super::{read_bank, Chr, Mapper, Mirroring};
+
+/// Mapper 66: a switchable 32 KB PRG bank and 8 KB CHR bank, both picked by
+/// one register.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gxrom {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl Gxrom {
+    pub fn new(cartridge: &Cartridge) -> Gxrom {
+        Gxrom {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Gxrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xffff => {
+                read_bank(&self.prg_rom, 0x8000, self.prg_bank as usize, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.prg_bank = (val >> 4) & 0x03;
+            self.chr_bank = val & 0x03;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, self.chr_bank as usize, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, self.chr_bank as usize, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn banks() {
+        let cartridge = cartridge(66, 0, &banked_rom(0x8000, 4), &banked_rom(0x2000, 4));
+        let mut gxrom = Gxrom::new(&cartridge);
+
+        gxrom.cpu_write(0x8000, 0x21);
+
+        assert_eq!(gxrom.cpu_peek(0x8000), 2);
+        assert_eq!(gxrom.ppu_peek(0x0000), 1);
+    }
+}
@@ -0,0 +1,470 @@
+use rom::Cartridge;
+
+use super::{bank_index, prg_ram, Chr, Mapper, Mirroring};
+
+/// The boards MMC1 is wired in, as far as they differ to the software.
+///
+/// Boards with 8 KB of CHR RAM have spare CHR bank lines, which the bigger
+/// ones use to reach more PRG ROM and PRG RAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mmc1Board {
+    /// SNROM, SKROM, SLROM and the others: up to 256 KB of PRG ROM and 8 KB
+    /// of PRG RAM.
+    Standard,
+    /// 16 KB of PRG RAM, banked by bit 3 of the CHR bank.
+    Sorom,
+    /// 512 KB of PRG ROM, the 256 KB half picked by bit 4 of the CHR bank.
+    Surom,
+    /// SUROM with 32 KB of PRG RAM, banked by bits 2-3 of the CHR bank.
+    Sxrom,
+}
+
+impl Mmc1Board {
+    /// NES 2.0 submappers 1 to 3 name the board; otherwise it is told by the
+    /// sizes of PRG ROM and PRG RAM.
+    pub fn detect(cartridge: &Cartridge) -> Mmc1Board {
+        let header = cartridge.header();
+
+        match header.submapper() {
+            Some(1) => return Mmc1Board::Surom,
+            Some(2) => return Mmc1Board::Sorom,
+            Some(3) => return Mmc1Board::Sxrom,
+            _ => {}
+        }
+
+        let prg_ram_bytes = header.prg_ram_bytes() + header.prg_nvram_bytes();
+        if prg_ram_bytes >= 0x8000 {
+            Mmc1Board::Sxrom
+        } else if prg_ram_bytes >= 0x4000 {
+            Mmc1Board::Sorom
+        } else if cartridge.prg_rom().len() > 0x40000 {
+            Mmc1Board::Surom
+        } else {
+            Mmc1Board::Standard
+        }
+    }
+
+    fn has_prg_rom_a18(&self) -> bool {
+        *self == Mmc1Board::Surom || *self == Mmc1Board::Sxrom
+    }
+
+    // The 8 KB PRG RAM bank, from the CHR bank driving the spare lines.
+    fn prg_ram_bank(&self, chr_bank: u8) -> usize {
+        match *self {
+            Mmc1Board::Sorom => (chr_bank as usize >> 3) & 0x01,
+            Mmc1Board::Sxrom => (chr_bank as usize >> 2) & 0x03,
+            _ => 0,
+        }
+    }
+}
+
+/// Mapper 1: the MMC1, whose registers are loaded one bit per write through
+/// a serial port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mmc1 {
+    board: Mmc1Board,
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+
+    // PPU A12 of the last pattern table access, which picks the CHR bank
+    // register driving the spare lines in 4 KB mode.
+    chr_a12: bool,
+
+    cycle: u64,
+    last_write_cycle: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(cartridge: &Cartridge) -> Mmc1 {
+        Mmc1::with_board(cartridge, Mmc1Board::detect(cartridge))
+    }
+
+    pub fn with_board(cartridge: &Cartridge, board: Mmc1Board) -> Mmc1 {
+        Mmc1 {
+            board,
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            shift: 0,
+            shift_count: 0,
+            // The last bank is fixed at $C000 at power-on, so the reset
+            // vector can be found.
+            control: 0x0c,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            chr_a12: false,
+            cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    pub fn board(&self) -> Mmc1Board {
+        self.board
+    }
+
+    fn prg_mode(&self) -> u8 {
+        (self.control >> 2) & 0x03
+    }
+
+    fn chr_4k_mode(&self) -> bool {
+        self.control & 0x10 != 0
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+
+    // The CHR bank whose upper bits drive the spare lines.
+    fn line_chr_bank(&self) -> u8 {
+        if self.chr_4k_mode() && self.chr_a12 {
+            self.chr_bank_1
+        } else {
+            self.chr_bank_0
+        }
+    }
+
+    // The 16 KB bank at $8000 or $C000.
+    fn prg_rom_bank(&self, addr: u16) -> usize {
+        let outer = if self.board.has_prg_rom_a18() {
+            self.line_chr_bank() as usize & 0x10
+        } else {
+            0
+        };
+        let bank = self.prg_bank as usize & 0x0f;
+        let upper = addr >= 0xc000;
+
+        outer |
+            match self.prg_mode() {
+                0 | 1 => (bank & 0x0e) | upper as usize,
+                2 if upper => bank,
+                2 => 0,
+                _ if upper => 0x0f,
+                _ => bank,
+            }
+    }
+
+    fn prg_ram_index(&self, addr: u16) -> usize {
+        let bank = self.board.prg_ram_bank(self.line_chr_bank());
+
+        bank_index(self.prg_ram.len(), 0x2000, bank, addr as usize - 0x6000)
+    }
+
+    // The 4 KB CHR bank at $0000 or $1000.
+    fn chr_bank(&self, addr: u16) -> usize {
+        let upper = addr >= 0x1000;
+
+        if self.chr_4k_mode() {
+            if upper {
+                self.chr_bank_1 as usize
+            } else {
+                self.chr_bank_0 as usize
+            }
+        } else {
+            (self.chr_bank_0 as usize & 0x1e) | upper as usize
+        }
+    }
+
+    fn write_serial(&mut self, addr: u16, val: u8) {
+        if val & 0x80 != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= 0x0c;
+            return;
+        }
+
+        self.shift |= (val & 0x01) << self.shift_count;
+        self.shift_count += 1;
+
+        if self.shift_count == 5 {
+            match addr {
+                0x8000..=0x9fff => self.control = self.shift,
+                0xa000..=0xbfff => self.chr_bank_0 = self.shift,
+                0xc000..=0xdfff => self.chr_bank_1 = self.shift,
+                _ => self.prg_bank = self.shift,
+            }
+
+            self.shift = 0;
+            self.shift_count = 0;
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled() && !self.prg_ram.is_empty() => {
+                self.prg_ram[self.prg_ram_index(addr)]
+            }
+            0x8000..=0xffff if !self.prg_rom.is_empty() => {
+                let bank = self.prg_rom_bank(addr);
+                self.prg_rom[bank_index(self.prg_rom.len(), 0x4000, bank, addr as usize)]
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled() && !self.prg_ram.is_empty() => {
+                let index = self.prg_ram_index(addr);
+                self.prg_ram[index] = val;
+            }
+            0x8000..=0xffff => {
+                // The serial port only listens to the first of writes on
+                // consecutive cycles, such as the two of INC or ASL. A bus
+                // which clocks the board after the whole instruction hands
+                // both of those over on the same cycle.
+                let consecutive = match self.last_write_cycle {
+                    Some(cycle) => self.cycle.wrapping_sub(cycle) <= 1,
+                    None => false,
+                };
+                self.last_write_cycle = Some(self.cycle);
+
+                if !consecutive {
+                    self.write_serial(addr, val);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.cycle = self.cycle.wrapping_add(1);
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x1000, self.chr_bank(addr), addr as usize)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr_a12 = addr & 0x1000 != 0;
+
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr_a12 = addr & 0x1000 != 0;
+
+        let bank = self.chr_bank(addr);
+        self.chr.write(0x1000, bank, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    // Loads a register the way games do, one bit per write, a cycle apart.
+    fn write_register(mmc1: &mut Mmc1, addr: u16, val: u8) {
+        for bit in 0..5 {
+            mmc1.cpu_write(addr, val >> bit);
+            mmc1.cpu_clock();
+            mmc1.cpu_clock();
+        }
+    }
+
+    fn mmc1(prg_banks: usize) -> Mmc1 {
+        Mmc1::new(&cartridge(1, 0, &banked_rom(0x4000, prg_banks), &banked_rom(0x1000, 32)))
+    }
+
+    #[test]
+    fn power_on_fixes_last_bank() {
+        let mmc1 = mmc1(16);
+
+        assert_eq!(mmc1.cpu_peek(0x8000), 0);
+        assert_eq!(mmc1.cpu_peek(0xc000), 15);
+        assert_eq!(mmc1.board(), Mmc1Board::Standard);
+    }
+
+    #[test]
+    fn prg_mode_fix_last() {
+        let mut mmc1 = mmc1(16);
+
+        write_register(&mut mmc1, 0xe000, 0x05);
+
+        assert_eq!(mmc1.cpu_peek(0x8000), 5);
+        assert_eq!(mmc1.cpu_peek(0xffff), 15);
+    }
+
+    #[test]
+    fn prg_mode_fix_first() {
+        let mut mmc1 = mmc1(16);
+
+        write_register(&mut mmc1, 0x8000, 0x08);
+        write_register(&mut mmc1, 0xe000, 0x05);
+
+        assert_eq!(mmc1.cpu_peek(0x8000), 0);
+        assert_eq!(mmc1.cpu_peek(0xc000), 5);
+    }
+
+    #[test]
+    fn prg_mode_32k_ignores_low_bit() {
+        let mut mmc1 = mmc1(16);
+
+        write_register(&mut mmc1, 0x8000, 0x00);
+        write_register(&mut mmc1, 0xe000, 0x05);
+
+        assert_eq!(mmc1.cpu_peek(0x8000), 4);
+        assert_eq!(mmc1.cpu_peek(0xc000), 5);
+    }
+
+    #[test]
+    fn chr_modes() {
+        let mut mmc1 = mmc1(2);
+
+        write_register(&mut mmc1, 0xa000, 0x05);
+        write_register(&mut mmc1, 0xc000, 0x09);
+        assert_eq!(mmc1.ppu_peek(0x0000), 4);
+        assert_eq!(mmc1.ppu_peek(0x1000), 5);
+
+        write_register(&mut mmc1, 0x8000, 0x1c);
+        assert_eq!(mmc1.ppu_peek(0x0000), 5);
+        assert_eq!(mmc1.ppu_peek(0x1000), 9);
+    }
+
+    #[test]
+    fn mirroring_control() {
+        let mut mmc1 = mmc1(2);
+        let mut mirroring = |control| {
+            write_register(&mut mmc1, 0x8000, control);
+            mmc1.mirroring()
+        };
+
+        assert_eq!(mirroring(0x0c), Mirroring::SingleScreenLower);
+        assert_eq!(mirroring(0x0d), Mirroring::SingleScreenUpper);
+        assert_eq!(mirroring(0x0e), Mirroring::Vertical);
+        assert_eq!(mirroring(0x0f), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn reset_clears_shift_register_and_fixes_last_bank() {
+        let mut mmc1 = mmc1(16);
+        write_register(&mut mmc1, 0x8000, 0x00);
+
+        mmc1.cpu_write(0xe000, 0x01);
+        mmc1.cpu_clock();
+        mmc1.cpu_clock();
+        mmc1.cpu_write(0x8000, 0x80);
+        mmc1.cpu_clock();
+        mmc1.cpu_clock();
+        write_register(&mut mmc1, 0xe000, 0x02);
+
+        assert_eq!(mmc1.cpu_peek(0x8000), 2);
+        assert_eq!(mmc1.cpu_peek(0xc000), 15);
+    }
+
+    #[test]
+    fn consecutive_cycle_writes_are_ignored() {
+        let mut mmc1 = mmc1(16);
+
+        // The reset after a bank write, as done by INC on $FFxx.
+        mmc1.cpu_write(0xe000, 0x01);
+        mmc1.cpu_clock();
+        mmc1.cpu_write(0xe000, 0x80);
+        mmc1.cpu_clock();
+        mmc1.cpu_clock();
+        for _ in 0..4 {
+            mmc1.cpu_write(0xe000, 0x00);
+            mmc1.cpu_clock();
+            mmc1.cpu_clock();
+        }
+
+        assert_eq!(mmc1.cpu_peek(0x8000), 1);
+    }
+
+    #[test]
+    fn prg_ram_enable() {
+        let mut mmc1 = mmc1(2);
+        mmc1.cpu_write(0x6000, 0x12);
+
+        write_register(&mut mmc1, 0xe000, 0x10);
+        mmc1.cpu_write(0x6000, 0x34);
+        assert_eq!(mmc1.cpu_peek(0x6000), 0x00);
+
+        write_register(&mut mmc1, 0xe000, 0x00);
+        assert_eq!(mmc1.cpu_peek(0x6000), 0x12);
+    }
+
+    #[test]
+    fn surom_selects_prg_half_with_chr_bank() {
+        let mut mmc1 = Mmc1::new(&cartridge(1, 0, &banked_rom(0x4000, 32), &[]));
+        assert_eq!(mmc1.board(), Mmc1Board::Surom);
+
+        write_register(&mut mmc1, 0xe000, 0x03);
+        assert_eq!(mmc1.cpu_peek(0x8000), 3);
+        assert_eq!(mmc1.cpu_peek(0xc000), 15);
+
+        write_register(&mut mmc1, 0xa000, 0x10);
+        assert_eq!(mmc1.cpu_peek(0x8000), 19);
+        assert_eq!(mmc1.cpu_peek(0xc000), 31);
+    }
+
+    #[test]
+    fn surom_4k_chr_mode_follows_ppu_a12() {
+        let mut mmc1 = Mmc1::new(&cartridge(1, 0, &banked_rom(0x4000, 32), &[]));
+        write_register(&mut mmc1, 0x8000, 0x1c);
+        write_register(&mut mmc1, 0xc000, 0x10);
+
+        mmc1.ppu_read(0x0000);
+        assert_eq!(mmc1.cpu_peek(0xc000), 15);
+
+        mmc1.ppu_read(0x1000);
+        assert_eq!(mmc1.cpu_peek(0xc000), 31);
+    }
+
+    #[test]
+    fn sxrom_banks_prg_ram() {
+        let cartridge = cartridge(1, 0, &banked_rom(0x4000, 32), &[]);
+        let mut mmc1 = Mmc1::with_board(&cartridge, Mmc1Board::Sxrom);
+        mmc1.prg_ram = vec![0x00; 0x8000];
+
+        for bank in 0..4 {
+            write_register(&mut mmc1, 0xa000, bank << 2);
+            mmc1.cpu_write(0x6000, bank);
+        }
+        write_register(&mut mmc1, 0xa000, 0x08);
+
+        assert_eq!(mmc1.cpu_peek(0x6000), 2);
+        assert_eq!(mmc1.prg_ram[0x6000], 3);
+    }
+
+    #[test]
+    fn sorom_banks_prg_ram() {
+        let cartridge = cartridge(1, 0, &banked_rom(0x4000, 16), &[]);
+        let mut mmc1 = Mmc1::with_board(&cartridge, Mmc1Board::Sorom);
+        mmc1.prg_ram = vec![0x00; 0x4000];
+
+        write_register(&mut mmc1, 0xa000, 0x08);
+        mmc1.cpu_write(0x6000, 0x55);
+
+        assert_eq!(mmc1.prg_ram[0x2000], 0x55);
+    }
+
+    #[test]
+    fn board_from_submapper() {
+        let mut cartridge = cartridge(1, 0, &banked_rom(0x4000, 16), &[]);
+        cartridge.header_mut().set_submapper(2);
+
+        assert_eq!(Mmc1Board::detect(&cartridge), Mmc1Board::Sorom);
+    }
+}
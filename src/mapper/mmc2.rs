@@ -0,0 +1,305 @@
+use rom::Cartridge;
+
+use super::{prg_ram, read_bank, Chr, Mapper, Mirroring};
+
+/// The CHR side the MMC2 and MMC4 share: two 4 KB banks, each with a latch
+/// which picks between two bank registers and flips when the PPU fetches
+/// tile $FD or $FE from its pattern table.
+#[derive(Debug, Clone, PartialEq)]
+struct LatchedChr {
+    chr: Chr,
+    // The $FD and $FE banks of each pattern table.
+    banks: [[u8; 2]; 2],
+    // Whether each pattern table's latch holds $FE.
+    latches: [bool; 2],
+    // The MMC2 only sees the first fetch of the tiles in the lower table.
+    exact_lower_latch: bool,
+    mirroring: Mirroring,
+}
+
+impl LatchedChr {
+    fn new(cartridge: &Cartridge, exact_lower_latch: bool) -> LatchedChr {
+        LatchedChr {
+            chr: Chr::new(cartridge),
+            banks: [[0, 0], [0, 0]],
+            latches: [true, true],
+            exact_lower_latch,
+            mirroring: Mirroring::Vertical,
+        }
+    }
+
+    fn bank(&self, addr: u16) -> usize {
+        let table = (addr >> 12) as usize & 0x01;
+
+        self.banks[table][self.latches[table] as usize] as usize
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x1000, self.bank(addr), addr as usize)
+    }
+
+    // The fetch itself still comes from the old bank; the latch flips
+    // after.
+    fn read(&mut self, addr: u16) -> u8 {
+        let val = self.peek(addr);
+
+        let table = (addr >> 12) as usize & 0x01;
+        let tile = addr & 0x0ff8;
+        let exact = table == 0 && self.exact_lower_latch;
+        match tile {
+            0x0fd8 if !exact || addr == 0x0fd8 => self.latches[table] = false,
+            0x0fe8 if !exact || addr == 0x0fe8 => self.latches[table] = true,
+            _ => {}
+        }
+
+        val
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        let bank = self.bank(addr);
+        self.chr.write(0x1000, bank, addr as usize, val);
+    }
+
+    // $B000-$FFFF.
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xb000..=0xefff => {
+                let register = (addr as usize - 0xb000) >> 12;
+                self.banks[register / 2][register % 2] = val & 0x1f;
+            }
+            _ => {
+                self.mirroring = if val & 0x01 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+        }
+    }
+}
+
+/// Mapper 9: the MMC2 of Punch-Out!!, with an 8 KB PRG bank and the last
+/// three fixed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mmc2 {
+    prg_rom: Vec<u8>,
+    prg_bank: u8,
+    chr: LatchedChr,
+}
+
+impl Mmc2 {
+    pub fn new(cartridge: &Cartridge) -> Mmc2 {
+        Mmc2 {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_bank: 0,
+            chr: LatchedChr::new(cartridge, true),
+        }
+    }
+}
+
+impl Mapper for Mmc2 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        let last = self.prg_rom.len() / 0x2000;
+        let prg_bank = self.prg_bank as usize;
+
+        match addr {
+            0x8000..=0x9fff => read_bank(&self.prg_rom, 0x2000, prg_bank, addr as usize),
+            0xa000..=0xffff => {
+                let bank = (last + ((addr as usize - 0xa000) >> 13)).saturating_sub(3);
+                read_bank(&self.prg_rom, 0x2000, bank, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xa000..=0xafff => self.prg_bank = val & 0x0f,
+            0xb000..=0xffff => self.chr.write_register(addr, val),
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.peek(addr)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr.read(addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(addr, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.chr.mirroring
+    }
+}
+
+/// Mapper 10: the MMC4 of the Fire Emblem games, with a 16 KB PRG bank,
+/// the last one fixed, and PRG RAM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mmc4 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    prg_bank: u8,
+    chr: LatchedChr,
+}
+
+impl Mmc4 {
+    pub fn new(cartridge: &Cartridge) -> Mmc4 {
+        Mmc4 {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            prg_bank: 0,
+            chr: LatchedChr::new(cartridge, false),
+        }
+    }
+}
+
+impl Mapper for Mmc4 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        let last = (self.prg_rom.len() / 0x4000).saturating_sub(1);
+        let prg_bank = self.prg_bank as usize;
+
+        match addr {
+            0x6000..=0x7fff => read_bank(&self.prg_ram, 0x2000, 0, addr as usize),
+            0x8000..=0xbfff => read_bank(&self.prg_rom, 0x4000, prg_bank, addr as usize),
+            0xc000..=0xffff => read_bank(&self.prg_rom, 0x4000, last, addr as usize),
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = val;
+            }
+            0xa000..=0xafff => self.prg_bank = val & 0x0f,
+            0xb000..=0xffff => self.chr.write_register(addr, val),
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.peek(addr)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr.read(addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(addr, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.chr.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    fn set_chr_banks(mapper: &mut dyn Mapper) {
+        mapper.cpu_write(0xb000, 1);
+        mapper.cpu_write(0xc000, 2);
+        mapper.cpu_write(0xd000, 3);
+        mapper.cpu_write(0xe000, 4);
+    }
+
+    fn mmc2() -> Mmc2 {
+        let cartridge = cartridge(9, 0, &banked_rom(0x2000, 16), &banked_rom(0x1000, 8));
+        let mut mmc2 = Mmc2::new(&cartridge);
+        set_chr_banks(&mut mmc2);
+        mmc2
+    }
+
+    fn mmc4() -> Mmc4 {
+        let cartridge = cartridge(10, 0, &banked_rom(0x4000, 8), &banked_rom(0x1000, 8));
+        let mut mmc4 = Mmc4::new(&cartridge);
+        set_chr_banks(&mut mmc4);
+        mmc4
+    }
+
+    #[test]
+    fn mmc2_prg_banks() {
+        let mut mmc2 = mmc2();
+        mmc2.cpu_write(0xa000, 5);
+
+        assert_eq!(mmc2.cpu_peek(0x8000), 5);
+        assert_eq!(mmc2.cpu_peek(0xa000), 13);
+        assert_eq!(mmc2.cpu_peek(0xc000), 14);
+        assert_eq!(mmc2.cpu_peek(0xe000), 15);
+    }
+
+    #[test]
+    fn mmc4_prg_banks() {
+        let mut mmc4 = mmc4();
+        mmc4.cpu_write(0xa000, 5);
+        mmc4.cpu_write(0x6000, 0x12);
+
+        assert_eq!(mmc4.cpu_peek(0x8000), 5);
+        assert_eq!(mmc4.cpu_peek(0xc000), 7);
+        assert_eq!(mmc4.cpu_peek(0x6000), 0x12);
+    }
+
+    #[test]
+    fn latches_flip_after_the_fetch() {
+        let mut mmc2 = mmc2();
+        assert_eq!(mmc2.ppu_peek(0x0000), 2);
+        assert_eq!(mmc2.ppu_peek(0x1000), 4);
+
+        assert_eq!(mmc2.ppu_read(0x0fd8), 2);
+        assert_eq!(mmc2.ppu_read(0x0000), 1);
+
+        assert_eq!(mmc2.ppu_read(0x1fd8), 4);
+        assert_eq!(mmc2.ppu_read(0x1000), 3);
+
+        mmc2.ppu_read(0x1fe8);
+        assert_eq!(mmc2.ppu_read(0x1000), 4);
+    }
+
+    #[test]
+    fn peek_leaves_latches() {
+        let mmc2 = mmc2();
+
+        mmc2.ppu_peek(0x0fd8);
+
+        assert_eq!(mmc2.ppu_peek(0x0000), 2);
+    }
+
+    #[test]
+    fn mmc2_lower_latch_is_exact() {
+        let mut mmc2 = mmc2();
+        mmc2.ppu_read(0x0fdf);
+        assert_eq!(mmc2.ppu_peek(0x0000), 2);
+
+        mmc2.ppu_read(0x1fdf);
+        assert_eq!(mmc2.ppu_peek(0x1000), 3);
+    }
+
+    #[test]
+    fn mmc4_lower_latch_covers_the_tile() {
+        let mut mmc4 = mmc4();
+
+        mmc4.ppu_read(0x0fdf);
+
+        assert_eq!(mmc4.ppu_peek(0x0000), 1);
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut mmc4 = mmc4();
+        assert_eq!(mmc4.mirroring(), Mirroring::Vertical);
+
+        mmc4.cpu_write(0xf000, 0x01);
+
+        assert_eq!(mmc4.mirroring(), Mirroring::Horizontal);
+    }
+}
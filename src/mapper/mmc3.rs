@@ -0,0 +1,446 @@
+use rom::{self, Cartridge};
+
+use super::{read_bank, Chr, Mapper, Mirroring};
+
+// How long A12 must stay low for its next rise to clock the counter.
+const A12_FILTER_CYCLES: u8 = 3;
+
+/// How the scanline counter treats a reload value of 0, the one behaviour
+/// which tells the MMC3 revisions apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mmc3Revision {
+    /// MMC3B and MMC3C: the IRQ fires on every clock which leaves the
+    /// counter at 0, so a reload value of 0 fires on every scanline.
+    Sharp,
+    /// MMC3A: the IRQ only fires when the counter is decremented to 0 or
+    /// reloaded by a write to $C001, so a reload value of 0 fires once.
+    Nec,
+}
+
+impl Mmc3Revision {
+    /// NES 2.0 submapper 4 is the NEC MMC3A; everything else is taken for
+    /// the far more common Sharp chips.
+    pub fn detect(cartridge: &Cartridge) -> Mmc3Revision {
+        match cartridge.header().submapper() {
+            Some(4) => Mmc3Revision::Nec,
+            _ => Mmc3Revision::Sharp,
+        }
+    }
+}
+
+/// Mapper 4: the MMC3, with 8 KB PRG banks, 1 and 2 KB CHR banks, and a
+/// scanline counter clocked by PPU A12 rising.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mmc3 {
+    revision: Mmc3Revision,
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+    four_screen: bool,
+
+    bank_select: u8,
+    banks: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    prg_ram_writable: bool,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq: bool,
+    a12: bool,
+    // M2 cycles since A12 was last seen high, saturating.
+    a12_low_cycles: u8,
+}
+
+impl Mmc3 {
+    pub fn new(cartridge: &Cartridge) -> Mmc3 {
+        Mmc3::with_revision(cartridge, Mmc3Revision::detect(cartridge))
+    }
+
+    pub fn with_revision(cartridge: &Cartridge, revision: Mmc3Revision) -> Mmc3 {
+        Mmc3 {
+            revision,
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: super::prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            four_screen: cartridge.mirroring() == rom::Mirroring::FourScreen,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            prg_ram_enabled: true,
+            prg_ram_writable: true,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq: false,
+            a12: false,
+            a12_low_cycles: 0,
+        }
+    }
+
+    pub fn revision(&self) -> Mmc3Revision {
+        self.revision
+    }
+
+    fn can_write_prg_ram(&self) -> bool {
+        self.prg_ram_enabled && self.prg_ram_writable && !self.prg_ram.is_empty()
+    }
+
+    // The 8 KB bank at $8000, $A000, $C000 or $E000.
+    fn prg_bank(&self, addr: u16) -> usize {
+        let second_last = (self.prg_rom.len() / 0x2000).saturating_sub(2);
+        let prg_mode = self.bank_select & 0x40 != 0;
+
+        match (addr >> 13) & 0x03 {
+            0 if prg_mode => second_last,
+            0 => self.banks[6] as usize,
+            1 => self.banks[7] as usize,
+            2 if prg_mode => self.banks[6] as usize,
+            2 => second_last,
+            _ => second_last + 1,
+        }
+    }
+
+    // The 1 KB bank at a pattern table address.
+    fn chr_bank(&self, addr: u16) -> usize {
+        // The inversion swaps the 2 KB banks and the 1 KB banks.
+        let addr = if self.bank_select & 0x80 != 0 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+
+        match addr >> 10 {
+            0 => self.banks[0] as usize & 0xfe,
+            1 => self.banks[0] as usize | 0x01,
+            2 => self.banks[1] as usize & 0xfe,
+            3 => self.banks[1] as usize | 0x01,
+            slot => self.banks[slot as usize - 2] as usize,
+        }
+    }
+
+    // Clocks the scanline counter on a rising edge of PPU A12. The edge
+    // only counts after A12 has been low for a few M2 cycles, which filters
+    // out the quick toggles of 8x16 sprite fetches.
+    fn watch_a12(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 {
+            if !self.a12 && self.a12_low_cycles >= A12_FILTER_CYCLES {
+                self.clock_counter();
+            }
+            self.a12_low_cycles = 0;
+        }
+        self.a12 = a12;
+    }
+
+    fn clock_counter(&mut self) {
+        let was_zero = self.irq_counter == 0;
+        let reloaded = self.irq_reload;
+
+        if was_zero || reloaded {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        let fire = match self.revision {
+            Mmc3Revision::Sharp => self.irq_counter == 0,
+            Mmc3Revision::Nec => self.irq_counter == 0 && (!was_zero || reloaded),
+        };
+        if fire && self.irq_enabled {
+            self.irq = true;
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled => {
+                read_bank(&self.prg_ram, 0x2000, 0, addr as usize)
+            }
+            0x8000..=0xffff => read_bank(&self.prg_rom, 0x2000, self.prg_bank(addr), addr as usize),
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        let odd = addr & 0x01 != 0;
+
+        match addr {
+            0x6000..=0x7fff if self.can_write_prg_ram() => {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = val;
+            }
+            0x8000..=0x9fff if odd => self.banks[self.bank_select as usize & 0x07] = val,
+            0x8000..=0x9fff => self.bank_select = val,
+            0xa000..=0xbfff if odd => {
+                self.prg_ram_enabled = val & 0x80 != 0;
+                self.prg_ram_writable = val & 0x40 == 0;
+            }
+            0xa000..=0xbfff if !self.four_screen => {
+                self.mirroring = if val & 0x01 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            0xc000..=0xdfff if odd => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xc000..=0xdfff => self.irq_latch = val,
+            0xe000..=0xffff if odd => self.irq_enabled = true,
+            0xe000..=0xffff => {
+                self.irq_enabled = false;
+                self.irq = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x0400, self.chr_bank(addr), addr as usize)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.watch_a12(addr);
+
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.watch_a12(addr);
+
+        let bank = self.chr_bank(addr);
+        self.chr.write(0x0400, bank, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    fn mmc3(revision: Mmc3Revision) -> Mmc3 {
+        let cartridge = cartridge(4, 0, &banked_rom(0x2000, 16), &banked_rom(0x0400, 32));
+
+        Mmc3::with_revision(&cartridge, revision)
+    }
+
+    fn clock(mmc3: &mut Mmc3, cycles: usize) {
+        for _ in 0..cycles {
+            mmc3.cpu_clock();
+        }
+    }
+
+    // What the PPU does to A12 on a rendered scanline, with the background
+    // at $0000 and the sprites at $1000.
+    fn scanline(mmc3: &mut Mmc3) {
+        mmc3.ppu_read(0x0000);
+        clock(mmc3, 85);
+        mmc3.ppu_read(0x1000);
+        clock(mmc3, 28);
+    }
+
+    fn set_bank(mmc3: &mut Mmc3, register: u8, bank: u8) {
+        mmc3.cpu_write(0x8000, register);
+        mmc3.cpu_write(0x8001, bank);
+    }
+
+    #[test]
+    fn prg_modes() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        set_bank(&mut mmc3, 6, 3);
+        set_bank(&mut mmc3, 7, 4);
+
+        assert_eq!(mmc3.cpu_peek(0x8000), 3);
+        assert_eq!(mmc3.cpu_peek(0xa000), 4);
+        assert_eq!(mmc3.cpu_peek(0xc000), 14);
+        assert_eq!(mmc3.cpu_peek(0xe000), 15);
+
+        mmc3.cpu_write(0x8000, 0x40);
+
+        assert_eq!(mmc3.cpu_peek(0x8000), 14);
+        assert_eq!(mmc3.cpu_peek(0xa000), 4);
+        assert_eq!(mmc3.cpu_peek(0xc000), 3);
+        assert_eq!(mmc3.cpu_peek(0xe000), 15);
+    }
+
+    #[test]
+    fn chr_modes() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        for register in 0..6 {
+            set_bank(&mut mmc3, register, 8 + register * 2);
+        }
+
+        let banks = |mmc3: &Mmc3| (0..8).map(|i| mmc3.ppu_peek(i * 0x0400)).collect::<Vec<_>>();
+        assert_eq!(banks(&mmc3), [8, 9, 10, 11, 12, 14, 16, 18]);
+
+        mmc3.cpu_write(0x8000, 0x80);
+        assert_eq!(banks(&mmc3), [12, 14, 16, 18, 8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+
+        mmc3.cpu_write(0xa000, 0x00);
+        assert_eq!(mmc3.mirroring(), Mirroring::Vertical);
+
+        mmc3.cpu_write(0xa000, 0x01);
+        assert_eq!(mmc3.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn four_screen_ignores_mirroring() {
+        let cartridge = cartridge(4, 0b0000_1000, &banked_rom(0x2000, 16), &[]);
+        let mut mmc3 = Mmc3::new(&cartridge);
+
+        mmc3.cpu_write(0xa000, 0x01);
+
+        assert_eq!(mmc3.mirroring(), Mirroring::FourScreen);
+    }
+
+    #[test]
+    fn prg_ram_protect() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        mmc3.cpu_write(0x6000, 0x12);
+
+        mmc3.cpu_write(0xa001, 0xc0);
+        mmc3.cpu_write(0x6000, 0x34);
+        assert_eq!(mmc3.cpu_peek(0x6000), 0x12);
+
+        mmc3.cpu_write(0xa001, 0x00);
+        assert_eq!(mmc3.cpu_peek(0x6000), 0x00);
+    }
+
+    #[test]
+    fn irq_after_latch_scanlines() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        mmc3.cpu_write(0xc000, 3);
+        mmc3.cpu_write(0xc001, 0);
+        mmc3.cpu_write(0xe001, 0);
+
+        // Reloaded to 3, then 2, 1 and 0.
+        for _ in 0..3 {
+            scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+
+        mmc3.cpu_write(0xe000, 0);
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn a12_must_rise() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        mmc3.cpu_write(0xc000, 1);
+        mmc3.cpu_write(0xc001, 0);
+        mmc3.cpu_write(0xe001, 0);
+
+        scanline(&mut mmc3);
+        for _ in 0..8 {
+            mmc3.ppu_read(0x1000);
+        }
+
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn a12_filter_ignores_sprite_fetches() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        mmc3.cpu_write(0xc000, 2);
+        mmc3.cpu_write(0xc001, 0);
+        mmc3.cpu_write(0xe001, 0);
+
+        // 8x16 sprites from both pattern tables: A12 drops for under
+        // 3 M2 cycles between the fetches.
+        for _ in 0..2 {
+            mmc3.ppu_read(0x0000);
+            clock(&mut mmc3, 85);
+            for _ in 0..8 {
+                mmc3.ppu_read(0x1000);
+                clock(&mut mmc3, 1);
+                mmc3.ppu_read(0x0000);
+                clock(&mut mmc3, 2);
+            }
+            assert!(!mmc3.irq());
+        }
+
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    #[test]
+    fn irq_disabled() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        mmc3.cpu_write(0xc000, 0);
+        mmc3.cpu_write(0xc001, 0);
+
+        scanline(&mut mmc3);
+
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn zero_latch_sharp_fires_every_scanline() {
+        let mut mmc3 = mmc3(Mmc3Revision::Sharp);
+        mmc3.cpu_write(0xc000, 0);
+        mmc3.cpu_write(0xc001, 0);
+        mmc3.cpu_write(0xe001, 0);
+
+        for _ in 0..3 {
+            scanline(&mut mmc3);
+            assert!(mmc3.irq());
+            mmc3.cpu_write(0xe000, 0);
+            mmc3.cpu_write(0xe001, 0);
+        }
+    }
+
+    #[test]
+    fn zero_latch_nec_fires_once_after_reload() {
+        let mut mmc3 = mmc3(Mmc3Revision::Nec);
+        mmc3.cpu_write(0xc000, 0);
+        mmc3.cpu_write(0xc001, 0);
+        mmc3.cpu_write(0xe001, 0);
+
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+        mmc3.cpu_write(0xe000, 0);
+        mmc3.cpu_write(0xe001, 0);
+
+        scanline(&mut mmc3);
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn revision_from_submapper() {
+        let mut cartridge = cartridge(4, 0, &banked_rom(0x2000, 16), &[]);
+        assert_eq!(Mmc3::new(&cartridge).revision(), Mmc3Revision::Sharp);
+
+        cartridge.header_mut().set_submapper(4);
+        assert_eq!(Mmc3::new(&cartridge).revision(), Mmc3Revision::Nec);
+    }
+}
@@ -0,0 +1,813 @@
+use std::cmp;
+
+use rom::Cartridge;
+
+use super::{bank_index, read_bank, Chr, Mapper, Mirroring};
+
+// What ExRAM is used for, from $5104.
+const EXRAM_NAMETABLE: u8 = 0;
+const EXRAM_EXTENDED_ATTRIBUTES: u8 = 1;
+const EXRAM_READ_WRITE: u8 = 2;
+
+/// The two sets of CHR bank registers. With 8×16 sprites the PPU's sprite
+/// fetches use the first and its background fetches the second; otherwise
+/// whichever was written last is used for everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChrSet {
+    Sprites,
+    Background,
+}
+
+/// How the MMC5 answers the attribute and pattern fetches which follow a
+/// background nametable fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tile {
+    Normal,
+    /// Extended attribute mode, with the tile's byte of ExRAM.
+    Extended(u8),
+    /// Inside the vertical split, at this tile column and split scroll.
+    Split { x: u8, y: u8 },
+}
+
+/// Mapper 5: the MMC5, with four PRG and CHR banking modes, 1 KB of ExRAM,
+/// nametables it can fill or answer from ExRAM, a vertical split, a
+/// scanline IRQ and an 8×8 multiplier.
+///
+/// The MMC5 cannot see the PPU's state, so like the real chip it follows
+/// the pattern of the PPU's fetches: three reads of the same nametable
+/// address start a scanline, and counting nametable fetches from there
+/// tells the background fetches from the sprite ones. The PPU has to make
+/// all of its fetches for this to work, including the garbage nametable
+/// fetches during sprite loading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mmc5 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+    exram: Vec<u8>,
+
+    prg_mode: u8,
+    chr_mode: u8,
+    prg_ram_protect: [u8; 2],
+    exram_mode: u8,
+    nametables: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    // $5113-$5117.
+    prg_banks: [u8; 5],
+    // $5120-$512B, with the upper bits from $5130.
+    chr_banks: [u16; 12],
+    chr_upper: u8,
+    last_chr_set: ChrSet,
+
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+
+    irq_scanline: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    multiplicand: u8,
+    multiplier: u8,
+
+    sprites_8x16: bool,
+    in_frame: bool,
+    scanline: u8,
+    last_ppu_addr: u16,
+    repeated_reads: u8,
+    idle_cycles: u8,
+    // Nametable fetches since the scanline started.
+    tile_fetches: u8,
+    tile: Tile,
+}
+
+impl Mmc5 {
+    pub fn new(cartridge: &Cartridge) -> Mmc5 {
+        Mmc5 {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            exram: vec![0x00; 0x0400],
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect: [0, 0],
+            exram_mode: EXRAM_NAMETABLE,
+            nametables: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            prg_banks: [0, 0, 0, 0, 0xff],
+            chr_banks: [0; 12],
+            chr_upper: 0,
+            last_chr_set: ChrSet::Sprites,
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            irq_scanline: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            multiplicand: 0xff,
+            multiplier: 0xff,
+            sprites_8x16: false,
+            in_frame: false,
+            scanline: 0,
+            last_ppu_addr: 0,
+            repeated_reads: 0,
+            idle_cycles: 0,
+            tile_fetches: 0,
+            tile: Tile::Normal,
+        }
+    }
+
+    // $5204: the IRQ pending flag and whether the PPU is rendering.
+    fn status(&self) -> u8 {
+        (self.irq_pending as u8) << 7 | (self.in_frame as u8) << 6
+    }
+
+    fn product(&self) -> u16 {
+        self.multiplicand as u16 * self.multiplier as u16
+    }
+
+    // $5102 and $5103 must both hold their magic values.
+    fn can_write_prg_ram(&self) -> bool {
+        self.prg_ram_protect == [0b10, 0b01] && !self.prg_ram.is_empty()
+    }
+
+    // The 8 KB bank at an address in $6000-$FFFF, and whether it is ROM.
+    fn prg_bank(&self, addr: u16) -> (usize, bool) {
+        if addr < 0x8000 {
+            return (self.prg_banks[0] as usize, false);
+        }
+
+        let slot = ((addr - 0x8000) >> 13) as usize;
+        let (register, bank) = match self.prg_mode {
+            0 => (4, self.prg_banks[4] & 0x7c | slot as u8),
+            1 => {
+                let register = 2 + slot / 2 * 2;
+                (register, self.prg_banks[register] & 0x7e | (slot & 0x01) as u8)
+            }
+            2 if slot < 2 => (2, self.prg_banks[2] & 0x7e | slot as u8),
+            _ => (slot + 1, self.prg_banks[slot + 1] & 0x7f),
+        };
+
+        // $5117 can only select ROM.
+        (bank as usize, register == 4 || self.prg_banks[register] & 0x80 != 0)
+    }
+
+    fn write_prg_ram(&mut self, addr: u16, val: u8) {
+        let (bank, rom) = self.prg_bank(addr);
+
+        if !rom {
+            let index = bank_index(self.prg_ram.len(), 0x2000, bank, addr as usize);
+            self.prg_ram[index] = val;
+        }
+    }
+
+    fn write_exram(&mut self, addr: u16, val: u8) {
+        let offset = addr as usize & 0x03ff;
+
+        match self.exram_mode {
+            // The PPU owns ExRAM outside of rendering in these modes, and
+            // the CPU's writes land as 0.
+            EXRAM_NAMETABLE | EXRAM_EXTENDED_ATTRIBUTES => {
+                self.exram[offset] = if self.in_frame { val } else { 0x00 }
+            }
+            EXRAM_READ_WRITE => self.exram[offset] = val,
+            _ => {}
+        }
+    }
+
+    fn chr_set(&self) -> ChrSet {
+        match (self.sprites_8x16 && self.in_frame, self.sprite_fetches()) {
+            (true, true) => ChrSet::Sprites,
+            (true, false) => ChrSet::Background,
+            (false, _) => self.last_chr_set,
+        }
+    }
+
+    // The bank size and the bank at a pattern table address.
+    fn chr_bank(&self, set: ChrSet, addr: u16) -> (usize, usize) {
+        let bank_size = 0x2000 >> self.chr_mode;
+        let slot = addr as usize / bank_size;
+
+        let register = match set {
+            ChrSet::Sprites => {
+                let slots = 1 << self.chr_mode;
+                (slot + 1) * (8 / slots) - 1
+            }
+            // The background set only has registers for 4 KB, which are
+            // repeated in both pattern tables.
+            ChrSet::Background => {
+                let slots = cmp::max(1, (1 << self.chr_mode) / 2);
+                8 + (slot % slots + 1) * (4 / slots) - 1
+            }
+        };
+
+        (bank_size, self.chr_banks[register] as usize)
+    }
+
+    // The PPU is between the sprite fetches at dots 257-320.
+    fn sprite_fetches(&self) -> bool {
+        self.in_frame && self.tile_fetches >= 32 && self.tile_fetches < 48
+    }
+
+    fn watch_ppu(&mut self, addr: u16) {
+        self.idle_cycles = 0;
+
+        if addr == self.last_ppu_addr {
+            self.repeated_reads = self.repeated_reads.saturating_add(1);
+        } else {
+            self.repeated_reads = 0;
+        }
+        self.last_ppu_addr = addr;
+    }
+
+    fn start_scanline(&mut self) {
+        if self.in_frame {
+            self.scanline = self.scanline.wrapping_add(1);
+            if self.scanline == self.irq_scanline && self.irq_scanline != 0 {
+                self.irq_pending = true;
+            }
+        } else {
+            self.in_frame = true;
+            self.scanline = 0;
+            self.irq_pending = false;
+        }
+
+        self.tile_fetches = 0;
+    }
+
+    fn end_frame(&mut self) {
+        self.in_frame = false;
+        self.tile = Tile::Normal;
+    }
+
+    // Decides what a background tile, fetched from the nametable at
+    // `addr`, is made of.
+    fn background_tile(&self, addr: u16) -> Tile {
+        // The first two tiles of a line are fetched at the end of the one
+        // before.
+        let (x, line) = match self.tile_fetches {
+            fetch @ 0..=31 => (fetch + 2, self.scanline),
+            fetch @ 48..=49 => (fetch - 48, self.scanline.wrapping_add(1)),
+            _ => return Tile::Normal,
+        };
+
+        if self.in_split(x) {
+            let y = (self.split_scroll as u16 + line as u16) % 240;
+            return Tile::Split { x: x & 0x1f, y: y as u8 };
+        }
+
+        if self.exram_mode == EXRAM_EXTENDED_ATTRIBUTES {
+            Tile::Extended(self.exram[addr as usize & 0x03ff])
+        } else {
+            Tile::Normal
+        }
+    }
+
+    fn in_split(&self, x: u8) -> bool {
+        let threshold = self.split_control & 0x1f;
+        let right = self.split_control & 0x40 != 0;
+
+        self.split_control & 0x80 != 0
+            && self.exram_mode <= EXRAM_EXTENDED_ATTRIBUTES
+            && (x >= threshold) == right
+    }
+}
+
+// iNES headers cannot say how much PRG RAM there is, so they get as much
+// as any MMC5 board has.
+fn prg_ram(cartridge: &Cartridge) -> Vec<u8> {
+    let mut ram = super::prg_ram(cartridge);
+    if cartridge.header().nes2().is_none() {
+        ram.resize(0x10000, 0x00);
+    }
+
+    ram
+}
+
+// An attribute byte with the same palette in all four quadrants.
+fn attribute(palette: u8) -> u8 {
+    (palette & 0x03) * 0x55
+}
+
+impl Mapper for Mmc5 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x5204 => self.status(),
+            0x5205 => self.product() as u8,
+            0x5206 => (self.product() >> 8) as u8,
+            0x5c00..=0x5fff if self.exram_mode >= EXRAM_READ_WRITE => {
+                self.exram[addr as usize & 0x03ff]
+            }
+            0x6000..=0xffff => {
+                let (bank, rom) = self.prg_bank(addr);
+                let data = if rom { &self.prg_rom } else { &self.prg_ram };
+
+                read_bank(data, 0x2000, bank, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let val = self.cpu_peek(addr);
+
+        match addr {
+            0x5204 => self.irq_pending = false,
+            // Fetching the NMI vector means the PPU has reached vblank.
+            0xfffa | 0xfffb => self.end_frame(),
+            _ => {}
+        }
+
+        val
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x5100 => self.prg_mode = val & 0x03,
+            0x5101 => self.chr_mode = val & 0x03,
+            0x5102 => self.prg_ram_protect[0] = val & 0x03,
+            0x5103 => self.prg_ram_protect[1] = val & 0x03,
+            0x5104 => self.exram_mode = val & 0x03,
+            0x5105 => self.nametables = val,
+            0x5106 => self.fill_tile = val,
+            0x5107 => self.fill_attribute = val & 0x03,
+            0x5113..=0x5117 => self.prg_banks[addr as usize - 0x5113] = val,
+            0x5120..=0x512b => {
+                let register = addr as usize - 0x5120;
+                self.chr_banks[register] = val as u16 | (self.chr_upper as u16) << 8;
+                self.last_chr_set = if register < 8 {
+                    ChrSet::Sprites
+                } else {
+                    ChrSet::Background
+                };
+            }
+            0x5130 => self.chr_upper = val & 0x03,
+            0x5200 => self.split_control = val,
+            0x5201 => self.split_scroll = val,
+            0x5202 => self.split_bank = val,
+            0x5203 => self.irq_scanline = val,
+            0x5204 => self.irq_enabled = val & 0x80 != 0,
+            0x5205 => self.multiplicand = val,
+            0x5206 => self.multiplier = val,
+            0x5c00..=0x5fff => self.write_exram(addr, val),
+            0x6000..=0xffff if self.can_write_prg_ram() => self.write_prg_ram(addr, val),
+            _ => {}
+        }
+    }
+
+    /// Notices the PPU has stopped rendering when it has not fetched
+    /// anything for 3 cycles.
+    fn cpu_clock(&mut self) {
+        if self.in_frame {
+            self.idle_cycles += 1;
+            if self.idle_cycles >= 3 {
+                self.end_frame();
+            }
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        let (bank_size, bank) = self.chr_bank(self.chr_set(), addr);
+
+        self.chr.read(bank_size, bank, addr as usize)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.watch_ppu(addr);
+
+        match self.tile {
+            Tile::Normal => self.ppu_peek(addr),
+            Tile::Extended(exram) => {
+                let bank = (exram & 0x3f) as usize | (self.chr_upper as usize) << 6;
+                self.chr.read(0x1000, bank, addr as usize)
+            }
+            // The split has its own vertical scroll, so the fine Y of the
+            // PPU's address is replaced.
+            Tile::Split { y, .. } => {
+                let offset = (addr as usize & !0x07) | (y as usize & 0x07);
+                self.chr.read(0x1000, self.split_bank as usize, offset)
+            }
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let (bank_size, bank) = self.chr_bank(self.last_chr_set, addr);
+
+        self.chr.write(bank_size, bank, addr as usize, val);
+    }
+
+    /// The closest of the usual layouts to what $5105 selects. Only the
+    /// nametable methods see ExRAM and fill mode.
+    fn mirroring(&self) -> Mirroring {
+        match self.nametables {
+            0x00 => Mirroring::SingleScreenLower,
+            0x55 => Mirroring::SingleScreenUpper,
+            0x50 => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn nametable_peek(&self, addr: u16, vram: &[u8]) -> u8 {
+        let offset = addr as usize & 0x03ff;
+
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            0 => vram[offset],
+            1 => vram[0x0400 + offset],
+            2 if self.exram_mode <= EXRAM_EXTENDED_ATTRIBUTES => self.exram[offset],
+            2 => 0x00,
+            _ if offset < 0x03c0 => self.fill_tile,
+            _ => attribute(self.fill_attribute),
+        }
+    }
+
+    fn nametable_read(&mut self, addr: u16, vram: &[u8]) -> u8 {
+        self.watch_ppu(addr);
+
+        if addr & 0x03ff < 0x03c0 {
+            if self.repeated_reads == 2 {
+                self.start_scanline();
+            } else {
+                self.tile_fetches = self.tile_fetches.saturating_add(1);
+            }
+
+            self.tile = if self.in_frame {
+                self.background_tile(addr)
+            } else {
+                Tile::Normal
+            };
+
+            if let Tile::Split { x, y } = self.tile {
+                return self.exram[y as usize / 8 * 32 + x as usize];
+            }
+        } else {
+            match self.tile {
+                Tile::Normal => {}
+                Tile::Extended(exram) => return attribute(exram >> 6),
+                Tile::Split { x, y } => {
+                    let exram = self.exram[0x03c0 + y as usize / 32 * 8 + x as usize / 4];
+                    let shift = ((y / 16) & 0x01) * 4 + ((x / 2) & 0x01) * 2;
+                    return attribute(exram >> shift);
+                }
+            }
+        }
+
+        self.nametable_peek(addr, vram)
+    }
+
+    fn nametable_write(&mut self, addr: u16, val: u8, vram: &mut [u8]) {
+        let offset = addr as usize & 0x03ff;
+
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            0 => vram[offset] = val,
+            1 => vram[0x0400 + offset] = val,
+            2 if self.exram_mode <= EXRAM_EXTENDED_ATTRIBUTES => self.exram[offset] = val,
+            _ => {}
+        }
+    }
+
+    fn ppu_register_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x2000 => self.sprites_8x16 = val & 0x20 != 0,
+            0x2001 if val & 0x18 == 0 => self.end_frame(),
+            _ => {}
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_enabled && self.irq_pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    fn mmc5() -> Mmc5 {
+        Mmc5::new(&cartridge(5, 0, &banked_rom(0x2000, 16), &banked_rom(0x0400, 64)))
+    }
+
+    // The nametable, attribute and first pattern byte of a background tile.
+    fn fetch_tile(mmc5: &mut Mmc5, vram: &[u8], addr: u16) -> (u8, u8, u8) {
+        let tile = mmc5.nametable_read(addr, vram);
+        let attribute = mmc5.nametable_read(0x23c0, vram);
+        let pattern = mmc5.ppu_read(0x0000);
+        mmc5.ppu_read(0x0008);
+
+        (tile, attribute, pattern)
+    }
+
+    // Dots 321-340: the first two tiles of the next line, then the two
+    // dummy nametable fetches of the third.
+    fn end_of_line(mmc5: &mut Mmc5, vram: &[u8]) {
+        fetch_tile(mmc5, vram, 0x2000);
+        fetch_tile(mmc5, vram, 0x2001);
+        mmc5.nametable_read(0x2002, vram);
+        mmc5.nametable_read(0x2002, vram);
+    }
+
+    // Dots 1-256, for tiles 2 to 33.
+    fn background(mmc5: &mut Mmc5, vram: &[u8]) -> Vec<(u8, u8, u8)> {
+        (2..34).map(|tile| fetch_tile(mmc5, vram, 0x2000 + tile % 32)).collect()
+    }
+
+    // Dots 257-320, with their garbage nametable fetches.
+    fn sprites(mmc5: &mut Mmc5, vram: &[u8]) -> Vec<u8> {
+        (0..8)
+            .map(|_| {
+                mmc5.nametable_read(0x2000, vram);
+                mmc5.nametable_read(0x2000, vram);
+                let pattern = mmc5.ppu_read(0x1000);
+                mmc5.ppu_read(0x1008);
+                pattern
+            })
+            .collect()
+    }
+
+    fn scanline(mmc5: &mut Mmc5, vram: &[u8]) {
+        background(mmc5, vram);
+        sprites(mmc5, vram);
+        end_of_line(mmc5, vram);
+    }
+
+    #[test]
+    fn prg_modes() {
+        let mut mmc5 = mmc5();
+        let banks = |mmc5: &Mmc5| {
+            [0x8000, 0xa000, 0xc000, 0xe000]
+                .iter()
+                .map(|&addr| mmc5.cpu_peek(addr))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(mmc5.cpu_peek(0xfffc), 15);
+
+        mmc5.cpu_write(0x5114, 0x81);
+        mmc5.cpu_write(0x5115, 0x83);
+        mmc5.cpu_write(0x5116, 0x85);
+        mmc5.cpu_write(0x5117, 0x07);
+        assert_eq!(banks(&mmc5), [1, 3, 5, 7]);
+
+        mmc5.cpu_write(0x5100, 2);
+        assert_eq!(banks(&mmc5), [2, 3, 5, 7]);
+
+        mmc5.cpu_write(0x5100, 1);
+        assert_eq!(banks(&mmc5), [2, 3, 6, 7]);
+
+        mmc5.cpu_write(0x5100, 0);
+        assert_eq!(banks(&mmc5), [4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn prg_ram_protect_and_banking() {
+        let mut mmc5 = mmc5();
+        mmc5.cpu_write(0x5113, 1);
+        mmc5.cpu_write(0x5114, 0x01);
+
+        mmc5.cpu_write(0x8000, 0x12);
+        assert_eq!(mmc5.cpu_peek(0x6000), 0x00);
+
+        mmc5.cpu_write(0x5102, 0x02);
+        mmc5.cpu_write(0x5103, 0x01);
+        mmc5.cpu_write(0x8000, 0x12);
+        assert_eq!(mmc5.cpu_peek(0x6000), 0x12);
+    }
+
+    #[test]
+    fn ines_gets_64k_of_prg_ram() {
+        assert_eq!(mmc5().prg_ram.len(), 0x10000);
+    }
+
+    #[test]
+    fn chr_modes() {
+        let mut mmc5 = mmc5();
+        for register in 0..8 {
+            mmc5.cpu_write(0x5120 + register, register as u8 + 1);
+        }
+        let banks = |mmc5: &Mmc5| (0..8).map(|i| mmc5.ppu_peek(i * 0x0400)).collect::<Vec<_>>();
+
+        mmc5.cpu_write(0x5101, 3);
+        assert_eq!(banks(&mmc5), [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        mmc5.cpu_write(0x5101, 2);
+        assert_eq!(banks(&mmc5), [4, 5, 8, 9, 12, 13, 16, 17]);
+
+        mmc5.cpu_write(0x5101, 1);
+        assert_eq!(banks(&mmc5), [16, 17, 18, 19, 32, 33, 34, 35]);
+
+        // 8 KB bank 8 wraps around to the start of the 64 KB of CHR.
+        mmc5.cpu_write(0x5101, 0);
+        assert_eq!(banks(&mmc5), [0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn background_set_repeats() {
+        let mut mmc5 = mmc5();
+        mmc5.cpu_write(0x5101, 3);
+        for register in 0..4 {
+            mmc5.cpu_write(0x5128 + register, register as u8 + 10);
+        }
+
+        let banks = (0..8).map(|i| mmc5.ppu_peek(i * 0x0400)).collect::<Vec<_>>();
+
+        assert_eq!(banks, [10, 11, 12, 13, 10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn chr_upper_bits() {
+        let mut mmc5 = mmc5();
+        mmc5.cpu_write(0x5101, 3);
+        mmc5.cpu_write(0x5130, 1);
+        mmc5.cpu_write(0x5120, 2);
+
+        assert_eq!(mmc5.chr_banks[0], 0x102);
+    }
+
+    #[test]
+    fn separate_sets_for_8x16_sprites() {
+        let mut mmc5 = mmc5();
+        let vram = [0x00; 0x0800];
+        mmc5.cpu_write(0x5101, 3);
+        mmc5.cpu_write(0x5120, 1);
+        mmc5.cpu_write(0x5124, 5);
+        mmc5.cpu_write(0x5128, 9);
+        mmc5.ppu_register_write(0x2000, 0x20);
+
+        end_of_line(&mut mmc5, &vram);
+        let background = background(&mut mmc5, &vram);
+        let sprites = sprites(&mut mmc5, &vram);
+
+        assert!(background.iter().all(|&(_, _, pattern)| pattern == 9));
+        assert!(sprites.iter().all(|&pattern| pattern == 5));
+    }
+
+    #[test]
+    fn last_written_set_for_8x8_sprites() {
+        let mut mmc5 = mmc5();
+        let vram = [0x00; 0x0800];
+        mmc5.cpu_write(0x5101, 3);
+        mmc5.cpu_write(0x5128, 9);
+        mmc5.cpu_write(0x5120, 1);
+
+        end_of_line(&mut mmc5, &vram);
+        let background = background(&mut mmc5, &vram);
+
+        assert!(background.iter().all(|&(_, _, pattern)| pattern == 1));
+    }
+
+    #[test]
+    fn nametable_mapping() {
+        let mut mmc5 = mmc5();
+        let mut vram = [0x00; 0x0800];
+        vram[0x0005] = 0x11;
+        vram[0x0405] = 0x22;
+        mmc5.cpu_write(0x5104, EXRAM_READ_WRITE);
+        mmc5.cpu_write(0x5c05, 0x33);
+        mmc5.cpu_write(0x5104, EXRAM_NAMETABLE);
+        mmc5.cpu_write(0x5105, 0b11_10_01_00);
+        mmc5.cpu_write(0x5106, 0x44);
+        mmc5.cpu_write(0x5107, 0x02);
+
+        assert_eq!(mmc5.nametable_peek(0x2005, &vram), 0x11);
+        assert_eq!(mmc5.nametable_peek(0x2405, &vram), 0x22);
+        assert_eq!(mmc5.nametable_peek(0x2805, &vram), 0x33);
+        assert_eq!(mmc5.nametable_peek(0x2c05, &vram), 0x44);
+        assert_eq!(mmc5.nametable_peek(0x2fc0, &vram), 0xaa);
+
+        mmc5.nametable_write(0x2c05, 0x55, &mut vram);
+        mmc5.nametable_write(0x2805, 0x66, &mut vram);
+        assert_eq!(mmc5.nametable_peek(0x2c05, &vram), 0x44);
+        assert_eq!(mmc5.exram[0x05], 0x66);
+    }
+
+    #[test]
+    fn exram_cpu_access() {
+        let mut mmc5 = mmc5();
+
+        mmc5.cpu_write(0x5c00, 0x12);
+        assert_eq!(mmc5.exram[0], 0x00);
+        assert_eq!(mmc5.cpu_peek(0x5c00), 0x00);
+
+        mmc5.cpu_write(0x5104, EXRAM_READ_WRITE);
+        mmc5.cpu_write(0x5c00, 0x12);
+        assert_eq!(mmc5.cpu_peek(0x5c00), 0x12);
+
+        mmc5.cpu_write(0x5104, 3);
+        mmc5.cpu_write(0x5c00, 0x34);
+        assert_eq!(mmc5.cpu_peek(0x5c00), 0x12);
+    }
+
+    #[test]
+    fn extended_attributes() {
+        let mut mmc5 = mmc5();
+        let vram = [0x00; 0x0800];
+        mmc5.cpu_write(0x5104, EXRAM_READ_WRITE);
+        mmc5.cpu_write(0x5c02, 0b10_000101);
+        mmc5.cpu_write(0x5104, EXRAM_EXTENDED_ATTRIBUTES);
+
+        end_of_line(&mut mmc5, &vram);
+        let background = background(&mut mmc5, &vram);
+
+        // 4 KB bank 5, which starts with the 1 KB bank 20.
+        assert_eq!(background[0], (0x00, 0xaa, 20));
+        assert_eq!(background[1], (0x00, 0x00, 0));
+    }
+
+    #[test]
+    fn vertical_split() {
+        let mut mmc5 = mmc5();
+        let vram = [0x00; 0x0800];
+        mmc5.cpu_write(0x5104, EXRAM_READ_WRITE);
+        for i in 0..0x3c0 {
+            mmc5.cpu_write(0x5c00 + i, i as u8);
+        }
+        mmc5.cpu_write(0x5fc0 + 2 * 8, 0b00_00_11_00);
+        mmc5.cpu_write(0x5104, EXRAM_NAMETABLE);
+        mmc5.cpu_write(0x5200, 0x80 | 4);
+        mmc5.cpu_write(0x5201, 70);
+        mmc5.cpu_write(0x5202, 3);
+
+        end_of_line(&mut mmc5, &vram);
+        let background = background(&mut mmc5, &vram);
+
+        // Split Y 70 is tile row 8, in the top right quadrant of its
+        // attribute byte at row 2.
+        let split_tile = (8 * 32 + 2) as u8;
+        assert_eq!(background[0].0, split_tile);
+        assert_eq!(background[1].0, split_tile + 1);
+        assert_eq!(background[0].1, 0xff);
+        assert_eq!(background[0].2, 12);
+        assert_eq!(background[2], (0x00, 0x00, 0));
+        assert_eq!(mmc5.ppu_read(0x0000), 0);
+    }
+
+    #[test]
+    fn split_attribute_quadrant() {
+        let mut mmc5 = mmc5();
+        mmc5.exram[0x3c0 + 8] = 0b11_10_01_00;
+        mmc5.split_control = 0x80 | 0x1f;
+
+        mmc5.in_frame = true;
+        mmc5.tile = Tile::Split { x: 2, y: 48 };
+
+        assert_eq!(mmc5.nametable_read(0x23c0, &[0x00; 0x0800]), 0xff);
+    }
+
+    #[test]
+    fn scanline_irq() {
+        let mut mmc5 = mmc5();
+        let vram = [0x00; 0x0800];
+        mmc5.cpu_write(0x5203, 2);
+        mmc5.cpu_write(0x5204, 0x80);
+
+        end_of_line(&mut mmc5, &vram);
+        assert_eq!(mmc5.cpu_peek(0x5204), 0x00);
+
+        scanline(&mut mmc5, &vram);
+        assert_eq!(mmc5.cpu_peek(0x5204), 0x40);
+        scanline(&mut mmc5, &vram);
+        assert!(!mmc5.irq());
+
+        background(&mut mmc5, &vram);
+        assert!(mmc5.irq());
+
+        assert_eq!(mmc5.cpu_read(0x5204), 0xc0);
+        assert!(!mmc5.irq());
+    }
+
+    #[test]
+    fn frame_ends() {
+        let mut mmc5 = mmc5();
+        let vram = [0x00; 0x0800];
+
+        end_of_line(&mut mmc5, &vram);
+        mmc5.nametable_read(0x2002, &vram);
+        assert!(mmc5.in_frame);
+        mmc5.cpu_read(0xfffa);
+        assert!(!mmc5.in_frame);
+
+        end_of_line(&mut mmc5, &vram);
+        mmc5.nametable_read(0x2002, &vram);
+        for _ in 0..3 {
+            mmc5.cpu_clock();
+        }
+        assert!(!mmc5.in_frame);
+
+        end_of_line(&mut mmc5, &vram);
+        mmc5.nametable_read(0x2002, &vram);
+        mmc5.ppu_register_write(0x2001, 0x00);
+        assert!(!mmc5.in_frame);
+    }
+
+    #[test]
+    fn multiplier() {
+        let mut mmc5 = mmc5();
+        mmc5.cpu_write(0x5205, 0xfe);
+        mmc5.cpu_write(0x5206, 0x12);
+
+        assert_eq!(mmc5.cpu_peek(0x5205), 0xdc);
+        assert_eq!(mmc5.cpu_peek(0x5206), 0x11);
+    }
+}
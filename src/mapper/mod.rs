@@ -0,0 +1,310 @@
+//! The boards inside cartridges, which map PRG and CHR memory into the
+//! address spaces of the CPU and the PPU.
+
+mod axrom;
+mod bnrom;
+mod cartridge_bus;
+mod cnrom;
+mod color_dreams;
+mod gxrom;
+mod mmc1;
+mod mmc2;
+mod mmc3;
+mod mmc5;
+mod nrom;
+mod uxrom;
+mod vrc4;
+mod vrc6;
+mod vrc_irq;
+
+pub use self::axrom::Axrom;
+pub use self::bnrom::{Bnrom, Nina001};
+pub use self::cartridge_bus::CartridgeBus;
+pub use self::cnrom::Cnrom;
+pub use self::color_dreams::ColorDreams;
+pub use self::gxrom::Gxrom;
+pub use self::mmc1::{Mmc1, Mmc1Board};
+pub use self::mmc2::{Mmc2, Mmc4};
+pub use self::mmc3::{Mmc3, Mmc3Revision};
+pub use self::mmc5::Mmc5;
+pub use self::nrom::Nrom;
+pub use self::uxrom::Uxrom;
+pub use self::vrc4::{Vrc4, Vrc4Board};
+pub use self::vrc6::{Vrc6, Vrc6Board};
+
+use rom::{self, Cartridge, RomError, TRAINER_ADDR};
+
+/// How the PPU's $2000-$2FFF nametables are laid out over the console's
+/// 2 KB of VRAM, or the cartridge's own for four-screen boards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    /// Every nametable is the first 1 KB of VRAM.
+    SingleScreenLower,
+    /// Every nametable is the second 1 KB of VRAM.
+    SingleScreenUpper,
+    FourScreen,
+}
+
+impl Mirroring {
+    /// The offset into VRAM of a nametable address in $2000-$3EFF.
+    pub fn vram_offset(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x0fff;
+        let table = addr / 0x0400;
+        let offset = addr & 0x03ff;
+
+        let table = match *self {
+            Mirroring::Horizontal => table / 2,
+            Mirroring::Vertical => table % 2,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table,
+        };
+
+        table * 0x0400 + offset
+    }
+}
+
+impl From<rom::Mirroring> for Mirroring {
+    fn from(mirroring: rom::Mirroring) -> Mirroring {
+        match mirroring {
+            rom::Mirroring::Horizontal => Mirroring::Horizontal,
+            rom::Mirroring::Vertical => Mirroring::Vertical,
+            rom::Mirroring::FourScreen => Mirroring::FourScreen,
+        }
+    }
+}
+
+/// A cartridge board, as seen by the CPU and the PPU.
+///
+/// Like `Bus`, the `read` methods may have side effects on the board and
+/// default to `peek`, which must not.
+pub trait Mapper {
+    /// Reads from $4020-$FFFF. Unmapped addresses read as 0.
+    fn cpu_peek(&self, addr: u16) -> u8;
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.cpu_peek(addr)
+    }
+
+    /// Writes to $4020-$FFFF, where most boards have their registers.
+    fn cpu_write(&mut self, addr: u16, val: u8);
+
+    /// Called once per CPU cycle, for boards which keep time on M2. The PPU
+    /// must be stepped between calls, 3 dots each, as boards such as the
+    /// MMC5 take a few cycles without pattern or nametable fetches for the
+    /// end of a frame.
+    fn cpu_clock(&mut self) {}
+
+    /// Reads from the pattern tables at $0000-$1FFF.
+    fn ppu_peek(&self, addr: u16) -> u8;
+
+    /// The PPU's pattern fetches. The PPU must make every one of them
+    /// through here, as boards like the MMC2 and MMC3 act on the addresses
+    /// they see.
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.ppu_peek(addr)
+    }
+
+    /// Writes to the pattern tables, ignored unless they are CHR RAM.
+    fn ppu_write(&mut self, addr: u16, val: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    /// Reads from the nametables at $2000-$2FFF, given the VRAM the PPU
+    /// has: 2 KB, or 4 KB for four-screen boards. Most boards only choose
+    /// the mirroring, but some answer with memory of their own.
+    fn nametable_peek(&self, addr: u16, vram: &[u8]) -> u8 {
+        vram[self.mirroring().vram_offset(addr)]
+    }
+
+    fn nametable_read(&mut self, addr: u16, vram: &[u8]) -> u8 {
+        self.nametable_peek(addr, vram)
+    }
+
+    fn nametable_write(&mut self, addr: u16, val: u8, vram: &mut [u8]) {
+        vram[self.mirroring().vram_offset(addr)] = val;
+    }
+
+    /// Sees the CPU's writes to the PPU registers at $2000-$2007, for
+    /// boards which snoop on them to follow the PPU's settings.
+    fn ppu_register_write(&mut self, _addr: u16, _val: u8) {}
+
+    /// Whether the board is holding the CPU's IRQ line low.
+    fn irq(&self) -> bool {
+        false
+    }
+}
+
+/// Builds the board the cartridge's header asks for.
+pub fn from_cartridge(cartridge: &Cartridge) -> Result<Box<dyn Mapper>, RomError> {
+    match cartridge.mapper_number() {
+        0 => Ok(Box::new(Nrom::new(cartridge))),
+        1 => Ok(Box::new(Mmc1::new(cartridge))),
+        2 => Ok(Box::new(Uxrom::new(cartridge))),
+        3 => Ok(Box::new(Cnrom::new(cartridge))),
+        4 => Ok(Box::new(Mmc3::new(cartridge))),
+        5 => Ok(Box::new(Mmc5::new(cartridge))),
+        7 => Ok(Box::new(Axrom::new(cartridge))),
+        9 => Ok(Box::new(Mmc2::new(cartridge))),
+        10 => Ok(Box::new(Mmc4::new(cartridge))),
+        11 => Ok(Box::new(ColorDreams::new(cartridge))),
+        21..=23 | 25 => Ok(Box::new(Vrc4::new(cartridge))),
+        24 | 26 => Ok(Box::new(Vrc6::new(cartridge))),
+        34 if Nina001::is_nina001(cartridge) => Ok(Box::new(Nina001::new(cartridge))),
+        34 => Ok(Box::new(Bnrom::new(cartridge))),
+        66 => Ok(Box::new(Gxrom::new(cartridge))),
+        mapper => Err(RomError::UnsupportedMapper(mapper)),
+    }
+}
+
+/// Whether writes to the board's register also reach the PRG ROM, which
+/// drives the data bus at the same time so only bits both agree on stick.
+/// NES 2.0 says so with submapper 2 and denies it with submapper 1, iNES
+/// headers with flag 10.
+fn has_bus_conflicts(cartridge: &Cartridge) -> bool {
+    let header = cartridge.header();
+
+    match header.submapper() {
+        Some(1) => false,
+        Some(2) => true,
+        _ => header.has_bus_conflicts(),
+    }
+}
+
+// Bank numbers wrap around the memory, as boards leave the high lines of
+// their bank registers unconnected when the ROM is small.
+fn bank_index(len: usize, bank_size: usize, bank: usize, offset: usize) -> usize {
+    (bank * bank_size + offset % bank_size) % len
+}
+
+// A byte of a bank of ROM, or 0 if there is no ROM at all.
+fn read_bank(data: &[u8], bank_size: usize, bank: usize, offset: usize) -> u8 {
+    if data.is_empty() {
+        0x00
+    } else {
+        data[bank_index(data.len(), bank_size, bank, offset)]
+    }
+}
+
+/// PRG RAM at $6000-$7FFF, volatile and battery backed together, with the
+/// trainer already loaded at $7000.
+fn prg_ram(cartridge: &Cartridge) -> Vec<u8> {
+    let header = cartridge.header();
+    let mut ram = vec![0x00; header.prg_ram_bytes() + header.prg_nvram_bytes()];
+
+    if let Some(trainer) = cartridge.trainer() {
+        let start = (TRAINER_ADDR - 0x6000) as usize;
+        if ram.len() >= start + trainer.len() {
+            ram[start..start + trainer.len()].copy_from_slice(trainer);
+        }
+    }
+
+    ram
+}
+
+/// The pattern table memory: CHR ROM, or CHR RAM on boards without.
+#[derive(Debug, Clone, PartialEq)]
+struct Chr {
+    data: Vec<u8>,
+    is_ram: bool,
+}
+
+impl Chr {
+    fn new(cartridge: &Cartridge) -> Chr {
+        if cartridge.chr_rom().is_empty() {
+            let header = cartridge.header();
+            let size = header.chr_ram_bytes() + header.chr_nvram_bytes();
+
+            Chr {
+                data: vec![0x00; if size == 0 { 0x2000 } else { size }],
+                is_ram: true,
+            }
+        } else {
+            Chr {
+                data: cartridge.chr_rom().to_vec(),
+                is_ram: false,
+            }
+        }
+    }
+
+    fn read(&self, bank_size: usize, bank: usize, offset: usize) -> u8 {
+        self.data[bank_index(self.data.len(), bank_size, bank, offset)]
+    }
+
+    fn write(&mut self, bank_size: usize, bank: usize, offset: usize, val: u8) {
+        if self.is_ram {
+            let index = bank_index(self.data.len(), bank_size, bank, offset);
+            self.data[index] = val;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Each `bank_size` bytes filled with their bank number.
+    pub fn banked_rom(bank_size: usize, banks: usize) -> Vec<u8> {
+        (0..banks).flat_map(|bank| vec![bank as u8; bank_size]).collect()
+    }
+
+    /// An iNES image of the given mapper, with 16 KB PRG and 8 KB CHR banks.
+    pub fn cartridge(mapper: u16, flag6: u8, prg_rom: &[u8], chr_rom: &[u8]) -> Cartridge {
+        let mut image = vec![b'N', b'E', b'S', 0x1a];
+        image.push((prg_rom.len() / 0x4000) as u8);
+        image.push((chr_rom.len() / 0x2000) as u8);
+        image.push(flag6 | (mapper as u8) << 4);
+        image.push(mapper as u8 & 0xf0);
+        image.extend_from_slice(&[0x00; 8]);
+        image.extend_from_slice(prg_rom);
+        image.extend_from_slice(chr_rom);
+
+        Cartridge::from_bytes(&image).unwrap()
+    }
+
+    #[test]
+    fn vram_offsets() {
+        let offsets = |mirroring: Mirroring| {
+            [0x2000, 0x2400, 0x2800, 0x2c00, 0x3000]
+                .iter()
+                .map(|&addr| mirroring.vram_offset(addr + 0x12))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(offsets(Mirroring::Horizontal), [0x012, 0x012, 0x412, 0x412, 0x012]);
+        assert_eq!(offsets(Mirroring::Vertical), [0x012, 0x412, 0x012, 0x412, 0x012]);
+        assert_eq!(offsets(Mirroring::SingleScreenLower), [0x012; 5]);
+        assert_eq!(offsets(Mirroring::SingleScreenUpper), [0x412; 5]);
+        assert_eq!(offsets(Mirroring::FourScreen), [0x012, 0x412, 0x812, 0xc12, 0x012]);
+    }
+
+    #[test]
+    fn nametables_follow_mirroring() {
+        let mut nrom = Nrom::new(&cartridge(0, 0b0000_0001, &[0x00; 0x4000], &[]));
+        let mut vram = [0x00; 0x0800];
+
+        nrom.nametable_write(0x2c05, 0x12, &mut vram);
+
+        assert_eq!(vram[0x0405], 0x12);
+        assert_eq!(nrom.nametable_read(0x2405, &vram), 0x12);
+    }
+
+    #[test]
+    fn unsupported_mapper() {
+        let cartridge = cartridge(0xff, 0, &[0x00; 0x4000], &[]);
+
+        match from_cartridge(&cartridge) {
+            Err(RomError::UnsupportedMapper(0xff)) => {}
+            _ => panic!("mapper 255 should not be supported"),
+        }
+    }
+
+    #[test]
+    fn bank_index_wraps() {
+        assert_eq!(bank_index(0x8000, 0x4000, 1, 0x0123), 0x4123);
+        assert_eq!(bank_index(0x8000, 0x4000, 3, 0x0123), 0x4123);
+        assert_eq!(bank_index(0x4000, 0x8000, 0, 0x4123), 0x0123);
+    }
+}
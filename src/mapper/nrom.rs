@@ -0,0 +1,146 @@
+use rom::Cartridge;
+
+use super::{bank_index, prg_ram, Chr, Mapper, Mirroring};
+
+/// Mapper 0: 16 or 32 KB of PRG ROM and 8 KB of CHR, with no bank switching
+/// at all. A 16 KB ROM is mirrored into both halves of $8000-$FFFF.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(cartridge: &Cartridge) -> Nrom {
+        Nrom {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                self.prg_ram[(addr as usize - 0x6000) % self.prg_ram.len()]
+            }
+            0x8000..=0xffff if !self.prg_rom.is_empty() => {
+                self.prg_rom[bank_index(self.prg_rom.len(), 0x8000, 0, addr as usize - 0x8000)]
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let 0x6000..=0x7fff = addr {
+            if !self.prg_ram.is_empty() {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = val;
+            }
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, 0, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn prg_rom_16k_is_mirrored() {
+        let mut prg_rom = vec![0x00; 0x4000];
+        prg_rom[0x0000] = 0x12;
+        prg_rom[0x3ffc] = 0x34;
+        let nrom = Nrom::new(&cartridge(0, 0, &prg_rom, &[0x00; 0x2000]));
+
+        assert_eq!(nrom.cpu_peek(0x8000), 0x12);
+        assert_eq!(nrom.cpu_peek(0xc000), 0x12);
+        assert_eq!(nrom.cpu_peek(0xbffc), 0x34);
+        assert_eq!(nrom.cpu_peek(0xfffc), 0x34);
+    }
+
+    #[test]
+    fn prg_rom_32k() {
+        let nrom = Nrom::new(&cartridge(0, 0, &banked_rom(0x4000, 2), &[0x00; 0x2000]));
+
+        assert_eq!(nrom.cpu_peek(0x8000), 0);
+        assert_eq!(nrom.cpu_peek(0xc000), 1);
+    }
+
+    #[test]
+    fn prg_rom_is_read_only() {
+        let mut nrom = Nrom::new(&cartridge(0, 0, &banked_rom(0x4000, 2), &[0x00; 0x2000]));
+
+        nrom.cpu_write(0x8000, 0xff);
+
+        assert_eq!(nrom.cpu_read(0x8000), 0);
+    }
+
+    #[test]
+    fn prg_ram() {
+        let mut nrom = Nrom::new(&cartridge(0, 0, &[0x00; 0x4000], &[0x00; 0x2000]));
+
+        nrom.cpu_write(0x6123, 0x45);
+
+        assert_eq!(nrom.cpu_read(0x6123), 0x45);
+        assert_eq!(nrom.cpu_peek(0x5000), 0x00);
+    }
+
+    #[test]
+    fn chr_rom_is_read_only() {
+        let mut chr_rom = vec![0x00; 0x2000];
+        chr_rom[0x1234] = 0x56;
+        let mut nrom = Nrom::new(&cartridge(0, 0, &[0x00; 0x4000], &chr_rom));
+
+        nrom.ppu_write(0x1234, 0x78);
+
+        assert_eq!(nrom.ppu_read(0x1234), 0x56);
+    }
+
+    #[test]
+    fn chr_ram_without_chr_rom() {
+        let mut nrom = Nrom::new(&cartridge(0, 0, &[0x00; 0x4000], &[]));
+
+        nrom.ppu_write(0x1234, 0x78);
+
+        assert_eq!(nrom.ppu_read(0x1234), 0x78);
+    }
+
+    #[test]
+    fn mirroring_from_header() {
+        let nrom = Nrom::new(&cartridge(0, 0b0000_0001, &[0x00; 0x4000], &[]));
+
+        assert_eq!(nrom.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn trainer_is_in_prg_ram() {
+        let mut image = vec![b'N', b'E', b'S', 0x1a, 0x01, 0x00, 0b0000_0100, 0x00];
+        image.extend_from_slice(&[0x00; 8]);
+        image.extend(vec![0x99; 512]);
+        image.extend(vec![0x00; 0x4000]);
+        let nrom = Nrom::new(&Cartridge::from_bytes(&image).unwrap());
+
+        assert_eq!(nrom.cpu_peek(0x6fff), 0x00);
+        assert_eq!(nrom.cpu_peek(0x7000), 0x99);
+        assert_eq!(nrom.cpu_peek(0x71ff), 0x99);
+    }
+}
@@ -0,0 +1,115 @@
+use rom::Cartridge;
+
+use super::{has_bus_conflicts, read_bank, Chr, Mapper, Mirroring};
+
+/// Mapper 2: a switchable 16 KB PRG bank at $8000 and the last one fixed at
+/// $C000, with 8 KB of CHR RAM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uxrom {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    prg_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(cartridge: &Cartridge) -> Uxrom {
+        Uxrom {
+            prg_rom: cartridge.prg_rom().to_vec(),
+            chr: Chr::new(cartridge),
+            mirroring: Mirroring::from(cartridge.mirroring()),
+            bus_conflicts: has_bus_conflicts(cartridge),
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xbfff => {
+                read_bank(&self.prg_rom, 0x4000, self.prg_bank as usize, addr as usize)
+            }
+            0xc000..=0xffff => {
+                let last = (self.prg_rom.len() / 0x4000).saturating_sub(1);
+                read_bank(&self.prg_rom, 0x4000, last, addr as usize)
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.prg_bank = if self.bus_conflicts {
+                val & self.cpu_peek(addr)
+            } else {
+                val
+            };
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr.write(0x2000, 0, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    #[test]
+    fn banks() {
+        let mut uxrom = Uxrom::new(&cartridge(2, 0, &banked_rom(0x4000, 8), &[]));
+        assert_eq!(uxrom.cpu_peek(0x8000), 0);
+        assert_eq!(uxrom.cpu_peek(0xc000), 7);
+
+        uxrom.cpu_write(0x8000, 3);
+
+        assert_eq!(uxrom.cpu_peek(0xbfff), 3);
+        assert_eq!(uxrom.cpu_peek(0xffff), 7);
+    }
+
+    #[test]
+    fn chr_ram() {
+        let mut uxrom = Uxrom::new(&cartridge(2, 0, &banked_rom(0x4000, 8), &[]));
+
+        uxrom.ppu_write(0x1fff, 0x12);
+
+        assert_eq!(uxrom.ppu_read(0x1fff), 0x12);
+    }
+
+    #[test]
+    fn bus_conflicts() {
+        let mut cartridge = cartridge(2, 0, &banked_rom(0x4000, 8), &[]);
+        cartridge.header_mut().set_submapper(2);
+        let mut uxrom = Uxrom::new(&cartridge);
+
+        // The fixed bank reads 7 there, so 7 & 5 is written.
+        uxrom.cpu_write(0xc000, 5);
+        assert_eq!(uxrom.cpu_peek(0x8000), 5);
+
+        // Bank 5 reads 5 at $8000, so 5 & 6 is written.
+        uxrom.cpu_write(0x8000, 6);
+        assert_eq!(uxrom.cpu_peek(0x8000), 4);
+    }
+
+    #[test]
+    fn no_bus_conflicts_by_default() {
+        let mut uxrom = Uxrom::new(&cartridge(2, 0, &banked_rom(0x4000, 8), &[]));
+
+        uxrom.cpu_write(0x8000, 6);
+
+        assert_eq!(uxrom.cpu_peek(0x8000), 6);
+    }
+}
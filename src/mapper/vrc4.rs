@@ -0,0 +1,383 @@
+use rom::Cartridge;
+
+use super::vrc_irq::VrcIrq;
+use super::{prg_ram, read_bank, Chr, Mapper, Mirroring};
+
+/// The VRC2 and VRC4 variants. They only differ in which CPU address lines
+/// reach the chip's two register select pins, and in the VRC2 lacking the
+/// VRC4's IRQ counter, PRG swap mode and single-screen mirroring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vrc4Board {
+    Vrc2a,
+    Vrc2b,
+    Vrc2c,
+    Vrc4a,
+    Vrc4b,
+    Vrc4c,
+    Vrc4d,
+    Vrc4e,
+    Vrc4f,
+    /// A VRC4 of mapper 21, 23 or 25 without a submapper, listening on the
+    /// address lines of every variant of its mapper number. Games only
+    /// ever drive the lines of their own board, so this works for all of
+    /// them.
+    Unknown(u16),
+}
+
+impl Vrc4Board {
+    /// Mapper 22 is always the VRC2a. Mappers 21, 23 and 25 each cover
+    /// several boards, told apart by their NES 2.0 submapper.
+    pub fn detect(cartridge: &Cartridge) -> Vrc4Board {
+        match (cartridge.mapper_number(), cartridge.header().submapper()) {
+            (21, Some(1)) => Vrc4Board::Vrc4a,
+            (21, Some(2)) => Vrc4Board::Vrc4c,
+            (22, _) => Vrc4Board::Vrc2a,
+            (23, Some(1)) => Vrc4Board::Vrc4f,
+            (23, Some(2)) => Vrc4Board::Vrc4e,
+            (23, Some(3)) => Vrc4Board::Vrc2b,
+            (25, Some(1)) => Vrc4Board::Vrc4b,
+            (25, Some(2)) => Vrc4Board::Vrc4d,
+            (25, Some(3)) => Vrc4Board::Vrc2c,
+            (mapper, _) => Vrc4Board::Unknown(mapper),
+        }
+    }
+
+    pub fn is_vrc2(&self) -> bool {
+        matches!(*self, Vrc4Board::Vrc2a | Vrc4Board::Vrc2b | Vrc4Board::Vrc2c)
+    }
+
+    // The CPU address lines wired to the chip's A0 and A1.
+    fn address_lines(&self) -> (u16, u16) {
+        match *self {
+            Vrc4Board::Vrc2a | Vrc4Board::Vrc2c | Vrc4Board::Vrc4b => (0x02, 0x01),
+            Vrc4Board::Vrc2b | Vrc4Board::Vrc4f => (0x01, 0x02),
+            Vrc4Board::Vrc4a => (0x02, 0x04),
+            Vrc4Board::Vrc4c => (0x40, 0x80),
+            Vrc4Board::Vrc4d => (0x08, 0x04),
+            Vrc4Board::Vrc4e => (0x04, 0x08),
+            Vrc4Board::Unknown(21) => (0x42, 0x84),
+            Vrc4Board::Unknown(25) => (0x0a, 0x05),
+            Vrc4Board::Unknown(_) => (0x05, 0x0a),
+        }
+    }
+}
+
+/// Mappers 21, 22, 23 and 25: Konami's VRC2 and VRC4, with two switchable
+/// 8 KB PRG banks, eight 1 KB CHR banks and, on the VRC4, a CPU clocked
+/// IRQ counter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vrc4 {
+    board: Vrc4Board,
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; 8],
+    mirroring: Mirroring,
+    irq: VrcIrq,
+    // The one bit latch at $6000 of VRC2 boards without PRG RAM, which
+    // some games use as a copy protection check.
+    latch: u8,
+}
+
+impl Vrc4 {
+    pub fn new(cartridge: &Cartridge) -> Vrc4 {
+        Vrc4::with_board(cartridge, Vrc4Board::detect(cartridge))
+    }
+
+    pub fn with_board(cartridge: &Cartridge, board: Vrc4Board) -> Vrc4 {
+        Vrc4 {
+            board,
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            prg_banks: [0, 1],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            mirroring: Mirroring::Vertical,
+            irq: VrcIrq::new(),
+            latch: 0,
+        }
+    }
+
+    pub fn board(&self) -> Vrc4Board {
+        self.board
+    }
+
+    fn has_latch(&self) -> bool {
+        match self.board {
+            Vrc4Board::Unknown(_) => true,
+            board => board.is_vrc2(),
+        }
+    }
+
+    // Folds the CPU address to the register it selects, $8000-$F003.
+    fn register(&self, addr: u16) -> u16 {
+        let (a0, a1) = self.board.address_lines();
+
+        (addr & 0xf000) | (addr & a0 != 0) as u16 | ((addr & a1 != 0) as u16) << 1
+    }
+
+    // The 8 KB bank at $8000, $A000, $C000 or $E000.
+    fn prg_bank(&self, addr: u16) -> usize {
+        let second_last = (self.prg_rom.len() / 0x2000).saturating_sub(2);
+
+        match (addr >> 13) & 0x03 {
+            0 if self.prg_swap => second_last,
+            0 => self.prg_banks[0] as usize,
+            1 => self.prg_banks[1] as usize,
+            2 if self.prg_swap => self.prg_banks[0] as usize,
+            2 => second_last,
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[addr as usize >> 10] as usize;
+
+        // The VRC2a leaves the low bit of its CHR banks unconnected.
+        if self.board == Vrc4Board::Vrc2a {
+            bank >> 1
+        } else {
+            bank
+        }
+    }
+
+    // $B000-$E003 hold the CHR banks in nibbles: two registers for each.
+    fn write_chr_bank(&mut self, register: u16, val: u8) {
+        let bank = ((register - 0xb000) >> 12) as usize * 2 + (register as usize >> 1 & 0x01);
+        let old = self.chr_banks[bank];
+
+        self.chr_banks[bank] = if register & 0x01 == 0 {
+            (old & 0x1f0) | (val & 0x0f) as u16
+        } else {
+            let high = if self.board.is_vrc2() { 0x0f } else { 0x1f };
+            (old & 0x0f) | ((val & high) as u16) << 4
+        };
+    }
+
+    fn write_vrc4_register(&mut self, register: u16, val: u8) {
+        match register {
+            0x9000 | 0x9001 => {
+                self.mirroring = match val & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenLower,
+                    _ => Mirroring::SingleScreenUpper,
+                };
+            }
+            0x9002 => self.prg_swap = val & 0x02 != 0,
+            0xf000 => {
+                let latch = self.irq.latch();
+                self.irq.set_latch((latch & 0xf0) | (val & 0x0f));
+            }
+            0xf001 => {
+                let latch = self.irq.latch();
+                self.irq.set_latch((latch & 0x0f) | (val & 0x0f) << 4);
+            }
+            0xf002 => self.irq.write_control(val),
+            0xf003 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Vrc4 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            // An unknown board may be a VRC2, and a VRC4 leaves this open.
+            0x6000..=0x6fff if self.prg_ram.is_empty() && self.has_latch() => {
+                self.latch & 0x01
+            }
+            0x6000..=0x7fff => read_bank(&self.prg_ram, 0x2000, 0, addr as usize),
+            0x8000..=0xffff => read_bank(&self.prg_rom, 0x2000, self.prg_bank(addr), addr as usize),
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        let register = self.register(addr);
+
+        match addr {
+            0x6000..=0x6fff if self.prg_ram.is_empty() => self.latch = val,
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = val;
+            }
+            0x8000..=0x8fff => self.prg_banks[0] = val & 0x1f,
+            0x9000..=0x9fff if self.board.is_vrc2() => {
+                self.mirroring = if val & 0x01 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            0xa000..=0xafff => self.prg_banks[1] = val & 0x1f,
+            0xb000..=0xefff => self.write_chr_bank(register, val),
+            0x9000..=0x9fff | 0xf000..=0xffff if !self.board.is_vrc2() => {
+                self.write_vrc4_register(register, val)
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x0400, self.chr_bank(addr), addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let bank = self.chr_bank(addr);
+        self.chr.write(0x0400, bank, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    fn vrc4(board: Vrc4Board) -> Vrc4 {
+        let cartridge = cartridge(23, 0, &banked_rom(0x2000, 16), &banked_rom(0x0400, 64));
+
+        Vrc4::with_board(&cartridge, board)
+    }
+
+    #[test]
+    fn detect() {
+        let mut cartridge = cartridge(25, 0, &banked_rom(0x2000, 16), &[]);
+        assert_eq!(Vrc4Board::detect(&cartridge), Vrc4Board::Unknown(25));
+
+        cartridge.header_mut().set_submapper(2);
+        assert_eq!(Vrc4Board::detect(&cartridge), Vrc4Board::Vrc4d);
+
+        cartridge.header_mut().set_mapper_number(22);
+        assert_eq!(Vrc4Board::detect(&cartridge), Vrc4Board::Vrc2a);
+    }
+
+    #[test]
+    fn address_lines() {
+        let registers = |board: Vrc4Board, addrs: [u16; 4]| {
+            let vrc4 = vrc4(board);
+            addrs.iter().map(|&addr| vrc4.register(0xb000 | addr)).collect::<Vec<_>>()
+        };
+        let expected = [0xb000, 0xb001, 0xb002, 0xb003];
+
+        assert_eq!(registers(Vrc4Board::Vrc4a, [0x00, 0x02, 0x04, 0x06]), expected);
+        assert_eq!(registers(Vrc4Board::Vrc4b, [0x00, 0x02, 0x01, 0x03]), expected);
+        assert_eq!(registers(Vrc4Board::Vrc4c, [0x00, 0x40, 0x80, 0xc0]), expected);
+        assert_eq!(registers(Vrc4Board::Vrc4d, [0x00, 0x08, 0x04, 0x0c]), expected);
+        assert_eq!(registers(Vrc4Board::Vrc4e, [0x00, 0x04, 0x08, 0x0c]), expected);
+        assert_eq!(registers(Vrc4Board::Vrc4f, [0x00, 0x01, 0x02, 0x03]), expected);
+        assert_eq!(registers(Vrc4Board::Unknown(23), [0x00, 0x04, 0x08, 0x0c]), expected);
+        assert_eq!(registers(Vrc4Board::Unknown(23), [0x00, 0x01, 0x02, 0x03]), expected);
+    }
+
+    #[test]
+    fn prg_banks() {
+        let mut vrc4 = vrc4(Vrc4Board::Vrc4f);
+        vrc4.cpu_write(0x8000, 3);
+        vrc4.cpu_write(0xa000, 4);
+
+        assert_eq!(vrc4.cpu_peek(0x8000), 3);
+        assert_eq!(vrc4.cpu_peek(0xa000), 4);
+        assert_eq!(vrc4.cpu_peek(0xc000), 14);
+        assert_eq!(vrc4.cpu_peek(0xe000), 15);
+
+        vrc4.cpu_write(0x9002, 0x02);
+
+        assert_eq!(vrc4.cpu_peek(0x8000), 14);
+        assert_eq!(vrc4.cpu_peek(0xc000), 3);
+    }
+
+    #[test]
+    fn chr_banks() {
+        let mut vrc4 = vrc4(Vrc4Board::Vrc4f);
+        vrc4.cpu_write(0xe002, 0x02);
+        vrc4.cpu_write(0xe003, 0x02);
+
+        assert_eq!(vrc4.ppu_peek(0x1c00), 34);
+    }
+
+    #[test]
+    fn vrc2a_drops_low_chr_bit() {
+        let mut vrc2 = vrc4(Vrc4Board::Vrc2a);
+        vrc2.cpu_write(0xb000, 0x07);
+
+        assert_eq!(vrc2.ppu_peek(0x0000), 3);
+    }
+
+    #[test]
+    fn mirroring() {
+        let mut vrc2 = vrc4(Vrc4Board::Vrc2b);
+        vrc2.cpu_write(0x9002, 0x03);
+        assert_eq!(vrc2.mirroring(), Mirroring::Horizontal);
+
+        let mut vrc4 = vrc4(Vrc4Board::Vrc4f);
+        vrc4.cpu_write(0x9000, 0x03);
+        assert_eq!(vrc4.mirroring(), Mirroring::SingleScreenUpper);
+    }
+
+    #[test]
+    fn vrc2_latch() {
+        let mut vrc2 = vrc4(Vrc4Board::Vrc2a);
+        vrc2.prg_ram.clear();
+
+        vrc2.cpu_write(0x6000, 0xff);
+
+        assert_eq!(vrc2.cpu_peek(0x6000), 0x01);
+    }
+
+    #[test]
+    fn unknown_board_latch() {
+        let mut vrc = Vrc4::new(&cartridge(23, 0, &banked_rom(0x2000, 16), &[]));
+        assert_eq!(vrc.board(), Vrc4Board::Unknown(23));
+        vrc.prg_ram.clear();
+
+        vrc.cpu_write(0x6000, 0xff);
+
+        assert_eq!(vrc.cpu_peek(0x6000), 0x01);
+    }
+
+    #[test]
+    fn irq() {
+        let mut vrc4 = vrc4(Vrc4Board::Vrc4e);
+        vrc4.cpu_write(0xf000, 0x0e);
+        vrc4.cpu_write(0xf004, 0x0f);
+        vrc4.cpu_write(0xf008, 0x06);
+
+        vrc4.cpu_clock();
+        assert!(!vrc4.irq());
+        vrc4.cpu_clock();
+        assert!(vrc4.irq());
+
+        vrc4.cpu_write(0xf00c, 0x00);
+        assert!(!vrc4.irq());
+    }
+
+    #[test]
+    fn vrc2_has_no_irq() {
+        let mut vrc2 = vrc4(Vrc4Board::Vrc2b);
+        vrc2.cpu_write(0xf000, 0x0f);
+        vrc2.cpu_write(0xf001, 0x0f);
+        vrc2.cpu_write(0xf002, 0x06);
+
+        vrc2.cpu_clock();
+
+        assert!(!vrc2.irq());
+    }
+}
@@ -0,0 +1,250 @@
+use rom::Cartridge;
+
+use super::vrc_irq::VrcIrq;
+use super::{prg_ram, read_bank, Chr, Mapper, Mirroring};
+
+/// The two ways the VRC6 is wired to the CPU's address lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vrc6Board {
+    /// Mapper 24, Akumajou Densetsu: A0 and A1 in order.
+    Vrc6a,
+    /// Mapper 26, Madara and Esper Dream 2: A0 and A1 swapped.
+    Vrc6b,
+}
+
+impl Vrc6Board {
+    pub fn detect(cartridge: &Cartridge) -> Vrc6Board {
+        match cartridge.mapper_number() {
+            26 => Vrc6Board::Vrc6b,
+            _ => Vrc6Board::Vrc6a,
+        }
+    }
+}
+
+/// Mappers 24 and 26: Konami's VRC6, with a 16 KB and an 8 KB PRG bank,
+/// 1 or 2 KB CHR banks and a CPU clocked IRQ counter. Its expansion audio
+/// is not emulated, and neither are the CHR ROM nametables of $B003 which
+/// no game uses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vrc6 {
+    board: Vrc6Board,
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Chr,
+
+    prg_bank_16k: u8,
+    prg_bank_8k: u8,
+    chr_banks: [u8; 8],
+    ppu_banking: u8,
+    irq: VrcIrq,
+}
+
+impl Vrc6 {
+    pub fn new(cartridge: &Cartridge) -> Vrc6 {
+        Vrc6 {
+            board: Vrc6Board::detect(cartridge),
+            prg_rom: cartridge.prg_rom().to_vec(),
+            prg_ram: prg_ram(cartridge),
+            chr: Chr::new(cartridge),
+            prg_bank_16k: 0,
+            prg_bank_8k: 0,
+            chr_banks: [0; 8],
+            ppu_banking: 0,
+            irq: VrcIrq::new(),
+        }
+    }
+
+    pub fn board(&self) -> Vrc6Board {
+        self.board
+    }
+
+    // Folds the CPU address to the register it selects, $8000-$F003.
+    fn register(&self, addr: u16) -> u16 {
+        let select = match self.board {
+            Vrc6Board::Vrc6a => addr & 0x03,
+            Vrc6Board::Vrc6b => (addr & 0x01) << 1 | (addr & 0x02) >> 1,
+        };
+
+        (addr & 0xf000) | select
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.ppu_banking & 0x80 != 0 && !self.prg_ram.is_empty()
+    }
+
+    // The 8 KB bank at $8000-$FFFF.
+    fn prg_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x8000..=0xbfff => self.prg_bank_16k as usize * 2 + (addr as usize >> 13 & 0x01),
+            0xc000..=0xdfff => self.prg_bank_8k as usize,
+            _ => (self.prg_rom.len() / 0x2000).saturating_sub(1),
+        }
+    }
+
+    // The 1 KB bank at a pattern table address. In the 2 KB modes, bit 5
+    // of $B003 lets PPU A10 pick the half of the bank.
+    fn chr_bank(&self, addr: u16) -> usize {
+        let slot = addr as usize >> 10;
+        let two_k = |register: usize| {
+            let bank = self.chr_banks[register] as usize;
+            if self.ppu_banking & 0x20 != 0 {
+                (bank & !0x01) | (slot & 0x01)
+            } else {
+                bank
+            }
+        };
+
+        match self.ppu_banking & 0x03 {
+            0 => self.chr_banks[slot] as usize,
+            1 => two_k(slot / 2),
+            _ if slot < 4 => self.chr_banks[slot] as usize,
+            _ => two_k(4 + (slot - 4) / 2),
+        }
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled() => {
+                read_bank(&self.prg_ram, 0x2000, 0, addr as usize)
+            }
+            0x8000..=0xffff => read_bank(&self.prg_rom, 0x2000, self.prg_bank(addr), addr as usize),
+            _ => 0x00,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match (addr, self.register(addr)) {
+            (0x6000..=0x7fff, _) if self.prg_ram_enabled() => {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = val;
+            }
+            (_, 0x8000..=0x8003) => self.prg_bank_16k = val & 0x0f,
+            (_, 0xb003) => self.ppu_banking = val,
+            (_, 0xc000..=0xc003) => self.prg_bank_8k = val & 0x1f,
+            (_, register @ 0xd000..=0xd003) => self.chr_banks[register as usize & 0x03] = val,
+            (_, register @ 0xe000..=0xe003) => {
+                self.chr_banks[4 + (register as usize & 0x03)] = val
+            }
+            (_, 0xf000) => self.irq.set_latch(val),
+            (_, 0xf001) => self.irq.write_control(val),
+            (_, 0xf002) => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+    }
+
+    fn ppu_peek(&self, addr: u16) -> u8 {
+        self.chr.read(0x0400, self.chr_bank(addr), addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let bank = self.chr_bank(addr);
+        self.chr.write(0x0400, bank, addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.ppu_banking >> 2 & 0x03 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use mapper::test::{banked_rom, cartridge};
+
+    fn vrc6(mapper: u16) -> Vrc6 {
+        Vrc6::new(&cartridge(mapper, 0, &banked_rom(0x2000, 16), &banked_rom(0x0400, 64)))
+    }
+
+    #[test]
+    fn prg_banks() {
+        let mut vrc6 = vrc6(24);
+        vrc6.cpu_write(0x8000, 2);
+        vrc6.cpu_write(0xc000, 9);
+
+        assert_eq!(vrc6.cpu_peek(0x8000), 4);
+        assert_eq!(vrc6.cpu_peek(0xa000), 5);
+        assert_eq!(vrc6.cpu_peek(0xc000), 9);
+        assert_eq!(vrc6.cpu_peek(0xe000), 15);
+    }
+
+    #[test]
+    fn without_prg_rom() {
+        let vrc6 = Vrc6::new(&cartridge(24, 0, &[], &[]));
+
+        assert_eq!(vrc6.cpu_peek(0xe000), 0x00);
+    }
+
+    #[test]
+    fn vrc6b_swaps_address_lines() {
+        let mut vrc6 = vrc6(26);
+        assert_eq!(vrc6.board(), Vrc6Board::Vrc6b);
+
+        vrc6.cpu_write(0xd001, 7);
+        vrc6.cpu_write(0xb003, 0x24);
+
+        assert_eq!(vrc6.ppu_peek(0x0800), 7);
+        assert_eq!(vrc6.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn chr_modes() {
+        let mut vrc6 = vrc6(24);
+        for register in 0..4 {
+            vrc6.cpu_write(0xd000 + register, 10 + register as u8);
+            vrc6.cpu_write(0xe000 + register, 20 + register as u8);
+        }
+        let banks = |vrc6: &Vrc6| (0..8).map(|i| vrc6.ppu_peek(i * 0x0400)).collect::<Vec<_>>();
+
+        vrc6.cpu_write(0xb003, 0x20);
+        assert_eq!(banks(&vrc6), [10, 11, 12, 13, 20, 21, 22, 23]);
+
+        vrc6.cpu_write(0xb003, 0x21);
+        assert_eq!(banks(&vrc6), [10, 11, 10, 11, 12, 13, 12, 13]);
+
+        vrc6.cpu_write(0xb003, 0x22);
+        assert_eq!(banks(&vrc6), [10, 11, 12, 13, 20, 21, 20, 21]);
+
+        vrc6.cpu_write(0xb003, 0x01);
+        assert_eq!(banks(&vrc6), [10, 10, 11, 11, 12, 12, 13, 13]);
+    }
+
+    #[test]
+    fn prg_ram_enable() {
+        let mut vrc6 = vrc6(24);
+        vrc6.cpu_write(0x6000, 0x12);
+        assert_eq!(vrc6.cpu_peek(0x6000), 0x00);
+
+        vrc6.cpu_write(0xb003, 0x80);
+        vrc6.cpu_write(0x6000, 0x12);
+        assert_eq!(vrc6.cpu_peek(0x6000), 0x12);
+    }
+
+    #[test]
+    fn irq() {
+        let mut vrc6 = vrc6(24);
+        vrc6.cpu_write(0xf000, 0xff);
+        vrc6.cpu_write(0xf001, 0x06);
+
+        vrc6.cpu_clock();
+        assert!(vrc6.irq());
+
+        vrc6.cpu_write(0xf002, 0x00);
+        assert!(!vrc6.irq());
+    }
+}
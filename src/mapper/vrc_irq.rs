@@ -0,0 +1,147 @@
+/// The IRQ counter Konami put in the VRC4, VRC6 and VRC7. It is clocked by
+/// the CPU, either on every cycle or through a prescaler which divides the
+/// CPU clock down to one scanline's worth, 341 PPU dots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enabled_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub fn new() -> VrcIrq {
+        VrcIrq {
+            latch: 0,
+            counter: 0,
+            prescaler: 341,
+            enabled: false,
+            enabled_after_ack: false,
+            cycle_mode: false,
+            pending: false,
+        }
+    }
+
+    pub fn set_latch(&mut self, latch: u8) {
+        self.latch = latch;
+    }
+
+    pub fn latch(&self) -> u8 {
+        self.latch
+    }
+
+    /// Bit 0 re-enables the counter on acknowledge, bit 1 enables it and
+    /// reloads it from the latch, and bit 2 selects cycle mode.
+    pub fn write_control(&mut self, val: u8) {
+        self.enabled_after_ack = val & 0x01 != 0;
+        self.enabled = val & 0x02 != 0;
+        self.cycle_mode = val & 0x04 != 0;
+        self.pending = false;
+
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = 341;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enabled_after_ack;
+    }
+
+    /// Called once per CPU cycle.
+    pub fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.cycle_mode {
+            self.clock_counter();
+        } else {
+            self.prescaler -= 3;
+            if self.prescaler <= 0 {
+                self.prescaler += 341;
+                self.clock_counter();
+            }
+        }
+    }
+
+    // The counter counts up and fires when it overflows.
+    fn clock_counter(&mut self) {
+        if self.counter == 0xff {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clock(irq: &mut VrcIrq, cycles: usize) {
+        for _ in 0..cycles {
+            irq.clock();
+        }
+    }
+
+    #[test]
+    fn cycle_mode() {
+        let mut irq = VrcIrq::new();
+        irq.set_latch(0xfd);
+        irq.write_control(0x06);
+
+        clock(&mut irq, 2);
+        assert!(!irq.pending());
+        clock(&mut irq, 1);
+        assert!(irq.pending());
+    }
+
+    #[test]
+    fn scanline_mode() {
+        let mut irq = VrcIrq::new();
+        irq.set_latch(0xfe);
+        irq.write_control(0x02);
+
+        // Two scanlines are 682 dots, or 227⅓ CPU cycles.
+        clock(&mut irq, 227);
+        assert!(!irq.pending());
+        clock(&mut irq, 1);
+        assert!(irq.pending());
+    }
+
+    #[test]
+    fn reloads_from_latch() {
+        let mut irq = VrcIrq::new();
+        irq.set_latch(0xff);
+        irq.write_control(0x07);
+
+        clock(&mut irq, 1);
+        irq.acknowledge();
+        clock(&mut irq, 1);
+
+        assert!(irq.pending());
+    }
+
+    #[test]
+    fn acknowledge() {
+        let mut irq = VrcIrq::new();
+        irq.set_latch(0xff);
+        irq.write_control(0x06);
+        clock(&mut irq, 1);
+
+        irq.acknowledge();
+        clock(&mut irq, 256);
+
+        assert!(!irq.pending());
+    }
+}
@@ -1,7 +1,8 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn adc(registers: &mut Registers, val: u8) {
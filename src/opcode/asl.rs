@@ -1,7 +1,8 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn asl(registers: &mut Registers, val: u8) -> u8 {
@@ -14,7 +15,7 @@ fn asl(registers: &mut Registers, val: u8) -> u8 {
     new_val
 }
 
-pub fn asl_register_a(registers: &mut Registers, _mem: &mut Memory) -> Cycle {
+pub fn asl_register_a(registers: &mut Registers, _mem: &mut dyn Bus) -> Cycle {
     let val = registers.a;
 
     let new_val = asl(registers, val);
@@ -3,7 +3,8 @@ use super::utils;
 use super::utils::mem;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn beq(registers: &mut Registers, offset: u8) -> Cycle {
@@ -19,7 +20,7 @@ fn beq(registers: &mut Registers, offset: u8) -> Cycle {
     }
 }
 
-pub fn beq_relative(registers: &mut Registers, mem: &mut Memory) -> Cycle {
+pub fn beq_relative(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
     let val = mem::read_rel(mem, registers);
 
     beq(registers, val)
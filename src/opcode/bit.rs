@@ -2,7 +2,8 @@ use super::Cycle;
 use super::utils;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn bit(registers: &mut Registers, val: u8) {
@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn clc(registers: &mut Registers) {
     registers.set_carry_flag(false);
 }
 
-pub fn clc_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn clc_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     clc(registers);
 
     Cycle(2)
@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn cld(registers: &mut Registers) {
     registers.set_decimal_mode_flag(false);
 }
 
-pub fn cld_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn cld_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     cld(registers);
 
     Cycle(2)
@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn cli(registers: &mut Registers) {
     registers.set_interrupt_disable_flag(false);
 }
 
-pub fn cli_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn cli_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     cli(registers);
 
     Cycle(2)
@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn clv(registers: &mut Registers) {
     registers.set_overflow_flag(false);
 }
 
-pub fn clv_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn clv_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     clv(registers);
 
     Cycle(2)
@@ -1,8 +1,8 @@
 use opcode::Cycle;
-use opcode::OpCode;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn lda(registers: &mut Registers, val: u8) {
@@ -28,7 +28,11 @@ opcode_fn_with_mode!(indirect_y -> (lda_indirect_y, lda,
 mod test {
     use super::*;
 
+    use opcode::OpCode;
+
     use cpu::RP2A03;
+    #[cfg(feature = "alloc")]
+    use cpu::{Flag, Register};
 
     use opcode::utils::test::*;
 
@@ -46,23 +50,18 @@ mod test {
                     cpu.memory.write(0, $opcode.into());
                     $arrange_fn(&mut cpu, $expected_reg_a);
 
-                    let mem_snapshot = cpu.memory.clone();
-                    let regs_snaptshot = cpu.registers.clone();
+                    #[cfg(feature = "alloc")]
+                    let before = cpu.snapshot();
 
                     cpu.execute();
 
-                    assert_eq!(cpu.memory, mem_snapshot);
                     assert_eq!(cpu.registers.a, $expected_reg_a);
                     assert_eq!(cpu.registers.zero_flag(), $zero_flag);
                     assert_eq!(cpu.registers.sign_flag(), $sign_flag);
-                    assert_field_eq!(cpu.registers, regs_snaptshot, [
-                        carry_flag(),
-                        interrupt_disable_flag(),
-                        decimal_mode_flag(),
-                        break_command_flag(),
-                        overflow_flag()
-                    ]);
-                    assert_field_eq!(cpu.registers, regs_snaptshot, [sp, x, y]);
+                    #[cfg(feature = "alloc")]
+                    assert!(before.diff(&cpu.snapshot())
+                                  .only_changed(&[Register::A, Register::Pc],
+                                                &[Flag::Zero, Flag::Sign]));
                 }
             }
         );
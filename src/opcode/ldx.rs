@@ -1,8 +1,8 @@
 use super::Cycle;
-use super::OpCode;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn ldx(registers: &mut Registers, val: u8) {
@@ -23,7 +23,11 @@ opcode_fn_with_mode!(abs_y -> (ldx_abs_y, ldx,
 mod test {
     use super::*;
 
+    use opcode::OpCode;
+
     use cpu::RP2A03;
+    #[cfg(feature = "alloc")]
+    use cpu::{Flag, Register};
 
     use opcode::utils::test::*;
 
@@ -41,23 +45,18 @@ mod test {
                     cpu.memory.write(0, $opcode.into());
                     $arrange_fn(&mut cpu, $expected_reg_x);
 
-                    let mem_snapshot = cpu.memory.clone();
-                    let regs_snaptshot = cpu.registers.clone();
+                    #[cfg(feature = "alloc")]
+                    let before = cpu.snapshot();
 
                     cpu.execute();
 
-                    assert_eq!(cpu.memory, mem_snapshot);
                     assert_eq!(cpu.registers.x, $expected_reg_x);
                     assert_eq!(cpu.registers.zero_flag(), $zero_flag);
                     assert_eq!(cpu.registers.sign_flag(), $sign_flag);
-                    assert_field_eq!(cpu.registers, regs_snaptshot, [
-                        carry_flag(),
-                        interrupt_disable_flag(),
-                        decimal_mode_flag(),
-                        break_command_flag(),
-                        overflow_flag()
-                    ]);
-                    assert_field_eq!(cpu.registers, regs_snaptshot, [sp, a, y]);
+                    #[cfg(feature = "alloc")]
+                    assert!(before.diff(&cpu.snapshot())
+                                  .only_changed(&[Register::X, Register::Pc],
+                                                &[Flag::Zero, Flag::Sign]));
                 }
             }
         );
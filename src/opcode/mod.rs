@@ -1,5 +1,5 @@
 #[macro_use]
-mod utils;
+pub(crate) mod utils;
 
 mod adc;
 mod and;
@@ -21,14 +21,14 @@ mod lda;
 mod ldx;
 mod ldy;
 mod nop;
+mod rts;
 mod sec;
 mod sed;
 mod sei;
 
-use std::convert::From;
-
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 use self::adc::*;
 use self::and::*;
@@ -50,12 +50,22 @@ use self::lda::*;
 use self::ldx::*;
 use self::ldy::*;
 use self::nop::*;
+use self::rts::*;
 use self::sec::*;
 use self::sed::*;
 use self::sei::*;
 
 pub struct Cycle(pub u32);
 
+/// Opcodes which lock up the CPU until the next reset, also known as KIL.
+const JAM_OPCODES: [u8; 12] = [
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2,
+];
+
+pub fn is_jam(byte: u8) -> bool {
+    JAM_OPCODES.contains(&byte)
+}
+
 macro_rules! opcodes {
     (
         $(
@@ -80,7 +90,7 @@ macro_rules! opcodes {
                 }
             }
 
-            pub fn get_fn(&self) -> fn(&mut Registers, &mut Memory) -> Cycle {
+            pub fn get_fn(&self) -> fn(&mut Registers, &mut dyn Bus) -> Cycle {
                 match self {
                     $(
                         &OpCode::$opcode => $opcode_fn,
@@ -89,20 +99,29 @@ macro_rules! opcodes {
             }
         }
 
-        impl From<u8> for OpCode {
-            fn from(byte: u8) -> OpCode {
+        impl OpCode {
+            pub fn decode(byte: u8) -> Option<OpCode> {
                 match byte {
                     $(
-                        $opcode_hex => OpCode::$opcode,
+                        $opcode_hex => Some(OpCode::$opcode),
                     )*
-                    _ => unreachable!(),
+                    _ => None,
                 }
             }
         }
 
-        impl Into<u8> for OpCode {
-            fn into(self) -> u8 {
-                match self {
+        impl From<u8> for OpCode {
+            fn from(byte: u8) -> OpCode {
+                match OpCode::decode(byte) {
+                    Some(opcode) => opcode,
+                    None => unreachable!(),
+                }
+            }
+        }
+
+        impl From<OpCode> for u8 {
+            fn from(opcode: OpCode) -> u8 {
+                match opcode {
                     $(
                         OpCode::$opcode => $opcode_hex,
                     )*
@@ -187,6 +206,8 @@ opcodes!(
 
     (Nop, 0xEA, 0, nop),
 
+    (Rts, 0x60, 0, rts_implied),
+
     (Sec, 0x38, 0, sec_implied),
 
     (Sed, 0xF8, 0, sed_implied),
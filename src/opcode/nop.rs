@@ -1,16 +1,16 @@
 use super::Cycle;
-use super::OpCode;
 
 use cpu::Registers;
-use cpu::Memory;
 
-pub fn nop(_registers: &mut Registers, _mem: &mut Memory) -> Cycle {
+use bus::Bus;
+
+pub fn nop(_registers: &mut Registers, _mem: &mut dyn Bus) -> Cycle {
     Cycle(2)
 }
 
 #[cfg(test)]
 mod test {
-    use super::*;
+    use opcode::OpCode;
 
     use cpu::RP2A03;
 
@@ -0,0 +1,78 @@
+use super::Cycle;
+use super::utils::stack;
+
+use cpu::Registers;
+
+use bus::Bus;
+
+#[inline]
+fn rts(registers: &mut Registers, mem: &mut dyn Bus) {
+    let addr_low = stack::pull(registers, mem) as u16;
+    let addr_high = stack::pull(registers, mem) as u16;
+
+    // JSR pushes the address of its last byte, the usual pc increment after
+    // the opcode lands on the following instruction.
+    registers.pc = (addr_high << 8) | addr_low;
+}
+
+pub fn rts_implied(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
+    rts(registers, mem);
+
+    Cycle(6)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use opcode::OpCode;
+
+    use cpu::{Memory, RP2A03};
+
+    #[test]
+    fn rts_pulls_pc_from_stack() {
+        let mut registers = Registers::new();
+        let mut mem = Memory::new();
+        registers.sp = 0xfd;
+        mem.write(0x01fe, 0x33);
+        mem.write(0x01ff, 0x12);
+
+        rts(&mut registers, &mut mem);
+
+        assert_eq!(registers.pc, 0x1233);
+        assert_eq!(registers.sp, 0xff);
+    }
+
+    #[test]
+    fn rts_wraps_stack_pointer() {
+        let mut registers = Registers::new();
+        let mut mem = Memory::new();
+        registers.sp = 0xff;
+        mem.write(0x0100, 0x33);
+        mem.write(0x0101, 0x12);
+
+        rts(&mut registers, &mut mem);
+
+        assert_eq!(registers.pc, 0x1233);
+        assert_eq!(registers.sp, 0x01);
+    }
+
+    #[test]
+    fn rts_implied() {
+        let mut cpu = RP2A03::new();
+        cpu.memory.write(0x0400, OpCode::Rts.into());
+        cpu.memory.write(0x01fe, 0x33);
+        cpu.memory.write(0x01ff, 0x12);
+        cpu.registers.pc = 0x0400;
+        cpu.registers.sp = 0xfd;
+
+        let regs_snaptshot = cpu.registers.clone();
+
+        let Cycle(cycles) = cpu.execute();
+
+        assert_eq!(cycles, 6);
+        assert_eq!(cpu.registers.pc, 0x1234);
+        assert_eq!(cpu.registers.p(), regs_snaptshot.p());
+        assert_field_eq!(cpu.registers, regs_snaptshot, [a, x, y]);
+    }
+}
@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn sec(registers: &mut Registers) {
     registers.set_carry_flag(true);
 }
 
-pub fn sec_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn sec_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     sec(registers);
 
     Cycle(2)
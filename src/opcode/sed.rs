@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn sed(registers: &mut Registers) {
     registers.set_decimal_mode_flag(true);
 }
 
-pub fn sed_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn sed_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     sed(registers);
 
     Cycle(2)
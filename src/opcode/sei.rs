@@ -1,14 +1,15 @@
 use super::Cycle;
 
 use cpu::Registers;
-use cpu::Memory;
+
+use bus::Bus;
 
 #[inline]
 fn sei(registers: &mut Registers) {
     registers.set_interrupt_disable_flag(true);
 }
 
-pub fn sei_implied(registers: &mut Registers, _: &mut Memory) -> Cycle {
+pub fn sei_implied(registers: &mut Registers, _: &mut dyn Bus) -> Cycle {
     sei(registers);
 
     Cycle(2)
@@ -11,8 +11,8 @@ pub fn rel_addr(pc: u16, val: u8) -> (u16, bool) {
     let negtive = (val & 0b10000000) > 0;
 
     let new_pc = match negtive {
-        true => pc - unsigned_val,
-        false => pc + unsigned_val,
+        true => pc.wrapping_sub(unsigned_val),
+        false => pc.wrapping_add(unsigned_val),
     };
 
     (new_pc, new_page(pc, new_pc))
@@ -94,7 +94,7 @@ macro_rules! set_flag(
 macro_rules! gen_opcode_fn {
     ($fn_name: ident, $instruction: expr, $cycles_num: expr, $addressing_mode: expr) => {
 
-        pub fn $fn_name(registers: &mut Registers, mem: &mut Memory) -> Cycle {
+        pub fn $fn_name(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
             let val = $addressing_mode(mem, registers);
 
             $instruction(registers, val);
@@ -108,7 +108,7 @@ macro_rules! gen_opcode_fn {
      or_else $normal_cycles_num: expr,
      $addressing_mode: expr) => {
 
-        pub fn $fn_name(registers: &mut Registers, mem: &mut Memory) -> Cycle {
+        pub fn $fn_name(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
             let (val, page_crossed) = $addressing_mode(mem, registers);
 
             $instruction(registers, val);
@@ -124,10 +124,12 @@ macro_rules! gen_opcode_fn {
     (addr ->
      $fn_name: ident, $instruction: expr, $cycles_num: expr, $addressing_mode: expr) => {
 
-        pub fn $fn_name(registers: &mut Registers, mem: &mut Memory) -> Cycle {
+        pub fn $fn_name(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
             let addr = $addressing_mode(mem, registers);
             let val = mem.read(addr);
 
+            // Like the real CPU, write the old value back before the result.
+            mem.write(addr, val);
             let result = $instruction(registers, val);
             mem.write(addr, result);
 
@@ -139,10 +141,12 @@ macro_rules! gen_opcode_fn {
     (addr_ignore_page_crossing ->
      $fn_name: ident, $instruction: expr, $cycles_num: expr, $addressing_mode: expr) => {
 
-        pub fn $fn_name(registers: &mut Registers, mem: &mut Memory) -> Cycle {
+        pub fn $fn_name(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
             let (addr, _page_crossed) = $addressing_mode(mem, registers);
             let val = mem.read(addr);
 
+            // Like the real CPU, write the old value back before the result.
+            mem.write(addr, val);
             let result = $instruction(registers, val);
             mem.write(addr, result);
 
@@ -154,7 +158,7 @@ macro_rules! gen_opcode_fn {
 #[macro_export]
 macro_rules! opcode_fn_with_mode(
     (acc -> ($fn_name: ident, $instruction: expr, $cycles_num: expr)) => {
-        pub fn $fn_name(registers: &mut Registers, mem: &mut Memory) -> Cycle {
+        pub fn $fn_name(registers: &mut Registers, mem: &mut dyn Bus) -> Cycle {
             $instruction(registers, old_a);
 
             $cycles_num
@@ -253,9 +257,10 @@ macro_rules! opcode_fn_with_mode(
 );
 
 pub mod mem {
-    use cpu::Memory;
     use cpu::Registers;
 
+    use bus::Bus;
+
     #[inline]
     fn compose_addr(addr_high: u8, addr_low: u8) -> u16 {
         ((addr_high as u16) << 8) + addr_low as u16
@@ -276,165 +281,191 @@ pub mod mem {
     }
 
     #[inline]
-    pub fn get_imm_addr(_mem: &Memory, registers: &Registers) -> u16 {
+    pub fn get_imm_addr(_mem: &mut dyn Bus, registers: &Registers) -> u16 {
         let pc = registers.pc;
 
-        (pc + 1) as u16
+        pc.wrapping_add(1)
     }
 
     #[inline]
-    pub fn read_imm(mem: &Memory, registers: &Registers) -> u8 {
-        mem.read(get_imm_addr(mem, registers))
+    pub fn read_imm(mem: &mut dyn Bus, registers: &Registers) -> u8 {
+        let addr = get_imm_addr(mem, registers);
+
+        mem.read(addr)
     }
 
     #[inline]
-    pub fn get_zero_page_addr(mem: &Memory, registers: &Registers) -> u16 {
+    pub fn get_zero_page_addr(mem: &mut dyn Bus, registers: &Registers) -> u16 {
         let pc = registers.pc;
-        let addr = mem.read((pc + 1) as u16) as u16;
 
-        addr
+        mem.read(pc.wrapping_add(1)) as u16
     }
 
     #[inline]
-    pub fn read_zero_page(mem: &Memory, registers: &Registers) -> u8 {
-        mem.read(get_zero_page_addr(mem, registers))
+    pub fn read_zero_page(mem: &mut dyn Bus, registers: &Registers) -> u8 {
+        let addr = get_zero_page_addr(mem, registers);
+
+        mem.read(addr)
     }
 
     #[inline]
-    pub fn get_zero_page_x_addr(mem: &Memory, registers: &Registers) -> u16 {
+    pub fn get_zero_page_x_addr(mem: &mut dyn Bus, registers: &Registers) -> u16 {
         let pc = registers.pc;
         let x = registers.x;
-        let base_addr = mem.read((pc + 1) as u16);
-        let indexed_addr = (base_addr + x) as u16;
+        let base_addr = mem.read(pc.wrapping_add(1));
 
-        indexed_addr
+        base_addr.wrapping_add(x) as u16
     }
 
     #[inline]
-    pub fn read_zero_page_x(mem: &Memory, registers: &Registers) -> u8 {
-        mem.read(get_zero_page_x_addr(mem, registers))
+    pub fn read_zero_page_x(mem: &mut dyn Bus, registers: &Registers) -> u8 {
+        let addr = get_zero_page_x_addr(mem, registers);
+
+        mem.read(addr)
     }
 
     #[inline]
-    pub fn get_zero_page_y_addr(mem: &Memory, registers: &Registers) -> u16 {
+    pub fn get_zero_page_y_addr(mem: &mut dyn Bus, registers: &Registers) -> u16 {
         let pc = registers.pc;
         let y = registers.y;
-        let base_addr = mem.read((pc + 1) as u16);
-        let indexed_addr = (base_addr + y) as u16;
+        let base_addr = mem.read(pc.wrapping_add(1));
 
-        indexed_addr
+        base_addr.wrapping_add(y) as u16
     }
 
     #[inline]
-    pub fn read_zero_page_y(mem: &Memory, registers: &Registers) -> u8 {
-        mem.read(get_zero_page_y_addr(mem, registers))
+    pub fn read_zero_page_y(mem: &mut dyn Bus, registers: &Registers) -> u8 {
+        let addr = get_zero_page_y_addr(mem, registers);
+
+        mem.read(addr)
     }
 
     #[inline]
-    pub fn read_rel(mem: &Memory, registers: &Registers) -> u8 {
+    pub fn read_rel(mem: &mut dyn Bus, registers: &Registers) -> u8 {
         let pc = registers.pc;
 
-        mem.read((pc + 1) as u16)
+        mem.read(pc.wrapping_add(1))
     }
 
     #[inline]
-    pub fn get_abs_addr(mem: &Memory, registers: &Registers) -> u16 {
+    pub fn get_abs_addr(mem: &mut dyn Bus, registers: &Registers) -> u16 {
         let pc = registers.pc;
-        let addr_low = mem.read((pc + 1) as u16);
-        let addr_high = mem.read((pc + 2) as u16);
-        let addr = compose_addr(addr_high, addr_low);
+        let addr_low = mem.read(pc.wrapping_add(1));
+        let addr_high = mem.read(pc.wrapping_add(2));
 
-        addr
+        compose_addr(addr_high, addr_low)
     }
 
     #[inline]
-    pub fn read_abs(mem: &Memory, registers: &Registers) -> u8 {
-        mem.read(get_abs_addr(mem, registers))
+    pub fn read_abs(mem: &mut dyn Bus, registers: &Registers) -> u8 {
+        let addr = get_abs_addr(mem, registers);
+
+        mem.read(addr)
     }
 
     #[inline]
-    pub fn get_abs_x_addr(mem: &Memory, registers: &Registers) -> (u16, bool) {
+    pub fn get_abs_x_addr(mem: &mut dyn Bus, registers: &Registers) -> (u16, bool) {
         let pc = registers.pc;
         let x = registers.x;
-        let addr_low = mem.read((pc + 1) as u16);
-        let addr_high = mem.read((pc + 2) as u16);
+        let addr_low = mem.read(pc.wrapping_add(1));
+        let addr_high = mem.read(pc.wrapping_add(2));
 
         compose_indexed_addr(addr_high, addr_low, x)
     }
 
     #[inline]
-    pub fn read_abs_x(mem: &Memory, registers: &Registers) -> (u8, bool) {
+    pub fn read_abs_x(mem: &mut dyn Bus, registers: &Registers) -> (u8, bool) {
         let (addr, page_crossed) = get_abs_x_addr(mem, registers);
 
         (mem.read(addr), page_crossed)
     }
 
     #[inline]
-    pub fn get_abs_y_addr(mem: &Memory, registers: &Registers) -> (u16, bool) {
+    pub fn get_abs_y_addr(mem: &mut dyn Bus, registers: &Registers) -> (u16, bool) {
         let pc = registers.pc;
         let y = registers.y;
-        let addr_low = mem.read((pc + 1) as u16);
-        let addr_high = mem.read((pc + 2) as u16);
+        let addr_low = mem.read(pc.wrapping_add(1));
+        let addr_high = mem.read(pc.wrapping_add(2));
 
         compose_indexed_addr(addr_high, addr_low, y)
     }
 
     #[inline]
-    pub fn read_abs_y(mem: &Memory, registers: &Registers) -> (u8, bool) {
+    pub fn read_abs_y(mem: &mut dyn Bus, registers: &Registers) -> (u8, bool) {
         let (addr, page_crossed) = get_abs_y_addr(mem, registers);
 
         (mem.read(addr), page_crossed)
     }
 
     #[inline]
-    pub fn get_indirect_x_addr(mem: &Memory, registers: &Registers) -> u16 {
+    pub fn get_indirect_x_addr(mem: &mut dyn Bus, registers: &Registers) -> u16 {
         let pc = registers.pc;
         let x = registers.x;
-        let indirect_addr = mem.read((pc + 1) as u16) + x;
-
-        // TODO Figure out how this hardware handle one byte address overflowing.
-        let indirect_addr = indirect_addr as u16;
+        // The pointer and its high byte both wrap around within zero page.
+        let indirect_addr = mem.read(pc.wrapping_add(1)).wrapping_add(x);
 
-        let addr_low = mem.read(indirect_addr);
-        let addr_high = mem.read(indirect_addr + 1);
-        let addr = compose_addr(addr_high, addr_low);
+        let addr_low = mem.read(indirect_addr as u16);
+        let addr_high = mem.read(indirect_addr.wrapping_add(1) as u16);
 
-        addr
+        compose_addr(addr_high, addr_low)
     }
 
     #[inline]
-    pub fn read_indirect_x(mem: &Memory, registers: &Registers) -> u8 {
-        mem.read(get_indirect_x_addr(mem, registers))
+    pub fn read_indirect_x(mem: &mut dyn Bus, registers: &Registers) -> u8 {
+        let addr = get_indirect_x_addr(mem, registers);
+
+        mem.read(addr)
     }
 
     #[inline]
-    pub fn get_indirect_y_addr(mem: &Memory, registers: &Registers) -> (u16, bool) {
+    pub fn get_indirect_y_addr(mem: &mut dyn Bus, registers: &Registers) -> (u16, bool) {
         let pc = registers.pc;
         let y = registers.y;
-        let indirect_addr = mem.read((pc + 1) as u16) as u16;
+        let indirect_addr = mem.read(pc.wrapping_add(1));
 
-        let addr_low = mem.read(indirect_addr);
-        let addr_high = mem.read(indirect_addr + 1);
+        let addr_low = mem.read(indirect_addr as u16);
+        let addr_high = mem.read(indirect_addr.wrapping_add(1) as u16);
 
         compose_indexed_addr(addr_high, addr_low, y)
     }
 
     #[inline]
-    pub fn read_indirect_y(mem: &Memory, registers: &Registers) -> (u8, bool) {
+    pub fn read_indirect_y(mem: &mut dyn Bus, registers: &Registers) -> (u8, bool) {
         let (addr, page_crossed) = get_indirect_y_addr(mem, registers);
 
         (mem.read(addr), page_crossed)
     }
 }
 
+pub mod stack {
+    use cpu::Registers;
+
+    use bus::Bus;
+
+    const STACK_PAGE: u16 = 0x0100;
+
+    #[inline]
+    pub fn push(registers: &mut Registers, mem: &mut dyn Bus, val: u8) {
+        mem.write(STACK_PAGE | registers.sp as u16, val);
+        registers.sp = registers.sp.wrapping_sub(1);
+    }
+
+    #[inline]
+    pub fn pull(registers: &mut Registers, mem: &mut dyn Bus) -> u8 {
+        registers.sp = registers.sp.wrapping_add(1);
+        mem.read(STACK_PAGE | registers.sp as u16)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use opcode::Cycle;
 
-    use cpu::Memory;
     use cpu::RP2A03;
     use cpu::Registers;
 
+    use bus::Bus;
+
     pub fn arrange_for_imm(cpu: &mut RP2A03, val: u8) {
         cpu.memory.write(1, val);
     }
@@ -0,0 +1,572 @@
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use nom::{IResult, Needed};
+
+use bus::Bus;
+
+use super::unif::{self, parse_unif, Unif};
+use super::{hash, lenient, read_patched, writer, Database, Discrepancy, PatchError};
+use super::{parse_header, parse_ines_data, ConsoleType, Header, HeaderWarning, INes, Mirroring,
+            TvSystem};
+use super::{HEADER_SIZE, PLAY_CHOICE_INST_ROM_SIZE, TRAINER_ADDR, TRAINER_SIZE};
+
+const MAGIC: [u8; 4] = [b'N', b'E', b'S', 0x1a];
+const UNIF_MAGIC: &[u8] = b"UNIF";
+
+/// A ROM image which owns its data, so it outlives the buffer it was read
+/// from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cartridge {
+    header: Header,
+    trainer: Option<Vec<u8>>,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    play_choice_inst_rom: Option<Vec<u8>>,
+    play_choice_prom: Option<Vec<u8>>,
+    misc_rom: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(io::Error),
+    /// The file starts with neither "NES\x1a" nor "UNIF".
+    BadMagic,
+    /// The file is shorter than its header says it is.
+    Truncated { expected: usize, actual: usize },
+    /// No board is implemented for this mapper number.
+    UnsupportedMapper(u16),
+    /// A UNIF board name with no known mapper number.
+    UnknownBoard(String),
+    /// The header has the right magic but the rest of it is malformed.
+    InvalidHeader,
+    /// A patch could not be applied to the image.
+    Patch(PatchError),
+    /// A line of a header database is malformed.
+    InvalidDatabase { line: usize, reason: String },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::Io(ref e) => write!(fmt, "could not read rom: {}", e),
+            RomError::BadMagic => write!(fmt, "not an iNES or UNIF file: bad magic number"),
+            RomError::Truncated { expected, actual } => write!(
+                fmt,
+                "rom is truncated: expected {} bytes, got {}",
+                expected, actual
+            ),
+            RomError::UnsupportedMapper(mapper) => {
+                write!(fmt, "mapper {} is not supported", mapper)
+            }
+            RomError::UnknownBoard(ref board) => write!(fmt, "unknown UNIF board \"{}\"", board),
+            RomError::InvalidHeader => write!(fmt, "invalid header"),
+            RomError::Patch(ref e) => write!(fmt, "could not patch rom: {}", e),
+            RomError::InvalidDatabase { line, ref reason } => {
+                write!(fmt, "invalid database, line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl error::Error for RomError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            RomError::Io(ref e) => Some(e),
+            RomError::Patch(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> RomError {
+        RomError::Io(e)
+    }
+}
+
+impl From<PatchError> for RomError {
+    fn from(e: PatchError) -> RomError {
+        RomError::Patch(e)
+    }
+}
+
+// Size of the whole image as announced by the header.
+fn image_bytes(header: &Header) -> usize {
+    let mut size = HEADER_SIZE;
+
+    if header.has_trainer() {
+        size += TRAINER_SIZE;
+    }
+
+    size = size.saturating_add(header.prg_rom_bytes());
+    size = size.saturating_add(header.chr_rom_bytes());
+
+    if header.console_type() == ConsoleType::PlayChoice10 {
+        size = size.saturating_add(PLAY_CHOICE_INST_ROM_SIZE);
+    }
+
+    size
+}
+
+fn check_header_len(data: &[u8]) -> Result<(), RomError> {
+    let magic_len = cmp::min(data.len(), MAGIC.len());
+    if data[..magic_len] != MAGIC[..magic_len] {
+        return Err(RomError::BadMagic);
+    }
+
+    if data.len() < HEADER_SIZE {
+        return Err(RomError::Truncated {
+            expected: HEADER_SIZE,
+            actual: data.len(),
+        });
+    }
+
+    Ok(())
+}
+
+impl Cartridge {
+    /// Reads a ROM image, soft-patched with its sibling patch if there is
+    /// one, see `read_patched`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Cartridge, RomError> {
+        let (data, _) = read_patched(path)?;
+
+        Cartridge::from_bytes(&data)
+    }
+
+    /// Like `from_path`, but tolerates archaic headers, see
+    /// `parse_ines_lenient`.
+    pub fn from_path_lenient<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Cartridge, Vec<HeaderWarning>), RomError> {
+        let (data, _) = read_patched(path)?;
+
+        Cartridge::from_bytes_lenient(&data)
+    }
+
+    /// Reads an iNES, NES 2.0 or UNIF image.
+    pub fn from_bytes(data: &[u8]) -> Result<Cartridge, RomError> {
+        if data.starts_with(UNIF_MAGIC) {
+            return Cartridge::from_unif_bytes(data);
+        }
+
+        check_header_len(data)?;
+
+        let header = match parse_header(data) {
+            IResult::Done(_, header) => header,
+            _ => return Err(RomError::InvalidHeader),
+        };
+
+        Cartridge::from_header(header, data)
+    }
+
+    /// Like `from_bytes`, but tolerates archaic headers, see
+    /// `parse_ines_lenient`.
+    pub fn from_bytes_lenient(data: &[u8]) -> Result<(Cartridge, Vec<HeaderWarning>), RomError> {
+        if data.starts_with(UNIF_MAGIC) {
+            return Cartridge::from_unif_bytes(data).map(|cartridge| (cartridge, Vec::new()));
+        }
+
+        check_header_len(data)?;
+
+        let (header_bytes, warnings) = lenient::clean_header(data);
+        let header = match parse_header(&header_bytes) {
+            IResult::Done(_, header) => header,
+            _ => return Err(RomError::InvalidHeader),
+        };
+
+        Cartridge::from_header(header, data).map(|cartridge| (cartridge, warnings))
+    }
+
+    fn from_unif_bytes(data: &[u8]) -> Result<Cartridge, RomError> {
+        match parse_unif(data) {
+            IResult::Done(_, unif) => Cartridge::from_unif(&unif),
+            IResult::Incomplete(Needed::Size(expected)) => Err(RomError::Truncated {
+                expected,
+                actual: data.len(),
+            }),
+            _ => Err(RomError::InvalidHeader),
+        }
+    }
+
+    /// The board name is mapped to a mapper number, and the header is
+    /// rebuilt as NES 2.0, so the cartridge can be written as an iNES file.
+    pub fn from_unif(unif: &Unif) -> Result<Cartridge, RomError> {
+        let board = unif.board_name().ok_or(RomError::InvalidHeader)?;
+        let mapper = unif::board_mapper(board)
+            .ok_or_else(|| RomError::UnknownBoard(board.to_string()))?;
+
+        let prg_rom = unif.prg_rom();
+        let chr_rom = unif.chr_rom();
+        let header = unif.to_header(mapper, prg_rom.len(), chr_rom.len())
+            .ok_or(RomError::InvalidHeader)?;
+
+        Ok(Cartridge {
+            header,
+            trainer: None,
+            prg_rom,
+            chr_rom,
+            play_choice_inst_rom: None,
+            play_choice_prom: None,
+            misc_rom: None,
+        })
+    }
+
+    fn from_header(header: Header, data: &[u8]) -> Result<Cartridge, RomError> {
+        let expected = image_bytes(&header);
+        if data.len() < expected {
+            return Err(RomError::Truncated {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        match parse_ines_data(&data[HEADER_SIZE..], header) {
+            IResult::Done(_, ines) => Ok(Cartridge::from(ines)),
+            _ => Err(RomError::InvalidHeader),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// For fixing up a bad header before writing the cartridge back.
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    /// CRC-32 of the PRG and CHR ROM, without header or trainer, as ROM
+    /// databases hash them.
+    pub fn crc32(&self) -> u32 {
+        hash::crc32(&self.rom_data())
+    }
+
+    /// SHA-1 of the PRG and CHR ROM, like `crc32`.
+    pub fn sha1(&self) -> [u8; 20] {
+        hash::sha1(&self.rom_data())
+    }
+
+    fn rom_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.prg_rom.len() + self.chr_rom.len());
+        data.extend_from_slice(&self.prg_rom);
+        data.extend_from_slice(&self.chr_rom);
+
+        data
+    }
+
+    /// Corrects the header with what `database` knows about the cartridge.
+    /// Returns `None` if the cartridge is not in the database, otherwise the
+    /// header fields which were wrong.
+    pub fn apply_database(&mut self, database: &Database) -> Option<Vec<Discrepancy>> {
+        let entry = database.lookup(self.crc32(), &self.sha1())?;
+
+        Some(entry.correct(&mut self.header))
+    }
+
+    /// Serializes the cartridge into the iNES or NES 2.0 file format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        writer::write_image(
+            &self.header,
+            self.trainer(),
+            &self.prg_rom,
+            &self.chr_rom,
+            self.play_choice_inst_rom(),
+            self.play_choice_prom.as_ref().map(|prom| &prom[..]),
+            self.misc_rom(),
+        )
+    }
+
+    pub fn mapper_number(&self) -> u16 {
+        self.header.mapper_number()
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.header.mirroring()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.header.has_battery()
+    }
+
+    pub fn tv_system(&self) -> TvSystem {
+        self.header.tv_system()
+    }
+
+    pub fn prg_ram_bytes(&self) -> usize {
+        self.header.prg_ram_bytes()
+    }
+
+    /// 512 bytes of code from a copier, meant to be loaded at
+    /// `TRAINER_ADDR` ($7000) before the game starts.
+    pub fn trainer(&self) -> Option<&[u8]> {
+        self.trainer.as_ref().map(|trainer| &trainer[..])
+    }
+
+    pub fn prg_rom(&self) -> &[u8] {
+        &self.prg_rom
+    }
+
+    /// Empty if the board has CHR RAM instead of CHR ROM.
+    pub fn chr_rom(&self) -> &[u8] {
+        &self.chr_rom
+    }
+
+    pub fn play_choice_inst_rom(&self) -> Option<&[u8]> {
+        self.play_choice_inst_rom.as_ref().map(|rom| &rom[..])
+    }
+
+    pub fn play_choice_prom_data(&self) -> Option<&[u8]> {
+        self.play_choice_prom.as_ref().map(|prom| &prom[..16])
+    }
+
+    pub fn play_choice_prom_counter_out(&self) -> Option<&[u8]> {
+        self.play_choice_prom.as_ref().map(|prom| &prom[16..])
+    }
+
+    /// Copies the trainer to $7000-$71FF, where it sits in PRG RAM on the
+    /// copier hardware the dump came from. Call this when the cartridge is
+    /// inserted, after PRG RAM has been set up. Does nothing without one.
+    pub fn load_trainer<B: Bus + ?Sized>(&self, bus: &mut B) {
+        if let Some(ref trainer) = self.trainer {
+            for (addr, &byte) in (TRAINER_ADDR..).zip(trainer.iter()) {
+                bus.write(addr, byte);
+            }
+        }
+    }
+
+    pub fn misc_rom(&self) -> Option<&[u8]> {
+        self.misc_rom.as_ref().map(|rom| &rom[..])
+    }
+}
+
+impl<'n> From<INes<'n>> for Cartridge {
+    fn from(ines: INes<'n>) -> Cartridge {
+        Cartridge {
+            header: ines.header().clone(),
+            trainer: ines.trainer().map(|trainer| trainer.to_vec()),
+            prg_rom: ines.prg_rom().to_vec(),
+            chr_rom: ines.chr_rom().to_vec(),
+            play_choice_inst_rom: ines.play_choice_inst_rom().map(|rom| rom.to_vec()),
+            play_choice_prom: ines.play_choice_prom.map(|prom| prom.to_vec()),
+            misc_rom: ines.misc_rom().map(|rom| rom.to_vec()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use cpu::Memory;
+
+    fn ines_image(prg_banks: u8, chr_banks: u8) -> Vec<u8> {
+        let mut image = vec![b'N', b'E', b'S', 0x1a, prg_banks, chr_banks];
+        image.extend_from_slice(&[0x00; 10]);
+        image.extend(vec![0xaa; 16384 * prg_banks as usize]);
+        image.extend(vec![0x55; 8192 * chr_banks as usize]);
+
+        image
+    }
+
+    #[test]
+    fn from_bytes() {
+        let mut image = ines_image(1, 1);
+        image[6] = 0b0001_0001;
+
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+
+        assert_eq!(cartridge.mapper_number(), 1);
+        assert_eq!(cartridge.mirroring(), Mirroring::Vertical);
+        assert_eq!(cartridge.prg_rom().len(), 16384);
+        assert_eq!(cartridge.chr_rom().len(), 8192);
+        assert_eq!(cartridge.trainer(), None);
+    }
+
+    #[test]
+    fn from_bytes_with_trainer() {
+        let mut image = ines_image(1, 0);
+        image[6] = 0b0000_0100;
+        let prg = image.split_off(16);
+        image.extend(vec![0x12; 512]);
+        image.extend(prg);
+
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+
+        assert_eq!(cartridge.trainer(), Some(&[0x12; 512][..]));
+        assert!(cartridge.prg_rom().iter().all(|&byte| byte == 0xaa));
+    }
+
+    #[test]
+    fn to_bytes_round_trip() {
+        let mut image = ines_image(2, 1);
+        image[6] = 0b0011_0001;
+
+        assert_eq!(Cartridge::from_bytes(&image).unwrap().to_bytes(), image);
+    }
+
+    #[test]
+    fn load_trainer() {
+        let mut image = ines_image(1, 0);
+        image[6] = 0b0000_0100;
+        let prg = image.split_off(16);
+        image.extend((0..512).map(|i| i as u8));
+        image.extend(prg);
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+        let mut memory = Memory::new();
+
+        cartridge.load_trainer(&mut memory);
+
+        assert_eq!(memory.read(0x6fff), 0x00);
+        assert_eq!(memory.read(0x7000), 0x00);
+        assert_eq!(memory.read(0x7001), 0x01);
+        assert_eq!(memory.read(0x71ff), 0xff);
+        assert_eq!(memory.read(0x7200), 0x00);
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut image = ines_image(1, 1);
+        image[0] = b'U';
+
+        match Cartridge::from_bytes(&image) {
+            Err(RomError::BadMagic) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_header() {
+        match Cartridge::from_bytes(b"NES\x1a\x01") {
+            Err(RomError::Truncated {
+                expected: 16,
+                actual: 5,
+            }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_magic() {
+        match Cartridge::from_bytes(b"NE") {
+            Err(RomError::Truncated {
+                expected: 16,
+                actual: 2,
+            }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_data() {
+        let mut image = ines_image(2, 1);
+        image.truncate(16 + 16384);
+
+        match Cartridge::from_bytes(&image) {
+            Err(RomError::Truncated { expected, actual }) => {
+                assert_eq!(expected, 16 + 2 * 16384 + 8192);
+                assert_eq!(actual, 16 + 16384);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut image = ines_image(1, 1);
+        image[15] = 0x11;
+
+        match Cartridge::from_bytes(&image) {
+            Err(RomError::InvalidHeader) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_bytes_lenient() {
+        let mut image = ines_image(1, 1);
+        image[6] = 0b0001_0001;
+        image[7..16].copy_from_slice(b"DiskDude!");
+
+        let (cartridge, warnings) = Cartridge::from_bytes_lenient(&image).unwrap();
+
+        assert_eq!(cartridge.mapper_number(), 1);
+        assert_eq!(warnings, vec![HeaderWarning::RipperTag("DiskDude!".to_string())]);
+        assert_eq!(cartridge.to_bytes()[7..16], [0x00; 9]);
+    }
+
+    #[test]
+    fn from_bytes_is_strict() {
+        let mut image = ines_image(1, 1);
+        image[7..16].copy_from_slice(b"DiskDude!");
+
+        match Cartridge::from_bytes(&image) {
+            Err(RomError::InvalidHeader) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_unif_bytes() {
+        let mut image = b"UNIF\x07\0\0\0".to_vec();
+        image.extend_from_slice(&[0x00; 24]);
+        image.extend_from_slice(b"MAPR\x0a\0\0\0NES-UNROM\0");
+        image.extend_from_slice(b"MIRR\x01\0\0\0\x01");
+        image.extend_from_slice(b"PRG0\0\x80\0\0");
+        image.extend(vec![0xaa; 32768]);
+
+        let cartridge = Cartridge::from_bytes(&image).unwrap();
+
+        assert_eq!(cartridge.mapper_number(), 2);
+        assert_eq!(cartridge.mirroring(), Mirroring::Vertical);
+        assert_eq!(cartridge.prg_rom().len(), 32768);
+        assert_eq!(cartridge.chr_rom(), &[][..]);
+        assert_eq!(cartridge.header().chr_ram_bytes(), 8192);
+
+        let ines = cartridge.to_bytes();
+
+        assert_eq!(Cartridge::from_bytes(&ines).unwrap(), cartridge);
+    }
+
+    #[test]
+    fn unknown_unif_board() {
+        let mut image = b"UNIF\x07\0\0\0".to_vec();
+        image.extend_from_slice(&[0x00; 24]);
+        image.extend_from_slice(b"MAPR\x07\0\0\0UNL-FOO");
+
+        match Cartridge::from_bytes(&image) {
+            Err(RomError::UnknownBoard(ref board)) if board == "UNL-FOO" => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_unif() {
+        let mut image = b"UNIF\x07\0\0\0".to_vec();
+        image.extend_from_slice(&[0x00; 24]);
+        image.extend_from_slice(b"PRG0\x10\0\0\0\xaa");
+
+        match Cartridge::from_bytes(&image) {
+            Err(RomError::Truncated { expected, actual }) => {
+                assert_eq!(expected, 32 + 8 + 16);
+                assert_eq!(actual, 32 + 8 + 1);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            RomError::Truncated {
+                expected: 40976,
+                actual: 16,
+            }.to_string(),
+            "rom is truncated: expected 40976 bytes, got 16"
+        );
+        assert_eq!(RomError::UnsupportedMapper(5).to_string(), "mapper 5 is not supported");
+    }
+}
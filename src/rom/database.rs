@@ -0,0 +1,390 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::{Header, Mirroring, RomError, Timing};
+
+/// Header fields known to be right for some ROMs, found by hash.
+///
+/// The text format has one ROM per line, as whitespace separated
+/// `<field>=<value>` pairs, and `#` comments:
+///
+/// ```text
+/// # Super Mario Bros. 3 (USA)
+/// crc32=85f7cd89 mapper=4 mirroring=horizontal region=ntsc
+/// ```
+///
+/// Each line needs a `crc32` or a 40 digit `sha1`, both in hex, and when it
+/// has both, both must match. The other fields are `mapper`, `submapper`,
+/// `mirroring` (horizontal, vertical or four-screen), `battery` (true or
+/// false) and `region` (ntsc, pal, multi or dendy).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Database {
+    entries: Vec<DatabaseEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatabaseEntry {
+    crc32: Option<u32>,
+    sha1: Option<[u8; 20]>,
+    mapper: Option<u16>,
+    submapper: Option<u8>,
+    mirroring: Option<Mirroring>,
+    battery: Option<bool>,
+    region: Option<Timing>,
+}
+
+/// A header field which does not match the database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discrepancy {
+    Mapper { header: u16, database: u16 },
+    Submapper { header: Option<u8>, database: u8 },
+    Mirroring { header: Mirroring, database: Mirroring },
+    Battery { header: bool, database: bool },
+    Region { header: Timing, database: Timing },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Discrepancy::Mapper { header, database } => write!(
+                fmt,
+                "header says mapper {}, database says {}",
+                header, database
+            ),
+            Discrepancy::Submapper { header, database } => match header {
+                Some(header) => write!(
+                    fmt,
+                    "header says submapper {}, database says {}",
+                    header, database
+                ),
+                None => write!(fmt, "header has no submapper, database says {}", database),
+            },
+            Discrepancy::Mirroring { header, database } => write!(
+                fmt,
+                "header says {:?} mirroring, database says {:?}",
+                header, database
+            ),
+            Discrepancy::Battery { header, database } => write!(
+                fmt,
+                "header says battery {}, database says {}",
+                header, database
+            ),
+            Discrepancy::Region { header, database } => write!(
+                fmt,
+                "header says {:?} timing, database says {:?}",
+                header, database
+            ),
+        }
+    }
+}
+
+fn parse_hex(value: &str, digits: usize) -> Option<Vec<u8>> {
+    if value.len() != digits || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    (0..digits)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect()
+}
+
+impl DatabaseEntry {
+    fn parse(line: &str) -> Result<Option<DatabaseEntry>, String> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut entry = DatabaseEntry::default();
+        let mut empty = true;
+
+        for field in line.split_whitespace() {
+            let mut parts = field.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => return Err(format!("expected <field>=<value>, got `{}`", field)),
+            };
+            let invalid = || format!("invalid value for {}: `{}`", name, value);
+            empty = false;
+
+            match name {
+                "crc32" => {
+                    let bytes = parse_hex(value, 8).ok_or_else(invalid)?;
+                    entry.crc32 = Some(bytes.iter().fold(0, |crc, &byte| crc << 8 | byte as u32));
+                }
+                "sha1" => {
+                    let bytes = parse_hex(value, 40).ok_or_else(invalid)?;
+                    let mut sha1 = [0; 20];
+                    sha1.copy_from_slice(&bytes);
+                    entry.sha1 = Some(sha1);
+                }
+                "mapper" => match value.parse() {
+                    Ok(mapper) if mapper <= 0x0fff => entry.mapper = Some(mapper),
+                    _ => return Err(invalid()),
+                },
+                "submapper" => match value.parse() {
+                    Ok(submapper) if submapper <= 0x0f => entry.submapper = Some(submapper),
+                    _ => return Err(invalid()),
+                },
+                "mirroring" => {
+                    entry.mirroring = Some(match value {
+                        "horizontal" => Mirroring::Horizontal,
+                        "vertical" => Mirroring::Vertical,
+                        "four-screen" => Mirroring::FourScreen,
+                        _ => return Err(invalid()),
+                    })
+                }
+                "battery" => entry.battery = Some(value.parse().map_err(|_| invalid())?),
+                "region" => {
+                    entry.region = Some(match value {
+                        "ntsc" => Timing::Ntsc,
+                        "pal" => Timing::Pal,
+                        "multi" => Timing::MultipleRegion,
+                        "dendy" => Timing::Dendy,
+                        _ => return Err(invalid()),
+                    })
+                }
+                _ => return Err(format!("unknown field `{}`", name)),
+            }
+        }
+
+        if empty {
+            Ok(None)
+        } else if entry.crc32.is_none() && entry.sha1.is_none() {
+            Err("expected a crc32 or sha1".to_string())
+        } else {
+            Ok(Some(entry))
+        }
+    }
+
+    fn matches(&self, crc32: u32, sha1: &[u8; 20]) -> bool {
+        self.crc32.map_or(true, |entry_crc32| entry_crc32 == crc32) &&
+            self.sha1.map_or(true, |ref entry_sha1| entry_sha1 == sha1)
+    }
+
+    pub fn crc32(&self) -> Option<u32> {
+        self.crc32
+    }
+
+    pub fn sha1(&self) -> Option<[u8; 20]> {
+        self.sha1
+    }
+
+    pub fn mapper(&self) -> Option<u16> {
+        self.mapper
+    }
+
+    pub fn submapper(&self) -> Option<u8> {
+        self.submapper
+    }
+
+    pub fn mirroring(&self) -> Option<Mirroring> {
+        self.mirroring
+    }
+
+    pub fn battery(&self) -> Option<bool> {
+        self.battery
+    }
+
+    pub fn region(&self) -> Option<Timing> {
+        self.region
+    }
+
+    /// Overwrites the fields of `header` the entry knows about, and returns
+    /// those which were wrong.
+    pub fn correct(&self, header: &mut Header) -> Vec<Discrepancy> {
+        let mut discrepancies = Vec::new();
+
+        if let Some(mapper) = self.mapper {
+            if header.mapper_number() != mapper {
+                discrepancies.push(Discrepancy::Mapper {
+                    header: header.mapper_number(),
+                    database: mapper,
+                });
+                header.set_mapper_number(mapper);
+            }
+        }
+
+        // iNES headers have no submapper, which is only wrong if it is not 0.
+        if let Some(submapper) = self.submapper {
+            if header.submapper().unwrap_or(0) != submapper {
+                discrepancies.push(Discrepancy::Submapper {
+                    header: header.submapper(),
+                    database: submapper,
+                });
+                header.set_submapper(submapper);
+            }
+        }
+
+        if let Some(mirroring) = self.mirroring {
+            if header.mirroring() != mirroring {
+                discrepancies.push(Discrepancy::Mirroring {
+                    header: header.mirroring(),
+                    database: mirroring,
+                });
+                header.set_mirroring(mirroring);
+            }
+        }
+
+        if let Some(battery) = self.battery {
+            if header.has_battery() != battery {
+                discrepancies.push(Discrepancy::Battery {
+                    header: header.has_battery(),
+                    database: battery,
+                });
+                header.set_battery(battery);
+            }
+        }
+
+        if let Some(region) = self.region {
+            if header.timing() != region {
+                discrepancies.push(Discrepancy::Region {
+                    header: header.timing(),
+                    database: region,
+                });
+                header.set_timing(region);
+            }
+        }
+
+        discrepancies
+    }
+}
+
+impl Database {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Database, RomError> {
+        Database::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Database, RomError> {
+        let mut entries = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            match DatabaseEntry::parse(line) {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => {}
+                Err(reason) => return Err(RomError::InvalidDatabase { line: i + 1, reason }),
+            }
+        }
+
+        Ok(Database { entries })
+    }
+
+    pub fn entries(&self) -> &[DatabaseEntry] {
+        &self.entries
+    }
+
+    /// The first entry matching the hashes of a ROM's PRG and CHR data, see
+    /// `Cartridge::crc32` and `Cartridge::sha1`.
+    pub fn lookup(&self, crc32: u32, sha1: &[u8; 20]) -> Option<&DatabaseEntry> {
+        self.entries.iter().find(|entry| entry.matches(crc32, sha1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rom::{parse_header, TvSystem};
+
+    fn header() -> Header {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1a, 0x02, 0x01];
+        bytes.extend_from_slice(&[0x00; 10]);
+
+        parse_header(&bytes).unwrap().1
+    }
+
+    #[test]
+    fn parse() {
+        let database = Database::parse(
+            "# A comment\n\
+             \n\
+             crc32=85F7cd89 mapper=4 mirroring=vertical battery=true # Game (USA)\n\
+             sha1=a9993e364706816aba3e25717850c26c9cd0d89d submapper=1 region=dendy\n",
+        ).unwrap();
+
+        assert_eq!(database.entries().len(), 2);
+        let entry = &database.entries()[0];
+        assert_eq!(entry.crc32(), Some(0x85f7_cd89));
+        assert_eq!(entry.sha1(), None);
+        assert_eq!(entry.mapper(), Some(4));
+        assert_eq!(entry.mirroring(), Some(Mirroring::Vertical));
+        assert_eq!(entry.battery(), Some(true));
+        let entry = &database.entries()[1];
+        assert_eq!(entry.sha1().unwrap()[..3], [0xa9, 0x99, 0x3e]);
+        assert_eq!(entry.submapper(), Some(1));
+        assert_eq!(entry.region(), Some(Timing::Dendy));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let line = |text| match Database::parse(text) {
+            Err(RomError::InvalidDatabase { line, .. }) => line,
+            result => panic!("unexpected result: {:?}", result),
+        };
+
+        assert_eq!(line("crc32=00000000\nmapper=1"), 2);
+        assert_eq!(line("crc32=123"), 1);
+        assert_eq!(line("crc32=00000000 mapper=x"), 1);
+        assert_eq!(line("crc32=00000000 colour=red"), 1);
+        assert_eq!(line("\ncrc32=00000000 mapper"), 2);
+    }
+
+    #[test]
+    fn lookup_needs_every_hash_to_match() {
+        let sha1 = [0x11; 20];
+        let database = Database::parse(
+            "crc32=00000001 sha1=1111111111111111111111111111111111111111 mapper=1\n\
+             crc32=00000001 mapper=2\n",
+        ).unwrap();
+
+        assert_eq!(database.lookup(1, &sha1).unwrap().mapper(), Some(1));
+        assert_eq!(database.lookup(1, &[0x22; 20]).unwrap().mapper(), Some(2));
+        assert_eq!(database.lookup(2, &sha1), None);
+    }
+
+    #[test]
+    fn correct() {
+        let mut header = header();
+        let entry = DatabaseEntry {
+            mapper: Some(4),
+            submapper: Some(0),
+            mirroring: Some(Mirroring::Vertical),
+            battery: Some(false),
+            region: Some(Timing::Pal),
+            ..Default::default()
+        };
+
+        let discrepancies = entry.correct(&mut header);
+
+        assert_eq!(
+            discrepancies,
+            vec![
+                Discrepancy::Mapper { header: 0, database: 4 },
+                Discrepancy::Mirroring {
+                    header: Mirroring::Horizontal,
+                    database: Mirroring::Vertical,
+                },
+                Discrepancy::Region {
+                    header: Timing::Ntsc,
+                    database: Timing::Pal,
+                },
+            ]
+        );
+        assert_eq!(header.mapper_number(), 4);
+        assert_eq!(header.mirroring(), Mirroring::Vertical);
+        assert_eq!(header.tv_system(), TvSystem::PAL);
+        assert_eq!(header.nes2(), None);
+    }
+
+    #[test]
+    fn correct_upgrades_to_nes2() {
+        let mut header = header();
+        let entry = DatabaseEntry {
+            submapper: Some(3),
+            region: Some(Timing::Dendy),
+            ..Default::default()
+        };
+
+        assert_eq!(entry.correct(&mut header).len(), 2);
+        assert_eq!(header.submapper(), Some(3));
+        assert_eq!(header.timing(), Timing::Dendy);
+    }
+}
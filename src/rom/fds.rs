@@ -0,0 +1,437 @@
+use nom::{le_u16, le_u8, ErrorKind, IResult};
+
+/// Size of one disk side in a .fds image, without CRCs or gaps.
+pub const SIDE_SIZE: usize = 65500;
+
+#[derive(Debug, PartialEq)]
+pub struct FdsDisk<'n> {
+    has_fwnes_header: bool,
+    sides: Vec<DiskSide<'n>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DiskSide<'n> {
+    info: DiskInfo<'n>,
+    file_amount: u8,
+    files: Vec<DiskFile<'n>>,
+}
+
+/// Block 1, the disk info block.
+#[derive(Debug, PartialEq)]
+pub struct DiskInfo<'n> {
+    manufacturer_code: u8,
+    game_name: &'n [u8],
+    game_type: u8,
+    revision: u8,
+    side_number: u8,
+    disk_number: u8,
+    disk_type: u8,
+    boot_file_id: u8,
+    manufacturing_date: &'n [u8],
+    country_code: u8,
+}
+
+/// A file header block (3) and the file data block (4) following it.
+#[derive(Debug, PartialEq)]
+pub struct DiskFile<'n> {
+    number: u8,
+    id: u8,
+    name: &'n [u8],
+    load_addr: u16,
+    kind: FileKind,
+    data: &'n [u8],
+}
+
+/// Where the BIOS loads a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// CPU memory.
+    Prg,
+    /// CHR RAM, through the PPU.
+    Chr,
+    /// Nametables, through the PPU.
+    Vram,
+    Unknown(u8),
+}
+
+impl<'n> FdsDisk<'n> {
+    /// Whether the image starts with the 16 byte fwNES header.
+    pub fn has_fwnes_header(&self) -> bool {
+        self.has_fwnes_header
+    }
+
+    pub fn sides(&self) -> &[DiskSide<'n>] {
+        &self.sides
+    }
+}
+
+impl<'n> DiskSide<'n> {
+    pub fn info(&self) -> &DiskInfo<'n> {
+        &self.info
+    }
+
+    /// Number of files the BIOS sees, from the file amount block.
+    pub fn file_amount(&self) -> u8 {
+        self.file_amount
+    }
+
+    /// Every file on the side, including hidden ones past `file_amount`
+    /// which some games load with their own code.
+    pub fn files(&self) -> &[DiskFile<'n>] {
+        &self.files
+    }
+
+    /// Files loaded at boot, those with an ID up to the boot file ID.
+    pub fn boot_files(&self) -> Vec<&DiskFile<'n>> {
+        self.files
+            .iter()
+            .take(self.file_amount as usize)
+            .filter(|file| file.id <= self.info.boot_file_id)
+            .collect()
+    }
+}
+
+impl<'n> DiskInfo<'n> {
+    pub fn manufacturer_code(&self) -> u8 {
+        self.manufacturer_code
+    }
+
+    /// Three ASCII characters.
+    pub fn game_name(&self) -> &'n [u8] {
+        self.game_name
+    }
+
+    pub fn game_type(&self) -> u8 {
+        self.game_type
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// 0 for side A, 1 for side B.
+    pub fn side_number(&self) -> u8 {
+        self.side_number
+    }
+
+    pub fn disk_number(&self) -> u8 {
+        self.disk_number
+    }
+
+    pub fn disk_type(&self) -> u8 {
+        self.disk_type
+    }
+
+    pub fn boot_file_id(&self) -> u8 {
+        self.boot_file_id
+    }
+
+    /// Year, month and day in BCD, the year counted from 1925 (Showa era).
+    pub fn manufacturing_date(&self) -> &'n [u8] {
+        self.manufacturing_date
+    }
+
+    pub fn country_code(&self) -> u8 {
+        self.country_code
+    }
+}
+
+impl<'n> DiskFile<'n> {
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Eight ASCII characters.
+    pub fn name(&self) -> &'n [u8] {
+        self.name
+    }
+
+    pub fn load_addr(&self) -> u16 {
+        self.load_addr
+    }
+
+    pub fn kind(&self) -> FileKind {
+        self.kind
+    }
+
+    pub fn data(&self) -> &'n [u8] {
+        self.data
+    }
+}
+
+named!(pub parse_fds(&[u8]) -> FdsDisk<'_>,
+    do_parse!(
+        side_count: opt!(complete!(parse_fwnes_header)) >>
+        counted_sides: cond_with_error!(
+            side_count.is_some(),
+            count!(parse_side, side_count.unwrap_or(0) as usize)
+        ) >>
+        sides: cond_with_error!(side_count.is_none(), many1!(parse_side)) >>
+
+        (
+            FdsDisk {
+                has_fwnes_header: side_count.is_some(),
+                sides: counted_sides.or(sides).unwrap_or_default(),
+            }
+        )
+    )
+);
+
+named!(parse_fwnes_header(&[u8]) -> u8,
+    do_parse!(
+        tag!(&[b'F', b'D', b'S', 0x1a][..]) >>
+        side_count: le_u8 >>
+        take!(11) >>
+
+        (side_count)
+    )
+);
+
+// A side always takes `SIDE_SIZE` bytes, the blocks have to fit in it.
+fn parse_side(input: &[u8]) -> IResult<&[u8], DiskSide<'_>> {
+    let (rest, side) = try_parse!(input, take!(SIDE_SIZE));
+
+    match parse_side_blocks(side) {
+        IResult::Done(_, side) => IResult::Done(rest, side),
+        IResult::Incomplete(_) => IResult::Error(error_position!(ErrorKind::Eof, input)),
+        IResult::Error(e) => IResult::Error(e),
+    }
+}
+
+named!(parse_side_blocks(&[u8]) -> DiskSide<'_>,
+    do_parse!(
+        info: parse_disk_info >>
+        tag!(&[0x02][..]) >>
+        file_amount: le_u8 >>
+        files: many0!(parse_file) >>
+
+        (
+            DiskSide {
+                info,
+                file_amount,
+                files,
+            }
+        )
+    )
+);
+
+named!(parse_disk_info(&[u8]) -> DiskInfo<'_>,
+    do_parse!(
+        tag!(&[0x01][..]) >>
+        tag!(&b"*NINTENDO-HVC*"[..]) >>
+        manufacturer_code: le_u8 >>
+        game_name: take!(3) >>
+        game_type: le_u8 >>
+        revision: le_u8 >>
+        side_number: le_u8 >>
+        disk_number: le_u8 >>
+        disk_type: le_u8 >>
+        le_u8 >>
+        boot_file_id: le_u8 >>
+        take!(5) >>
+        manufacturing_date: take!(3) >>
+        country_code: le_u8 >>
+        // Rewrite date, disk writer serial numbers and the like.
+        take!(21) >>
+
+        (
+            DiskInfo {
+                manufacturer_code,
+                game_name,
+                game_type,
+                revision,
+                side_number,
+                disk_number,
+                disk_type,
+                boot_file_id,
+                manufacturing_date,
+                country_code,
+            }
+        )
+    )
+);
+
+named!(parse_file(&[u8]) -> DiskFile<'_>,
+    do_parse!(
+        tag!(&[0x03][..]) >>
+        number: le_u8 >>
+        id: le_u8 >>
+        name: take!(8) >>
+        load_addr: le_u16 >>
+        size: le_u16 >>
+        kind: le_u8 >>
+        tag!(&[0x04][..]) >>
+        data: take!(size) >>
+
+        ({
+            let kind = match kind {
+                0 => FileKind::Prg,
+                1 => FileKind::Chr,
+                2 => FileKind::Vram,
+                n => FileKind::Unknown(n),
+            };
+
+            DiskFile {
+                number,
+                id,
+                name,
+                load_addr,
+                kind,
+                data,
+            }
+        })
+    )
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn disk_info(side_number: u8, boot_file_id: u8) -> Vec<u8> {
+        let mut block = vec![0x01];
+        block.extend_from_slice(b"*NINTENDO-HVC*");
+        block.extend_from_slice(&[0xa4]);
+        block.extend_from_slice(b"ZEL");
+        block.extend_from_slice(&[0x20, 0x01, side_number, 0x00, 0x00, 0x00, boot_file_id]);
+        block.extend_from_slice(&[0xff; 5]);
+        block.extend_from_slice(&[0x61, 0x02, 0x21, 0x49]);
+        block.extend_from_slice(&[0x00; 21]);
+
+        block
+    }
+
+    fn file(number: u8, id: u8, name: &[u8; 8], addr: u16, kind: u8, data: &[u8]) -> Vec<u8> {
+        let size = data.len();
+        let mut blocks = vec![0x03, number, id];
+        blocks.extend_from_slice(name);
+        blocks.extend_from_slice(&[addr as u8, (addr >> 8) as u8, size as u8, (size >> 8) as u8]);
+        blocks.push(kind);
+        blocks.push(0x04);
+        blocks.extend_from_slice(data);
+
+        blocks
+    }
+
+    fn side(side_number: u8, file_amount: u8, files: &[Vec<u8>]) -> Vec<u8> {
+        let mut side = disk_info(side_number, 1);
+        side.extend_from_slice(&[0x02, file_amount]);
+        for file in files {
+            side.extend_from_slice(file);
+        }
+        side.resize(SIDE_SIZE, 0x00);
+
+        side
+    }
+
+    fn two_files() -> Vec<Vec<u8>> {
+        vec![
+            file(0, 0, b"KYODAKU-", 0x2800, 2, &[0x24; 0xe0]),
+            file(1, 1, b"ZELDA   ", 0x6000, 0, &[0xea; 0x100]),
+        ]
+    }
+
+    #[test]
+    fn disk_info_block() {
+        let block = disk_info(1, 0x0f);
+
+        let (rest, info) = parse_disk_info(&block).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(info.manufacturer_code(), 0xa4);
+        assert_eq!(info.game_name(), b"ZEL");
+        assert_eq!(info.revision(), 0x01);
+        assert_eq!(info.side_number(), 1);
+        assert_eq!(info.boot_file_id(), 0x0f);
+        assert_eq!(info.manufacturing_date(), &[0x61, 0x02, 0x21][..]);
+        assert_eq!(info.country_code(), 0x49);
+    }
+
+    #[test]
+    fn file_blocks() {
+        let blocks = file(2, 5, b"CHARDATA", 0x0000, 1, &[0x55; 0x10]);
+
+        let (rest, file) = parse_file(&blocks).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(file.number(), 2);
+        assert_eq!(file.id(), 5);
+        assert_eq!(file.name(), b"CHARDATA");
+        assert_eq!(file.load_addr(), 0x0000);
+        assert_eq!(file.kind(), FileKind::Chr);
+        assert_eq!(file.data(), &[0x55; 0x10][..]);
+    }
+
+    #[test]
+    fn headerless_image() {
+        let mut image = side(0, 2, &two_files());
+        image.extend(side(1, 0, &[]));
+
+        let (rest, disk) = parse_fds(&image).unwrap();
+
+        assert!(rest.is_empty());
+        assert!(!disk.has_fwnes_header());
+        assert_eq!(disk.sides().len(), 2);
+        assert_eq!(disk.sides()[1].info().side_number(), 1);
+
+        let files = disk.sides()[0].files();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].kind(), FileKind::Vram);
+        assert_eq!(files[1].name(), b"ZELDA   ");
+        assert_eq!(files[1].load_addr(), 0x6000);
+        assert_eq!(files[1].data().len(), 0x100);
+    }
+
+    #[test]
+    fn fwnes_header() {
+        let mut image = vec![b'F', b'D', b'S', 0x1a, 0x01];
+        image.extend_from_slice(&[0x00; 11]);
+        image.extend(side(0, 2, &two_files()));
+
+        let (_, disk) = parse_fds(&image).unwrap();
+
+        assert!(disk.has_fwnes_header());
+        assert_eq!(disk.sides().len(), 1);
+    }
+
+    #[test]
+    fn hidden_files() {
+        let mut files = two_files();
+        files.push(file(2, 2, b"HIDDEN  ", 0x7000, 0, &[0x00; 4]));
+        let image = side(0, 2, &files);
+
+        let (_, disk) = parse_fds(&image).unwrap();
+        let side = &disk.sides()[0];
+
+        assert_eq!(side.file_amount(), 2);
+        assert_eq!(side.files().len(), 3);
+        assert_eq!(side.boot_files().len(), 2);
+    }
+
+    #[test]
+    fn truncated_side() {
+        let mut image = side(0, 2, &two_files());
+        image.truncate(SIDE_SIZE - 1);
+
+        assert!(parse_fds(&image).is_incomplete());
+    }
+
+    #[test]
+    fn file_overflowing_side() {
+        let image = side(0, 1, &[file(0, 0, b"BIGFILE ", 0x6000, 0, &[0xea; 0xffff])]);
+
+        assert!(parse_fds(&image[..SIDE_SIZE]).is_err());
+    }
+
+    #[test]
+    fn bad_disk_info() {
+        let mut image = side(0, 2, &two_files());
+        image[1] = b'X';
+
+        assert!(parse_fds(&image).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use super::nes2::rom_bytes;
+use super::{CHR_ROM_BANK_SIZE, HEADER_SIZE, PRG_ROM_BANK_SIZE};
+
+// Shortest printable run in bytes 7-15 taken for a ripper's signature.
+const MIN_TAG_LEN: usize = 4;
+
+/// Something `parse_ines_lenient` had to ignore to make sense of a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderWarning {
+    /// Bytes 7-15 hold a ripper's signature such as "DiskDude!", only
+    /// bytes 0-6 were used.
+    RipperTag(String),
+    /// Byte 7 or bytes 12-15 hold junk, only bytes 0-6 were used.
+    ArchaicHeader,
+    /// The NES 2.0 header announces more ROM than the file holds, so it was
+    /// read as an archaic iNES header instead.
+    Nes2SizeMismatch,
+    /// A padding byte which should be zero was ignored.
+    NonZeroPadding { offset: usize, value: u8 },
+}
+
+impl fmt::Display for HeaderWarning {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HeaderWarning::RipperTag(ref tag) => {
+                write!(fmt, "ignored ripper tag \"{}\" in header bytes 7-15", tag)
+            }
+            HeaderWarning::ArchaicHeader => {
+                write!(fmt, "ignored junk in header bytes 7-15 of an archaic iNES header")
+            }
+            HeaderWarning::Nes2SizeMismatch => write!(
+                fmt,
+                "NES 2.0 header announces more ROM than the file holds, read as archaic iNES"
+            ),
+            HeaderWarning::NonZeroPadding { offset, value } => write!(
+                fmt,
+                "ignored non-zero padding byte ${:02X} at offset {}",
+                value, offset
+            ),
+        }
+    }
+}
+
+// Size of the image a NES 2.0 header announces, trainer included.
+fn nes2_image_bytes(header: &[u8]) -> usize {
+    let trainer = if header[6] & 0b0000_0100 != 0 { 512 } else { 0 };
+    let prg = rom_bytes(header[4], header[9] & 0x0f, PRG_ROM_BANK_SIZE);
+    let chr = rom_bytes(header[5], header[9] >> 4, CHR_ROM_BANK_SIZE);
+
+    HEADER_SIZE.saturating_add(trainer).saturating_add(prg).saturating_add(chr)
+}
+
+fn ripper_tag(junk: &[u8]) -> Option<String> {
+    let end = junk.iter().position(|&byte| byte == 0x00).unwrap_or(junk.len());
+    let (tag, rest) = junk.split_at(end);
+
+    let printable = tag.iter().all(|&byte| (0x20..=0x7e).contains(&byte));
+    let zero_padded = rest.iter().all(|&byte| byte == 0x00);
+
+    if printable && zero_padded && tag.len() >= MIN_TAG_LEN {
+        Some(String::from_utf8_lossy(tag).trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Copy of the first 16 bytes of `data` which the strict header parser
+/// accepts, following the usual emulator heuristics:
+///
+/// * byte 7 & $0C == $08 and the announced size fits the file: NES 2.0
+/// * byte 7 & $0C == $00 and bytes 12-15 are zero: iNES
+/// * anything else: archaic iNES, where bytes 7-15 are junk
+///
+/// `data` must hold at least the 16 header bytes.
+pub(super) fn clean_header(data: &[u8]) -> ([u8; HEADER_SIZE], Vec<HeaderWarning>) {
+    let mut header = [0; HEADER_SIZE];
+    header.copy_from_slice(&data[..HEADER_SIZE]);
+    let mut warnings = Vec::new();
+
+    let archaic = match header[7] & 0b0000_1100 {
+        0b0000_1000 if nes2_image_bytes(&header) <= data.len() => false,
+        0b0000_1000 => {
+            warnings.push(HeaderWarning::Nes2SizeMismatch);
+            true
+        }
+        0b0000_0000 => header[12..].iter().any(|&byte| byte != 0x00),
+        _ => true,
+    };
+
+    if archaic {
+        warnings.push(match ripper_tag(&header[7..]) {
+            Some(tag) => HeaderWarning::RipperTag(tag),
+            None => HeaderWarning::ArchaicHeader,
+        });
+
+        for byte in &mut header[7..] {
+            *byte = 0x00;
+        }
+    } else if header[7] & 0b0000_1100 == 0 && header[11] != 0x00 {
+        warnings.push(HeaderWarning::NonZeroPadding {
+            offset: 11,
+            value: header[11],
+        });
+        header[11] = 0x00;
+    }
+
+    (header, warnings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(tail: &[u8; 9]) -> Vec<u8> {
+        let mut header = vec![b'N', b'E', b'S', 0x1a, 0x01, 0x01, 0b0001_0001];
+        header.extend_from_slice(tail);
+        header.extend(vec![0x00; 16384 + 8192]);
+
+        header
+    }
+
+    #[test]
+    fn ines_header_is_untouched() {
+        let data = header(&[0b0010_0000, 0x01, 0x01, 0x10, 0, 0, 0, 0, 0]);
+
+        let (cleaned, warnings) = clean_header(&data);
+
+        assert_eq!(cleaned[..], data[..16]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn nes2_header_is_untouched() {
+        let data = header(&[0b0000_1000, 0x10, 0, 0x70, 0, 0, 0, 0, 0x01]);
+
+        let (cleaned, warnings) = clean_header(&data);
+
+        assert_eq!(cleaned[..], data[..16]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn disk_dude() {
+        let data = header(b"DiskDude!");
+
+        let (cleaned, warnings) = clean_header(&data);
+
+        assert_eq!(cleaned[..7], data[..7]);
+        assert_eq!(cleaned[7..], [0x00; 9]);
+        assert_eq!(warnings, vec![HeaderWarning::RipperTag("DiskDude!".to_string())]);
+    }
+
+    #[test]
+    fn short_tag_with_padding() {
+        let data = header(b"demi\0\0\0\0\0");
+
+        let (_, warnings) = clean_header(&data);
+
+        assert_eq!(warnings, vec![HeaderWarning::RipperTag("demi".to_string())]);
+    }
+
+    #[test]
+    fn junk_in_last_bytes() {
+        let data = header(&[0b0100_0000, 0, 0, 0, 0, 0, 0x01, 0x02, 0x03]);
+
+        let (cleaned, warnings) = clean_header(&data);
+
+        assert_eq!(cleaned[7..], [0x00; 9]);
+        assert_eq!(warnings, vec![HeaderWarning::ArchaicHeader]);
+    }
+
+    #[test]
+    fn archaic_identifier() {
+        let data = header(&[0b0000_0100, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let (_, warnings) = clean_header(&data);
+
+        assert_eq!(warnings, vec![HeaderWarning::ArchaicHeader]);
+    }
+
+    #[test]
+    fn nes2_header_larger_than_file() {
+        let data = header(&[0b0000_1000, 0, 0x01, 0, 0, 0, 0, 0, 0]);
+
+        let (cleaned, warnings) = clean_header(&data);
+
+        assert_eq!(cleaned[7..], [0x00; 9]);
+        assert_eq!(
+            warnings,
+            vec![HeaderWarning::Nes2SizeMismatch, HeaderWarning::ArchaicHeader]
+        );
+    }
+
+    #[test]
+    fn non_zero_byte_11() {
+        let data = header(&[0, 0, 0, 0, 0xff, 0, 0, 0, 0]);
+
+        let (cleaned, warnings) = clean_header(&data);
+
+        assert_eq!(cleaned[11], 0x00);
+        assert_eq!(
+            warnings,
+            vec![
+                HeaderWarning::NonZeroPadding {
+                    offset: 11,
+                    value: 0xff,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            HeaderWarning::RipperTag("DiskDude!".to_string()).to_string(),
+            "ignored ripper tag \"DiskDude!\" in header bytes 7-15"
+        );
+    }
+}
@@ -0,0 +1,1014 @@
+use std::cmp;
+
+use nom::{self, le_u8, IResult, Needed};
+
+mod cartridge;
+mod database;
+mod fds;
+mod hash;
+mod lenient;
+mod nes2;
+mod nsf;
+mod patch;
+mod unif;
+mod writer;
+
+pub use self::cartridge::{Cartridge, RomError};
+pub use self::database::{Database, DatabaseEntry, Discrepancy};
+pub use self::fds::{parse_fds, DiskFile, DiskInfo, DiskSide, FdsDisk, FileKind};
+pub use self::hash::{crc32, sha1};
+pub use self::lenient::HeaderWarning;
+
+pub use self::nes2::{ConsoleType, Nes2Header, Timing, VsHardwareType, VsPpuType, VsSystemType};
+use self::nes2::parse_nes2_header;
+pub use self::nsf::{parse_nsf, parse_nsfe, ExpansionChips, Nsf, NsfMetadata, Nsfe, NSF_HEADER_SIZE};
+pub use self::patch::{find_sibling_patch, parse_bps, parse_ips, parse_ups, read_patched, Bps, Ips,
+                      IpsRecord, Patch, PatchError, Ups, PATCH_EXTENSIONS};
+pub use self::unif::{board_mapper, parse_unif, Chunk, Unif};
+
+#[derive(Debug, PartialEq)]
+pub struct INes<'n> {
+    header: Header,
+    trainer: Option<&'n [u8]>,
+    prg_rom_data: &'n [u8],
+    chr_rom_data: Option<&'n [u8]>,
+    play_choice_inst_rom: Option<&'n [u8]>,
+    play_choice_prom: Option<&'n [u8]>,
+    misc_rom_data: Option<&'n [u8]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    prg_rom_size: u8,
+    chr_rom_size: u8,
+    flag6: Flag6,
+    flag7: Flag7,
+    prg_ram_size: u8,
+    flag9: Flag9,
+    flag10: Flag10,
+    nes2: Option<Nes2Header>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flag6 {
+    mirroring: Mirroring,
+    contains_battery_backed_prg_ram: bool,
+    has_trainer: bool,
+    igore_mirroring_control: bool,
+    lower_nybble_of_mapper_number: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flag7 {
+    vs_unisystem: bool,
+    has_play_choice_10: bool,
+    is_nes_2_0: bool,
+    upper_nybble_of_mapper_numbe: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flag9 {
+    tv_system: TvSystem,
+}
+
+/// Unofficial and rarely used, NES 2.0 puts the PRG RAM size here instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flag10 {
+    // Both 0b01 and 0b11 mean dual compatible, so the bits are kept as
+    // they are to be written back unchanged.
+    tv_system_bits: u8,
+    has_prg_ram: bool,
+    has_bus_conflicts: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TvSystem {
+    NTSC,
+    PAL,
+}
+
+const HEADER_SIZE: usize = 16;
+const PRG_ROM_BANK_SIZE: usize = 16384;
+const CHR_ROM_BANK_SIZE: usize = 8192;
+const PRG_RAM_BANK_SIZE: usize = 8192;
+const TRAINER_SIZE: usize = 512;
+const PLAY_CHOICE_INST_ROM_SIZE: usize = 8192;
+const PLAY_CHOICE_PROM_SIZE: usize = 32;
+
+/// Where the trainer is loaded, in PRG RAM.
+pub const TRAINER_ADDR: u16 = 0x7000;
+
+impl<'n> INes<'n> {
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn mapper_number(&self) -> u16 {
+        self.header.mapper_number()
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.header.mirroring()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.header.has_battery()
+    }
+
+    pub fn tv_system(&self) -> TvSystem {
+        self.header.tv_system()
+    }
+
+    pub fn prg_ram_bytes(&self) -> usize {
+        self.header.prg_ram_bytes()
+    }
+
+    /// 512 bytes of code from a copier, meant to be loaded at
+    /// `TRAINER_ADDR` ($7000) before the game starts.
+    pub fn trainer(&self) -> Option<&'n [u8]> {
+        self.trainer
+    }
+
+    pub fn prg_rom(&self) -> &'n [u8] {
+        self.prg_rom_data
+    }
+
+    /// Empty if the board has CHR RAM instead of CHR ROM.
+    pub fn chr_rom(&self) -> &'n [u8] {
+        self.chr_rom_data.unwrap_or(&[])
+    }
+
+    pub fn play_choice_inst_rom(&self) -> Option<&'n [u8]> {
+        self.play_choice_inst_rom
+    }
+
+    /// The 16 data bytes of the PlayChoice-10 PROM, which many dumps lack.
+    pub fn play_choice_prom_data(&self) -> Option<&'n [u8]> {
+        self.play_choice_prom.map(|prom| &prom[..16])
+    }
+
+    /// The 16 CounterOut bytes following the PlayChoice-10 PROM data.
+    pub fn play_choice_prom_counter_out(&self) -> Option<&'n [u8]> {
+        self.play_choice_prom.map(|prom| &prom[16..])
+    }
+
+    /// Everything after CHR ROM when a NES 2.0 header announces
+    /// miscellaneous ROMs, left for the mapper to split up.
+    pub fn misc_rom(&self) -> Option<&'n [u8]> {
+        self.misc_rom_data
+    }
+}
+
+impl Header {
+    pub fn flag6(&self) -> &Flag6 {
+        &self.flag6
+    }
+
+    pub fn flag7(&self) -> &Flag7 {
+        &self.flag7
+    }
+
+    pub fn flag9(&self) -> &Flag9 {
+        &self.flag9
+    }
+
+    pub fn flag10(&self) -> &Flag10 {
+        &self.flag10
+    }
+
+    /// Only iNES headers can announce bus conflicts, in flag 10. NES 2.0
+    /// leaves them to the submapper.
+    pub fn has_bus_conflicts(&self) -> bool {
+        self.flag10.has_bus_conflicts
+    }
+
+    /// Bytes 8-15 of the header, if it is in the NES 2.0 format.
+    pub fn nes2(&self) -> Option<&Nes2Header> {
+        self.nes2.as_ref()
+    }
+
+    pub fn mapper_number(&self) -> u16 {
+        // The upper nybble is stored already shifted into place.
+        let upper = self.flag7.upper_nybble_of_mapper_numbe as u16;
+        let lower = self.flag6.lower_nybble_of_mapper_number as u16;
+        let msb = self.nes2.as_ref().map_or(0, |nes2| nes2.mapper_number_msb() as u16);
+
+        (msb << 8) | upper | lower
+    }
+
+    /// Only NES 2.0 headers have a submapper.
+    pub fn submapper(&self) -> Option<u8> {
+        self.nes2.as_ref().map(|nes2| nes2.submapper())
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.flag6.mirroring()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.flag6.contains_battery_backed_prg_ram
+    }
+
+    pub fn has_trainer(&self) -> bool {
+        self.flag6.has_trainer
+    }
+
+    pub fn tv_system(&self) -> TvSystem {
+        self.flag9.tv_system
+    }
+
+    pub fn timing(&self) -> Timing {
+        match self.nes2 {
+            Some(ref nes2) => nes2.timing(),
+            None => match self.flag9.tv_system {
+                TvSystem::NTSC => Timing::Ntsc,
+                TvSystem::PAL => Timing::Pal,
+            },
+        }
+    }
+
+    pub fn console_type(&self) -> ConsoleType {
+        match (self.flag7.vs_unisystem, self.flag7.has_play_choice_10) {
+            (false, false) => ConsoleType::Nes,
+            (true, false) => ConsoleType::VsSystem,
+            (false, true) => ConsoleType::PlayChoice10,
+            (true, true) => {
+                let console = self.nes2.as_ref().map_or(0, |nes2| nes2.system_type());
+
+                ConsoleType::Extended(console & 0x0f)
+            }
+        }
+    }
+
+    /// PPU and hardware of a NES 2.0 Vs. System game.
+    pub fn vs_system_type(&self) -> Option<VsSystemType> {
+        match (self.console_type(), self.nes2.as_ref()) {
+            (ConsoleType::VsSystem, Some(nes2)) => Some(nes2.vs_system_type()),
+            _ => None,
+        }
+    }
+
+    pub fn misc_rom_count(&self) -> u8 {
+        self.nes2.as_ref().map_or(0, |nes2| nes2.misc_rom_count())
+    }
+
+    pub fn default_expansion_device(&self) -> Option<u8> {
+        self.nes2.as_ref().map(|nes2| nes2.default_expansion_device())
+    }
+
+    /// Number of 16 KB PRG ROM banks.
+    pub fn prg_rom_size(&self) -> u8 {
+        self.prg_rom_size
+    }
+
+    /// Number of 8 KB CHR ROM banks, zero means the board uses CHR RAM.
+    pub fn chr_rom_size(&self) -> u8 {
+        self.chr_rom_size
+    }
+
+    pub fn prg_rom_bytes(&self) -> usize {
+        match self.nes2 {
+            Some(ref nes2) => nes2.prg_rom_bytes(self.prg_rom_size),
+            None => PRG_ROM_BANK_SIZE * self.prg_rom_size as usize,
+        }
+    }
+
+    pub fn chr_rom_bytes(&self) -> usize {
+        match self.nes2 {
+            Some(ref nes2) => nes2.chr_rom_bytes(self.chr_rom_size),
+            None => CHR_ROM_BANK_SIZE * self.chr_rom_size as usize,
+        }
+    }
+
+    /// Size of PRG RAM. A value of zero in an iNES header means 8 KB for
+    /// compatibility with older dumps, NES 2.0 gives the exact size of the
+    /// volatile part.
+    pub fn prg_ram_bytes(&self) -> usize {
+        match self.nes2 {
+            Some(ref nes2) => nes2.prg_ram_bytes(),
+            None => PRG_RAM_BANK_SIZE * cmp::max(1, self.prg_ram_size) as usize,
+        }
+    }
+
+    /// Battery backed PRG RAM, only known for NES 2.0 headers. iNES
+    /// headers use `has_battery` and `prg_ram_bytes` instead.
+    pub fn prg_nvram_bytes(&self) -> usize {
+        self.nes2.as_ref().map_or(0, |nes2| nes2.prg_nvram_bytes())
+    }
+
+    /// iNES headers imply 8 KB of CHR RAM when there is no CHR ROM.
+    pub fn chr_ram_bytes(&self) -> usize {
+        match self.nes2 {
+            Some(ref nes2) => nes2.chr_ram_bytes(),
+            None if self.chr_rom_size == 0 => CHR_ROM_BANK_SIZE,
+            None => 0,
+        }
+    }
+
+    pub fn chr_nvram_bytes(&self) -> usize {
+        self.nes2.as_ref().map_or(0, |nes2| nes2.chr_nvram_bytes())
+    }
+
+    /// Mapper numbers above 255 only fit in NES 2.0, so the header is
+    /// upgraded for them.
+    pub fn set_mapper_number(&mut self, mapper: u16) {
+        if mapper > 0xff {
+            self.upgrade_to_nes2();
+        }
+
+        self.flag6.lower_nybble_of_mapper_number = (mapper & 0x0f) as u8;
+        self.flag7.upper_nybble_of_mapper_numbe = (mapper & 0xf0) as u8;
+
+        if let Some(ref mut nes2) = self.nes2 {
+            nes2.set_mapper_number_msb((mapper >> 8) as u8);
+        }
+    }
+
+    /// Upgrades the header to NES 2.0, the only format with submappers.
+    pub fn set_submapper(&mut self, submapper: u8) {
+        self.upgrade_to_nes2();
+
+        if let Some(ref mut nes2) = self.nes2 {
+            nes2.set_submapper(submapper);
+        }
+    }
+
+    pub fn set_mirroring(&mut self, mirroring: Mirroring) {
+        match mirroring {
+            Mirroring::FourScreen => self.flag6.igore_mirroring_control = true,
+            mirroring => {
+                self.flag6.igore_mirroring_control = false;
+                self.flag6.mirroring = mirroring;
+            }
+        }
+    }
+
+    pub fn set_battery(&mut self, battery: bool) {
+        self.flag6.contains_battery_backed_prg_ram = battery;
+
+        if let Some(ref mut nes2) = self.nes2 {
+            nes2.set_battery(battery);
+        }
+    }
+
+    pub fn set_tv_system(&mut self, tv_system: TvSystem) {
+        self.flag9.tv_system = tv_system;
+
+        if let Some(ref mut nes2) = self.nes2 {
+            nes2.set_timing(match tv_system {
+                TvSystem::NTSC => Timing::Ntsc,
+                TvSystem::PAL => Timing::Pal,
+            });
+        }
+    }
+
+    /// Multiple-region and Dendy timing can only be told by NES 2.0 headers,
+    /// so they upgrade the header.
+    pub fn set_timing(&mut self, timing: Timing) {
+        match timing {
+            Timing::Ntsc => self.set_tv_system(TvSystem::NTSC),
+            Timing::Pal => self.set_tv_system(TvSystem::PAL),
+            timing => {
+                self.upgrade_to_nes2();
+
+                if let Some(ref mut nes2) = self.nes2 {
+                    nes2.set_timing(timing);
+                }
+            }
+        }
+    }
+}
+
+impl Flag6 {
+    /// Four-screen VRAM overrides the mirroring bit.
+    pub fn mirroring(&self) -> Mirroring {
+        if self.igore_mirroring_control {
+            Mirroring::FourScreen
+        } else {
+            self.mirroring
+        }
+    }
+
+    pub fn contains_battery_backed_prg_ram(&self) -> bool {
+        self.contains_battery_backed_prg_ram
+    }
+
+    pub fn has_trainer(&self) -> bool {
+        self.has_trainer
+    }
+
+    pub fn igore_mirroring_control(&self) -> bool {
+        self.igore_mirroring_control
+    }
+
+    pub fn lower_nybble_of_mapper_number(&self) -> u8 {
+        self.lower_nybble_of_mapper_number
+    }
+}
+
+impl Flag7 {
+    pub fn vs_unisystem(&self) -> bool {
+        self.vs_unisystem
+    }
+
+    pub fn has_play_choice_10(&self) -> bool {
+        self.has_play_choice_10
+    }
+
+    pub fn is_nes_2_0(&self) -> bool {
+        self.is_nes_2_0
+    }
+
+    /// Bits 4-7 of the mapper number, not shifted down.
+    pub fn upper_nybble_of_mapper_number(&self) -> u8 {
+        self.upper_nybble_of_mapper_numbe
+    }
+}
+
+impl Flag9 {
+    pub fn tv_system(&self) -> TvSystem {
+        self.tv_system
+    }
+}
+
+impl Default for Flag10 {
+    fn default() -> Flag10 {
+        Flag10 {
+            tv_system_bits: 0b00,
+            has_prg_ram: true,
+            has_bus_conflicts: false,
+        }
+    }
+}
+
+impl Flag10 {
+    /// `Timing::MultipleRegion` for dual compatible games.
+    pub fn tv_system(&self) -> Timing {
+        match self.tv_system_bits {
+            0b00 => Timing::Ntsc,
+            0b10 => Timing::Pal,
+            _ => Timing::MultipleRegion,
+        }
+    }
+
+    /// Whether there is PRG RAM at $6000-$7FFF.
+    pub fn has_prg_ram(&self) -> bool {
+        self.has_prg_ram
+    }
+
+    pub fn has_bus_conflicts(&self) -> bool {
+        self.has_bus_conflicts
+    }
+}
+
+named!(pub parse_ines(&[u8]) -> INes<'_>,
+     do_parse!(
+         header: parse_header >>
+         ines: apply!(parse_ines_data, header) >>
+
+         (ines)
+     )
+);
+
+/// Like `parse_ines`, but accepts archaic headers with junk in bytes 7-15,
+/// such as the "DiskDude!" tag, and reports what it ignored.
+pub fn parse_ines_lenient(data: &[u8]) -> IResult<&[u8], (INes<'_>, Vec<HeaderWarning>)> {
+    if data.len() < HEADER_SIZE {
+        return match parse_header(data) {
+            IResult::Error(e) => IResult::Error(e),
+            _ => IResult::Incomplete(Needed::Size(HEADER_SIZE)),
+        };
+    }
+
+    let (header_bytes, warnings) = lenient::clean_header(data);
+
+    match parse_header(&header_bytes) {
+        IResult::Done(_, header) => parse_ines_data(&data[HEADER_SIZE..], header)
+            .map(|ines| (ines, warnings)),
+        IResult::Error(e) => IResult::Error(e),
+        IResult::Incomplete(needed) => IResult::Incomplete(needed),
+    }
+}
+
+// Everything after the header.
+named_args!(parse_ines_data(header: Header) <INes<'_>>,
+     do_parse!(
+         trainer: cond!(header.flag6.has_trainer, take!(TRAINER_SIZE)) >>
+         prg_rom_data: take!(header.prg_rom_bytes()) >>
+         chr_rom_data: cond!(header.chr_rom_bytes() != 0, take!(header.chr_rom_bytes())) >>
+         play_choice_inst_rom: cond!(
+             header.console_type() == ConsoleType::PlayChoice10,
+             take!(PLAY_CHOICE_INST_ROM_SIZE)
+         ) >>
+         play_choice_prom: cond!(
+             header.console_type() == ConsoleType::PlayChoice10,
+             opt!(complete!(take!(PLAY_CHOICE_PROM_SIZE)))
+         ) >>
+         misc_rom_data: cond!(header.misc_rom_count() != 0, call!(nom::rest)) >>
+
+         (
+             INes {
+                 header,
+                 trainer,
+                 prg_rom_data,
+                 chr_rom_data,
+                 play_choice_inst_rom,
+                 play_choice_prom: play_choice_prom.and_then(|prom| prom),
+                 misc_rom_data,
+             }
+         )
+     )
+);
+
+named!(parse_header(&[u8]) -> Header,
+    do_parse!(
+        tag!(&[b'N', b'E', b'S', 0x1a][..]) >>
+        prg_rom_size: le_u8 >>
+        chr_rom_size: le_u8 >>
+        flag6: parse_flag6 >>
+        flag7: parse_flag7 >>
+        prg_ram_size: cond_with_error!(!flag7.is_nes_2_0, le_u8) >>
+        flag9: cond_with_error!(!flag7.is_nes_2_0, parse_flag9) >>
+        flag10: cond_with_error!(!flag7.is_nes_2_0, parse_flag10) >>
+        cond_with_error!(!flag7.is_nes_2_0, count!(tag!(&[0x00]), 5)) >>
+        nes2: cond_with_error!(flag7.is_nes_2_0, parse_nes2_header) >>
+
+        ({
+            // Keep the iNES fields meaningful for NES 2.0 headers too.
+            let flag9 = flag9.unwrap_or_else(|| {
+                let tv_system = match nes2.as_ref().map(|nes2| nes2.timing()) {
+                    Some(Timing::Pal) | Some(Timing::Dendy) => TvSystem::PAL,
+                    _ => TvSystem::NTSC,
+                };
+
+                Flag9 { tv_system }
+            });
+
+            Header {
+                prg_rom_size,
+                chr_rom_size,
+                flag6,
+                flag7,
+                prg_ram_size: prg_ram_size.unwrap_or(0),
+                flag9,
+                flag10: flag10.unwrap_or_default(),
+                nes2,
+            }
+        })
+    )
+);
+
+named!(parse_flag6(&[u8]) -> Flag6,
+    do_parse!(
+        data: le_u8 >>
+
+        ({
+            let mirroring = match data & 0b00000001 {
+                0b0 => Mirroring::Horizontal,
+                _ => Mirroring::Vertical,
+            };
+
+            Flag6 {
+                mirroring,
+                contains_battery_backed_prg_ram: (data & 0b00000010) != 0b0,
+                has_trainer: (data & 0b00000100) != 0b0,
+                igore_mirroring_control: (data & 0b00001000) != 0b0,
+                lower_nybble_of_mapper_number: data >> 4,
+            }
+        })
+    )
+);
+
+named!(parse_flag7(&[u8]) -> Flag7,
+    do_parse!(
+        data: le_u8 >>
+
+        (
+            Flag7 {
+                vs_unisystem: (data & 0b00000001) != 0b0,
+                has_play_choice_10: (data & 0b00000010) != 0b0,
+                is_nes_2_0: (data & 0b00001100) == 0b1000,
+                upper_nybble_of_mapper_numbe: (data >> 4) << 4,
+            }
+        )
+    )
+);
+
+named!(parse_flag9(&[u8]) -> Flag9,
+    do_parse!(
+        data: le_u8 >>
+
+        ({
+            let tv_system = match data & 0b00000001 {
+                0b0 => TvSystem::NTSC,
+                _ => TvSystem::PAL,
+            };
+
+            Flag9 {
+                tv_system,
+            }
+        })
+    )
+);
+
+named!(parse_flag10(&[u8]) -> Flag10,
+    do_parse!(
+        data: le_u8 >>
+
+        ({
+            Flag10 {
+                tv_system_bits: data & 0b00000011,
+                has_prg_ram: (data & 0b00010000) == 0b0,
+                has_bus_conflicts: (data & 0b00100000) != 0b0,
+            }
+        })
+    )
+);
+
+#[cfg(test)]
+mod test {
+    pub use nom::IResult;
+    pub use super::*;
+
+    mod header_parser {
+        use super::*;
+
+        #[test]
+        fn should_be_parsed_correctly() {
+            let header = &[
+                b'N',
+                b'E',
+                b'S',
+                0x1a,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+            ]
+                [..];
+
+            match parse_header(header) {
+                IResult::Error(e) => panic!("{}", e),
+                _ => {}
+            }
+        }
+
+        #[test]
+        fn last_five_byte_are_not_zero() {
+            let header = &[
+                b'N',
+                b'E',
+                b'S',
+                0x1a,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x11,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x11,
+            ]
+                [..];
+
+            match parse_header(header) {
+                IResult::Error(_) => {}
+                _ => panic!("Last five bytes are not all zero"),
+            }
+        }
+    }
+
+    mod flag6_parser {
+        use super::*;
+
+        #[test]
+        fn should_be_parsed_correctly() {
+            let raw_byte = &[0b01011011][..];
+            let expected = Flag6 {
+                mirroring: Mirroring::Vertical,
+                contains_battery_backed_prg_ram: true,
+                has_trainer: false,
+                igore_mirroring_control: true,
+                lower_nybble_of_mapper_number: 0b0101,
+            };
+
+            let (_, actual) = parse_flag6(raw_byte).unwrap();
+
+            assert_eq!(expected, actual);
+        }
+    }
+
+    mod flag7_parser {
+        use super::*;
+
+        #[test]
+        fn should_be_parsed_correctly() {
+            let raw_byte = &[0b01111001][..];
+            let expected = Flag7 {
+                vs_unisystem: true,
+                has_play_choice_10: false,
+                is_nes_2_0: true,
+                upper_nybble_of_mapper_numbe: 0b01110000,
+            };
+
+            let (_, actual) = parse_flag7(raw_byte).unwrap();
+
+            assert_eq!(expected, actual);
+        }
+    }
+
+    mod flag9_parser {
+        use super::*;
+
+        #[test]
+        fn should_be_parsed_correctly() {
+            let raw_byte = &[0b00000001][..];
+            let expected = Flag9 { tv_system: TvSystem::PAL };
+
+            let (_, actual) = parse_flag9(raw_byte).unwrap();
+
+            assert_eq!(expected, actual);
+        }
+    }
+
+    mod flag10_parser {
+        use super::*;
+
+        #[test]
+        fn should_be_parsed_correctly() {
+            let raw_byte = &[0b00110010][..];
+            let expected = Flag10 {
+                tv_system_bits: 0b10,
+                has_prg_ram: false,
+                has_bus_conflicts: true,
+            };
+
+            let (_, actual) = parse_flag10(raw_byte).unwrap();
+
+            assert_eq!(expected, actual);
+        }
+
+        #[test]
+        fn dual_compatible() {
+            assert_eq!(parse_flag10(&[0b01]).unwrap().1.tv_system(), Timing::MultipleRegion);
+            assert_eq!(parse_flag10(&[0b11]).unwrap().1.tv_system(), Timing::MultipleRegion);
+        }
+    }
+
+    mod ines_accessors {
+        use super::*;
+
+        fn ines_image(flag6: u8, flag7: u8, prg_ram_size: u8, flag9: u8) -> Vec<u8> {
+            let mut image = vec![b'N', b'E', b'S', 0x1a, 0x02, 0x01, flag6, flag7, prg_ram_size, flag9];
+            image.extend_from_slice(&[0x00; 6]);
+            image.extend(vec![0xaa; 2 * 16384]);
+            image.extend(vec![0x55; 8192]);
+
+            image
+        }
+
+        #[test]
+        fn mapper_number_combines_both_nybbles() {
+            let image = ines_image(0b0001_0000, 0b0100_0000, 0, 0);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.mapper_number(), 0x41);
+        }
+
+        #[test]
+        fn mapper_number_with_only_upper_nybble() {
+            let image = ines_image(0b0000_0000, 0b1111_0000, 0, 0);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.mapper_number(), 0xf0);
+        }
+
+        #[test]
+        fn mapper_number_with_only_lower_nybble() {
+            let image = ines_image(0b0100_0000, 0b0000_0000, 0, 0);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.mapper_number(), 4);
+        }
+
+        #[test]
+        fn mirroring() {
+            let horizontal = ines_image(0b0000_0000, 0, 0, 0);
+            let vertical = ines_image(0b0000_0001, 0, 0, 0);
+            let four_screen = ines_image(0b0000_1001, 0, 0, 0);
+
+            assert_eq!(parse_ines(&horizontal).unwrap().1.mirroring(), Mirroring::Horizontal);
+            assert_eq!(parse_ines(&vertical).unwrap().1.mirroring(), Mirroring::Vertical);
+            assert_eq!(parse_ines(&four_screen).unwrap().1.mirroring(), Mirroring::FourScreen);
+        }
+
+        #[test]
+        fn has_battery() {
+            let image = ines_image(0b0000_0010, 0, 0, 0);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert!(ines.has_battery());
+        }
+
+        #[test]
+        fn prg_and_chr_rom() {
+            let image = ines_image(0, 0, 0, 0);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.prg_rom().len(), 2 * 16384);
+            assert!(ines.prg_rom().iter().all(|&byte| byte == 0xaa));
+            assert_eq!(ines.chr_rom().len(), 8192);
+            assert!(ines.chr_rom().iter().all(|&byte| byte == 0x55));
+            assert_eq!(ines.trainer(), None);
+        }
+
+        #[test]
+        fn chr_rom_is_empty_with_chr_ram() {
+            let mut image = ines_image(0, 0, 0, 0);
+            image[5] = 0;
+            image.truncate(16 + 2 * 16384);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.chr_rom(), &[][..]);
+        }
+
+        #[test]
+        fn prg_ram_bytes() {
+            let zero = ines_image(0, 0, 0, 0);
+            let four = ines_image(0, 0, 4, 0);
+
+            assert_eq!(parse_ines(&zero).unwrap().1.prg_ram_bytes(), 8192);
+            assert_eq!(parse_ines(&four).unwrap().1.prg_ram_bytes(), 4 * 8192);
+        }
+
+        #[test]
+        fn tv_system() {
+            let image = ines_image(0, 0, 0, 1);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.tv_system(), TvSystem::PAL);
+        }
+
+        #[test]
+        fn flag10() {
+            let mut image = ines_image(0, 0, 0, 0);
+            image[10] = 0b0010_0000;
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert!(ines.header().has_bus_conflicts());
+            assert!(ines.header().flag10().has_prg_ram());
+            assert_eq!(ines.header().flag10().tv_system(), Timing::Ntsc);
+        }
+
+        #[test]
+        fn play_choice_prom() {
+            let mut image = ines_image(0, 0b0000_0010, 0, 0);
+            image.extend(vec![0x11; 8192]);
+            image.extend(vec![0x22; 16]);
+            image.extend(vec![0x33; 16]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.play_choice_inst_rom(), Some(&[0x11; 8192][..]));
+            assert_eq!(ines.play_choice_prom_data(), Some(&[0x22; 16][..]));
+            assert_eq!(ines.play_choice_prom_counter_out(), Some(&[0x33; 16][..]));
+        }
+
+        #[test]
+        fn play_choice_without_prom() {
+            let mut image = ines_image(0, 0b0000_0010, 0, 0);
+            image.extend(vec![0x11; 8192]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert!(ines.play_choice_inst_rom().is_some());
+            assert_eq!(ines.play_choice_prom_data(), None);
+        }
+    }
+
+    mod nes2_accessors {
+        use super::*;
+
+        fn nes2_image(flag7: u8, tail: [u8; 8]) -> Vec<u8> {
+            let mut image = vec![b'N', b'E', b'S', 0x1a, 0x02, 0x00, 0x10, flag7 | 0b0000_1000];
+            image.extend_from_slice(&tail);
+            image.extend(vec![0xaa; 2 * 16384]);
+
+            image
+        }
+
+        #[test]
+        fn is_detected() {
+            let image = nes2_image(0, [0; 8]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert!(ines.header().flag7().is_nes_2_0());
+            assert!(ines.header().nes2().is_some());
+        }
+
+        #[test]
+        fn ines_headers_have_no_nes2_fields() {
+            let mut image = vec![b'N', b'E', b'S', 0x1a, 0x02, 0x00, 0x00, 0x00];
+            image.extend_from_slice(&[0x00; 8]);
+            image.extend(vec![0xaa; 2 * 16384]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.header().nes2(), None);
+            assert_eq!(ines.header().submapper(), None);
+            assert_eq!(ines.header().chr_ram_bytes(), 8192);
+            assert_eq!(ines.header().timing(), Timing::Ntsc);
+        }
+
+        #[test]
+        fn mapper_and_submapper() {
+            let image = nes2_image(0b0010_0000, [0x31, 0, 0, 0, 0, 0, 0, 0]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.mapper_number(), 0x121);
+            assert_eq!(ines.header().submapper(), Some(3));
+        }
+
+        #[test]
+        fn ram_sizes() {
+            let image = nes2_image(0, [0, 0, 0x97, 0x07, 0, 0, 0, 0]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.prg_ram_bytes(), 8192);
+            assert_eq!(ines.header().prg_nvram_bytes(), 32768);
+            assert_eq!(ines.header().chr_ram_bytes(), 8192);
+            assert_eq!(ines.header().chr_nvram_bytes(), 0);
+        }
+
+        #[test]
+        fn prg_rom_with_exponent_multiplier() {
+            let mut image = nes2_image(0, [0, 0x0f, 0, 0, 0, 0, 0, 0]);
+            // 2^8 * 3 bytes
+            image[4] = 0b0010_0001;
+            image.truncate(16 + 768);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.prg_rom().len(), 768);
+        }
+
+        #[test]
+        fn timing() {
+            let dendy = nes2_image(0, [0, 0, 0, 0, 3, 0, 0, 0]);
+            let (_, ines) = parse_ines(&dendy).unwrap();
+
+            assert_eq!(ines.header().timing(), Timing::Dendy);
+            assert_eq!(ines.tv_system(), TvSystem::PAL);
+        }
+
+        #[test]
+        fn vs_system_type() {
+            let image = nes2_image(0b0000_0001, [0, 0, 0, 0, 0, 0x16, 0, 0]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.header().console_type(), ConsoleType::VsSystem);
+            assert_eq!(
+                ines.header().vs_system_type(),
+                Some(VsSystemType {
+                    ppu: VsPpuType::Rc2c03b,
+                    hardware: VsHardwareType::UnisystemRbiBaseball,
+                })
+            );
+        }
+
+        #[test]
+        fn extended_console_type() {
+            let image = nes2_image(0b0000_0011, [0, 0, 0, 0, 0, 0x03, 0, 0]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.header().console_type(), ConsoleType::Extended(3));
+            assert_eq!(ines.header().vs_system_type(), None);
+        }
+
+        #[test]
+        fn misc_roms_and_expansion_device() {
+            let mut image = nes2_image(0, [0, 0, 0, 0, 0, 0, 0x01, 0x08]);
+            image.extend_from_slice(&[0x12, 0x34]);
+            let (_, ines) = parse_ines(&image).unwrap();
+
+            assert_eq!(ines.header().misc_rom_count(), 1);
+            assert_eq!(ines.header().default_expansion_device(), Some(0x08));
+            assert_eq!(ines.misc_rom(), Some(&[0x12, 0x34][..]));
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::cmp;
+
+use nom::le_u8;
+
+/// Bytes 8-15 of a NES 2.0 header, which replace the PRG RAM size, flag 9,
+/// flag 10 and the zero padding of iNES.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nes2Header {
+    mapper_number_msb: u8,
+    submapper: u8,
+    prg_rom_size_msb: u8,
+    chr_rom_size_msb: u8,
+    prg_ram_shift: u8,
+    prg_nvram_shift: u8,
+    chr_ram_shift: u8,
+    chr_nvram_shift: u8,
+    timing: Timing,
+    // Vs. System type, or the extended console type, depending on flag 7.
+    system_type: u8,
+    misc_rom_count: u8,
+    default_expansion_device: u8,
+}
+
+/// CPU/PPU timing of the console the game was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultipleRegion,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    PlayChoice10,
+    /// Famiclones and other consoles, numbered as in byte 13 of the header.
+    Extended(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VsSystemType {
+    pub ppu: VsPpuType,
+    pub hardware: VsHardwareType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsPpuType {
+    Rp2c03b,
+    Rp2c03g,
+    /// RP2C04-0001 to RP2C04-0004, each with its own scrambled palette.
+    Rp2c04(u8),
+    Rc2c03b,
+    Rc2c03c,
+    /// RC2C05-01 to RC2C05-05, with swapped $2000/$2001 and a PPU ID.
+    Rc2c05(u8),
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsHardwareType {
+    Unisystem,
+    UnisystemRbiBaseball,
+    UnisystemTkoBoxing,
+    UnisystemSuperXevious,
+    UnisystemIceClimberJapan,
+    DualSystem,
+    DualSystemRaidOnBungelingBay,
+    Unknown(u8),
+}
+
+impl VsSystemType {
+    fn from_byte(data: u8) -> VsSystemType {
+        let ppu = match data & 0x0f {
+            0x0 => VsPpuType::Rp2c03b,
+            0x1 => VsPpuType::Rp2c03g,
+            n @ 0x2..=0x5 => VsPpuType::Rp2c04(n - 1),
+            0x6 => VsPpuType::Rc2c03b,
+            0x7 => VsPpuType::Rc2c03c,
+            n @ 0x8..=0xc => VsPpuType::Rc2c05(n - 7),
+            n => VsPpuType::Unknown(n),
+        };
+
+        let hardware = match data >> 4 {
+            0x0 => VsHardwareType::Unisystem,
+            0x1 => VsHardwareType::UnisystemRbiBaseball,
+            0x2 => VsHardwareType::UnisystemTkoBoxing,
+            0x3 => VsHardwareType::UnisystemSuperXevious,
+            0x4 => VsHardwareType::UnisystemIceClimberJapan,
+            0x5 => VsHardwareType::DualSystem,
+            0x6 => VsHardwareType::DualSystemRaidOnBungelingBay,
+            n => VsHardwareType::Unknown(n),
+        };
+
+        VsSystemType { ppu, hardware }
+    }
+}
+
+// ROM size with the exponent-multiplier notation, used when the MSB nybble
+// is $F: the LSB byte is EEEEEEMM and the size is 2^E * (MM * 2 + 1) bytes.
+pub(super) fn rom_bytes(lsb: u8, msb: u8, bank_size: usize) -> usize {
+    if msb == 0x0f {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = ((lsb & 0b11) * 2 + 1) as usize;
+
+        match 1usize.checked_shl(exponent) {
+            Some(size) => size.saturating_mul(multiplier),
+            None => usize::MAX,
+        }
+    } else {
+        bank_size * (((msb as usize) << 8) | lsb as usize)
+    }
+}
+
+/// The LSB and MSB nybble `rom_bytes` turns back into `bytes`, preferring
+/// whole banks over the exponent-multiplier notation.
+pub(super) fn rom_size_fields(bytes: usize, bank_size: usize) -> Option<(u8, u8)> {
+    let banks = bytes / bank_size;
+
+    if bytes % bank_size == 0 && banks < 0xf00 {
+        return Some(((banks & 0xff) as u8, (banks >> 8) as u8));
+    }
+
+    (0..64u8)
+        .flat_map(|exponent| (0..4u8).map(move |multiplier| (exponent, multiplier)))
+        .find(|&(exponent, multiplier)| {
+            rom_bytes((exponent << 2) | multiplier, 0x0f, bank_size) == bytes
+        })
+        .map(|(exponent, multiplier)| ((exponent << 2) | multiplier, 0x0f))
+}
+
+// RAM sizes are stored as a shift count: 0 means none, otherwise 64 << n.
+fn ram_bytes(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64usize << cmp::min(shift, 57)
+    }
+}
+
+// Smallest shift count holding at least `bytes`, saturating at $F.
+fn ram_shift(bytes: usize) -> u8 {
+    if bytes == 0 {
+        0
+    } else {
+        (1..0x0f).find(|&shift| ram_bytes(shift) >= bytes).unwrap_or(0x0f)
+    }
+}
+
+impl Nes2Header {
+    /// The NES 2.0 equivalent of an iNES header. iNES cannot tell volatile
+    /// from battery backed PRG RAM, so all of it is taken as NVRAM when the
+    /// battery flag is set.
+    pub(super) fn from_ines(
+        prg_ram_bytes: usize,
+        has_battery: bool,
+        chr_ram_bytes: usize,
+        timing: Timing,
+        system_type: u8,
+    ) -> Nes2Header {
+        let (prg_ram_shift, prg_nvram_shift) = if has_battery {
+            (0, ram_shift(prg_ram_bytes))
+        } else {
+            (ram_shift(prg_ram_bytes), 0)
+        };
+
+        Nes2Header {
+            mapper_number_msb: 0,
+            submapper: 0,
+            prg_rom_size_msb: 0,
+            chr_rom_size_msb: 0,
+            prg_ram_shift,
+            prg_nvram_shift,
+            chr_ram_shift: ram_shift(chr_ram_bytes),
+            chr_nvram_shift: 0,
+            timing,
+            system_type,
+            misc_rom_count: 0,
+            default_expansion_device: 0,
+        }
+    }
+
+    /// Bytes 8-15 as they appear in the file.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let timing = match self.timing {
+            Timing::Ntsc => 0,
+            Timing::Pal => 1,
+            Timing::MultipleRegion => 2,
+            Timing::Dendy => 3,
+        };
+
+        [
+            (self.submapper << 4) | self.mapper_number_msb,
+            (self.chr_rom_size_msb << 4) | self.prg_rom_size_msb,
+            (self.prg_nvram_shift << 4) | self.prg_ram_shift,
+            (self.chr_nvram_shift << 4) | self.chr_ram_shift,
+            timing,
+            self.system_type,
+            self.misc_rom_count,
+            self.default_expansion_device,
+        ]
+    }
+
+    pub(super) fn set_mapper_number_msb(&mut self, msb: u8) {
+        self.mapper_number_msb = msb & 0x0f;
+    }
+
+    pub(super) fn set_submapper(&mut self, submapper: u8) {
+        self.submapper = submapper & 0x0f;
+    }
+
+    /// Moves PRG RAM between the volatile and battery backed halves, the
+    /// way an iNES battery flag would.
+    pub(super) fn set_battery(&mut self, battery: bool) {
+        if battery && self.prg_nvram_shift == 0 {
+            self.prg_nvram_shift = self.prg_ram_shift;
+            self.prg_ram_shift = 0;
+        } else if !battery && self.prg_ram_shift == 0 {
+            self.prg_ram_shift = self.prg_nvram_shift;
+            self.prg_nvram_shift = 0;
+        }
+    }
+
+    pub(super) fn set_rom_size_msb(&mut self, prg_rom_size_msb: u8, chr_rom_size_msb: u8) {
+        self.prg_rom_size_msb = prg_rom_size_msb & 0x0f;
+        self.chr_rom_size_msb = chr_rom_size_msb & 0x0f;
+    }
+
+    pub(super) fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// Bits 8-11 of the 12-bit mapper number.
+    pub fn mapper_number_msb(&self) -> u8 {
+        self.mapper_number_msb
+    }
+
+    pub fn submapper(&self) -> u8 {
+        self.submapper
+    }
+
+    pub fn prg_rom_bytes(&self, lsb: u8) -> usize {
+        rom_bytes(lsb, self.prg_rom_size_msb, 16384)
+    }
+
+    pub fn chr_rom_bytes(&self, lsb: u8) -> usize {
+        rom_bytes(lsb, self.chr_rom_size_msb, 8192)
+    }
+
+    pub fn prg_rom_size_msb(&self) -> u8 {
+        self.prg_rom_size_msb
+    }
+
+    pub fn chr_rom_size_msb(&self) -> u8 {
+        self.chr_rom_size_msb
+    }
+
+    /// Volatile PRG RAM.
+    pub fn prg_ram_bytes(&self) -> usize {
+        ram_bytes(self.prg_ram_shift)
+    }
+
+    /// Battery backed PRG RAM or EEPROM.
+    pub fn prg_nvram_bytes(&self) -> usize {
+        ram_bytes(self.prg_nvram_shift)
+    }
+
+    pub fn chr_ram_bytes(&self) -> usize {
+        ram_bytes(self.chr_ram_shift)
+    }
+
+    pub fn chr_nvram_bytes(&self) -> usize {
+        ram_bytes(self.chr_nvram_shift)
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Raw byte 13: the Vs. System type for Vs. System games, the extended
+    /// console type for `ConsoleType::Extended`.
+    pub fn system_type(&self) -> u8 {
+        self.system_type
+    }
+
+    pub fn misc_rom_count(&self) -> u8 {
+        self.misc_rom_count
+    }
+
+    pub fn default_expansion_device(&self) -> u8 {
+        self.default_expansion_device
+    }
+
+    pub fn vs_system_type(&self) -> VsSystemType {
+        VsSystemType::from_byte(self.system_type)
+    }
+}
+
+named!(pub parse_nes2_header(&[u8]) -> Nes2Header,
+    do_parse!(
+        mapper: le_u8 >>
+        rom_size_msb: le_u8 >>
+        prg_ram: le_u8 >>
+        chr_ram: le_u8 >>
+        timing: le_u8 >>
+        system_type: le_u8 >>
+        misc_roms: le_u8 >>
+        expansion_device: le_u8 >>
+
+        ({
+            let timing = match timing & 0b11 {
+                0 => Timing::Ntsc,
+                1 => Timing::Pal,
+                2 => Timing::MultipleRegion,
+                _ => Timing::Dendy,
+            };
+
+            Nes2Header {
+                mapper_number_msb: mapper & 0x0f,
+                submapper: mapper >> 4,
+                prg_rom_size_msb: rom_size_msb & 0x0f,
+                chr_rom_size_msb: rom_size_msb >> 4,
+                prg_ram_shift: prg_ram & 0x0f,
+                prg_nvram_shift: prg_ram >> 4,
+                chr_ram_shift: chr_ram & 0x0f,
+                chr_nvram_shift: chr_ram >> 4,
+                timing,
+                system_type,
+                misc_rom_count: misc_roms & 0b11,
+                default_expansion_device: expansion_device & 0b0011_1111,
+            }
+        })
+    )
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_be_parsed_correctly() {
+        let raw_bytes = &[0x5a, 0x21, 0x97, 0x07, 0x01, 0x23, 0x02, 0x01][..];
+        let expected = Nes2Header {
+            mapper_number_msb: 0xa,
+            submapper: 0x5,
+            prg_rom_size_msb: 0x1,
+            chr_rom_size_msb: 0x2,
+            prg_ram_shift: 0x7,
+            prg_nvram_shift: 0x9,
+            chr_ram_shift: 0x7,
+            chr_nvram_shift: 0x0,
+            timing: Timing::Pal,
+            system_type: 0x23,
+            misc_rom_count: 2,
+            default_expansion_device: 1,
+        };
+
+        let (_, actual) = parse_nes2_header(raw_bytes).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn rom_size_in_banks() {
+        assert_eq!(rom_bytes(0x02, 0x0, 16384), 2 * 16384);
+        assert_eq!(rom_bytes(0x00, 0x1, 8192), 256 * 8192);
+        assert_eq!(rom_bytes(0xff, 0xe, 16384), 0xeff * 16384);
+    }
+
+    #[test]
+    fn rom_size_with_exponent_multiplier() {
+        // 2^10 * 1
+        assert_eq!(rom_bytes(0b0010_1000, 0xf, 16384), 1024);
+        // 2^4 * 3
+        assert_eq!(rom_bytes(0b0001_0001, 0xf, 16384), 48);
+        // 2^2 * 7
+        assert_eq!(rom_bytes(0b0000_1011, 0xf, 8192), 28);
+    }
+
+    #[test]
+    fn rom_size_fields_round_trip() {
+        assert_eq!(rom_size_fields(2 * 16384, 16384), Some((2, 0)));
+        assert_eq!(rom_size_fields(0x123 * 8192, 8192), Some((0x23, 0x1)));
+        assert_eq!(rom_size_fields(8192, 16384), Some((0b0011_0100, 0xf)));
+        assert_eq!(rom_size_fields(48, 16384), Some((0b0001_0001, 0xf)));
+        assert_eq!(rom_size_fields(11, 16384), None);
+    }
+
+    #[test]
+    fn ram_size() {
+        assert_eq!(ram_bytes(0), 0);
+        assert_eq!(ram_bytes(1), 128);
+        assert_eq!(ram_bytes(7), 8192);
+        assert_eq!(ram_bytes(0xf), 64 << 15);
+    }
+
+    #[test]
+    fn ram_shift_rounds_up() {
+        assert_eq!(ram_shift(0), 0);
+        assert_eq!(ram_shift(128), 1);
+        assert_eq!(ram_shift(8192), 7);
+        assert_eq!(ram_shift(8193), 8);
+        assert_eq!(ram_shift(usize::MAX), 0xf);
+    }
+
+    #[test]
+    fn to_bytes() {
+        let raw_bytes = [0x5a, 0x21, 0x97, 0x07, 0x01, 0x23, 0x02, 0x01];
+        let (_, header) = parse_nes2_header(&raw_bytes[..]).unwrap();
+
+        assert_eq!(header.to_bytes(), raw_bytes);
+    }
+
+    #[test]
+    fn vs_system_type() {
+        assert_eq!(
+            VsSystemType::from_byte(0x53),
+            VsSystemType {
+                ppu: VsPpuType::Rp2c04(2),
+                hardware: VsHardwareType::DualSystem,
+            }
+        );
+        assert_eq!(
+            VsSystemType::from_byte(0x0c),
+            VsSystemType {
+                ppu: VsPpuType::Rc2c05(5),
+                hardware: VsHardwareType::Unisystem,
+            }
+        );
+        assert_eq!(VsSystemType::from_byte(0xfd).ppu, VsPpuType::Unknown(0xd));
+    }
+}
@@ -0,0 +1,356 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use nom::{self, le_u16, le_u8};
+
+use super::Timing;
+
+mod nsfe;
+
+pub use self::nsfe::{parse_nsfe, NsfMetadata, Nsfe};
+
+/// Size of the header, the program data follows it.
+pub const NSF_HEADER_SIZE: usize = 128;
+
+#[derive(Debug, PartialEq)]
+pub struct Nsf<'n> {
+    version: u8,
+    total_songs: u8,
+    starting_song: u8,
+    load_addr: u16,
+    init_addr: u16,
+    play_addr: u16,
+    title: &'n [u8],
+    artist: &'n [u8],
+    copyright: &'n [u8],
+    ntsc_speed: u16,
+    bankswitch_init: [u8; 8],
+    pal_speed: u16,
+    region: u8,
+    expansion_chips: ExpansionChips,
+    nsf2_flags: u8,
+    data: &'n [u8],
+    metadata: Option<&'n [u8]>,
+}
+
+/// Sound chips beyond the 2A03 the tune uses, byte $7B of the header.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ExpansionChips(u8);
+
+impl ExpansionChips {
+    pub const VRC6: ExpansionChips = ExpansionChips(0b0000_0001);
+    pub const VRC7: ExpansionChips = ExpansionChips(0b0000_0010);
+    pub const FDS: ExpansionChips = ExpansionChips(0b0000_0100);
+    pub const MMC5: ExpansionChips = ExpansionChips(0b0000_1000);
+    pub const NAMCO_163: ExpansionChips = ExpansionChips(0b0001_0000);
+    pub const SUNSOFT_5B: ExpansionChips = ExpansionChips(0b0010_0000);
+    pub const VT02: ExpansionChips = ExpansionChips(0b0100_0000);
+
+    pub fn from_bits(bits: u8) -> ExpansionChips {
+        ExpansionChips(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, other: ExpansionChips) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl fmt::Debug for ExpansionChips {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (ExpansionChips::VRC6, "VRC6"),
+            (ExpansionChips::VRC7, "VRC7"),
+            (ExpansionChips::FDS, "FDS"),
+            (ExpansionChips::MMC5, "MMC5"),
+            (ExpansionChips::NAMCO_163, "NAMCO_163"),
+            (ExpansionChips::SUNSOFT_5B, "SUNSOFT_5B"),
+            (ExpansionChips::VT02, "VT02"),
+        ];
+
+        let mut list = fmt.debug_set();
+        for &(chip, name) in names.iter() {
+            if self.contains(chip) {
+                list.entry(&format_args!("{}", name));
+            }
+        }
+
+        list.finish()
+    }
+}
+
+// Header strings are zero padded, and not always zero terminated.
+fn header_string(data: &[u8]) -> Cow<'_, str> {
+    let end = data.iter().position(|&byte| byte == 0x00).unwrap_or(data.len());
+
+    String::from_utf8_lossy(&data[..end])
+}
+
+impl<'n> Nsf<'n> {
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn total_songs(&self) -> u8 {
+        self.total_songs
+    }
+
+    /// 1-based, as in the header.
+    pub fn starting_song(&self) -> u8 {
+        self.starting_song
+    }
+
+    /// Where the program data is loaded, unless the tune is bankswitched.
+    pub fn load_addr(&self) -> u16 {
+        self.load_addr
+    }
+
+    /// Called with the 0-based song number in A and the region in X.
+    pub fn init_addr(&self) -> u16 {
+        self.init_addr
+    }
+
+    /// Called once per frame, at the rate given by the speed fields.
+    pub fn play_addr(&self) -> u16 {
+        self.play_addr
+    }
+
+    pub fn title(&self) -> Cow<'n, str> {
+        header_string(self.title)
+    }
+
+    pub fn artist(&self) -> Cow<'n, str> {
+        header_string(self.artist)
+    }
+
+    pub fn copyright(&self) -> Cow<'n, str> {
+        header_string(self.copyright)
+    }
+
+    /// Microseconds between play calls on NTSC.
+    pub fn ntsc_speed(&self) -> u16 {
+        self.ntsc_speed
+    }
+
+    /// Microseconds between play calls on PAL.
+    pub fn pal_speed(&self) -> u16 {
+        self.pal_speed
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        self.bankswitch_init.iter().any(|&bank| bank != 0)
+    }
+
+    /// 4 KB banks written to $5FF8-$5FFF before init, if the tune is
+    /// bankswitched.
+    pub fn bankswitch_init(&self) -> Option<[u8; 8]> {
+        if self.is_bankswitched() {
+            Some(self.bankswitch_init)
+        } else {
+            None
+        }
+    }
+
+    /// `Timing::MultipleRegion` for tunes that play on both.
+    pub fn region(&self) -> Timing {
+        match self.region & 0b11 {
+            0b00 => Timing::Ntsc,
+            0b01 => Timing::Pal,
+            _ => Timing::MultipleRegion,
+        }
+    }
+
+    pub fn expansion_chips(&self) -> ExpansionChips {
+        self.expansion_chips
+    }
+
+    /// Byte $7C, zero before NSF2.
+    pub fn nsf2_flags(&self) -> u8 {
+        self.nsf2_flags
+    }
+
+    /// Program data, loaded at `load_addr` or split into 4 KB banks
+    /// starting at `load_addr & $0FFF`.
+    pub fn data(&self) -> &'n [u8] {
+        self.data
+    }
+
+    /// NSF2 metadata after the program data, in NSFe chunk format.
+    pub fn metadata(&self) -> Option<&'n [u8]> {
+        self.metadata
+    }
+}
+
+named!(pub parse_nsf(&[u8]) -> Nsf<'_>,
+    do_parse!(
+        tag!(&[b'N', b'E', b'S', b'M', 0x1a][..]) >>
+        version: le_u8 >>
+        total_songs: le_u8 >>
+        starting_song: le_u8 >>
+        load_addr: le_u16 >>
+        init_addr: le_u16 >>
+        play_addr: le_u16 >>
+        title: take!(32) >>
+        artist: take!(32) >>
+        copyright: take!(32) >>
+        ntsc_speed: le_u16 >>
+        bankswitch: take!(8) >>
+        pal_speed: le_u16 >>
+        region: le_u8 >>
+        expansion_chips: le_u8 >>
+        nsf2_flags: le_u8 >>
+        data_length: take!(3) >>
+        // NSF2 gives the program data length when metadata follows it,
+        // NSF1 leaves these bytes zero.
+        data: call!(
+            take_data,
+            data_length[0] as usize | (data_length[1] as usize) << 8 |
+                (data_length[2] as usize) << 16
+        ) >>
+        metadata: opt!(complete!(call!(nom::non_empty))) >>
+
+        ({
+            let mut bankswitch_init = [0; 8];
+            bankswitch_init.copy_from_slice(bankswitch);
+
+            Nsf {
+                version,
+                total_songs,
+                starting_song,
+                load_addr,
+                init_addr,
+                play_addr,
+                title,
+                artist,
+                copyright,
+                ntsc_speed,
+                bankswitch_init,
+                pal_speed,
+                region,
+                expansion_chips: ExpansionChips(expansion_chips),
+                nsf2_flags,
+                data,
+                metadata,
+            }
+        })
+    )
+);
+
+fn take_data(input: &[u8], length: usize) -> nom::IResult<&[u8], &[u8]> {
+    if length == 0 {
+        nom::rest(input)
+    } else {
+        take!(input, length)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn nsf_header() -> Vec<u8> {
+        let mut header = vec![b'N', b'E', b'S', b'M', 0x1a, 0x01, 0x05, 0x02];
+        header.extend_from_slice(&[0x00, 0x80, 0x03, 0x80, 0x06, 0x80]);
+
+        let mut title = b"Title".to_vec();
+        title.resize(32, 0x00);
+        header.extend(title);
+
+        let mut artist = b"Artist".to_vec();
+        artist.resize(32, 0x00);
+        header.extend(artist);
+
+        // Not zero terminated.
+        header.extend_from_slice(&[b'C'; 32]);
+
+        header.extend_from_slice(&[0x1a, 0x41]);
+        header.extend_from_slice(&[0x00; 8]);
+        header.extend_from_slice(&[0x20, 0x4e]);
+        header.extend_from_slice(&[0b10, 0b0000_0101, 0x00, 0x00, 0x00, 0x00]);
+
+        header
+    }
+
+    #[test]
+    fn should_be_parsed_correctly() {
+        let mut image = nsf_header();
+        image.extend_from_slice(&[0xa9, 0x00, 0x60]);
+
+        let (rest, nsf) = parse_nsf(&image).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(nsf.version(), 1);
+        assert_eq!(nsf.total_songs(), 5);
+        assert_eq!(nsf.starting_song(), 2);
+        assert_eq!(nsf.load_addr(), 0x8000);
+        assert_eq!(nsf.init_addr(), 0x8003);
+        assert_eq!(nsf.play_addr(), 0x8006);
+        assert_eq!(nsf.title(), "Title");
+        assert_eq!(nsf.artist(), "Artist");
+        assert_eq!(nsf.copyright(), "C".repeat(32));
+        assert_eq!(nsf.ntsc_speed(), 16666);
+        assert_eq!(nsf.pal_speed(), 20000);
+        assert_eq!(nsf.bankswitch_init(), None);
+        assert_eq!(nsf.region(), Timing::MultipleRegion);
+        assert_eq!(nsf.data(), &[0xa9, 0x00, 0x60][..]);
+        assert_eq!(nsf.metadata(), None);
+    }
+
+    #[test]
+    fn expansion_chips() {
+        let image = nsf_header();
+        let (_, nsf) = parse_nsf(&image).unwrap();
+        let chips = nsf.expansion_chips();
+
+        assert!(chips.contains(ExpansionChips::VRC6));
+        assert!(chips.contains(ExpansionChips::FDS));
+        assert!(!chips.contains(ExpansionChips::VRC7));
+        assert_eq!(format!("{:?}", chips), "{VRC6, FDS}");
+    }
+
+    #[test]
+    fn bankswitched() {
+        let mut image = nsf_header();
+        image[0x70..0x78].copy_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7]);
+
+        let (_, nsf) = parse_nsf(&image).unwrap();
+
+        assert!(nsf.is_bankswitched());
+        assert_eq!(nsf.bankswitch_init(), Some([0, 1, 2, 3, 4, 5, 6, 7]));
+    }
+
+    #[test]
+    fn nsf2_metadata() {
+        let mut image = nsf_header();
+        image[5] = 0x02;
+        image[0x7d] = 0x02;
+        image.extend_from_slice(&[0xea, 0x60, b'I', b'N', b'F', b'O']);
+
+        let (_, nsf) = parse_nsf(&image).unwrap();
+
+        assert_eq!(nsf.data(), &[0xea, 0x60][..]);
+        assert_eq!(nsf.metadata(), Some(&b"INFO"[..]));
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut image = nsf_header();
+        image[3] = b'N';
+
+        assert!(parse_nsf(&image).is_err());
+    }
+
+    #[test]
+    fn truncated_header() {
+        let image = nsf_header();
+
+        assert!(parse_nsf(&image[..NSF_HEADER_SIZE - 1]).is_incomplete());
+    }
+}
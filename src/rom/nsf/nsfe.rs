@@ -0,0 +1,420 @@
+use std::borrow::Cow;
+
+use nom::{le_u16, le_u32, le_u8};
+
+use super::{header_string, ExpansionChips, Nsf};
+
+// Play rates of the NSF specification, used when there is no RATE chunk.
+const DEFAULT_NTSC_SPEED: u16 = 16639;
+const DEFAULT_PAL_SPEED: u16 = 19997;
+
+/// An NSFe file: the tune itself, and what NSFe can say about its tracks.
+#[derive(Debug, PartialEq)]
+pub struct Nsfe<'n> {
+    nsf: Nsf<'n>,
+    metadata: NsfMetadata<'n>,
+}
+
+/// The optional NSFe chunks, also found after the program data of NSF2
+/// files.
+#[derive(Debug, Default, PartialEq)]
+pub struct NsfMetadata<'n> {
+    playlist: Option<&'n [u8]>,
+    times: Vec<i32>,
+    fades: Vec<i32>,
+    track_labels: Vec<&'n [u8]>,
+    authors: Vec<&'n [u8]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Chunk<'n> {
+    id: &'n [u8],
+    data: &'n [u8],
+}
+
+impl<'n> Nsfe<'n> {
+    /// The header fields of an NSF file, filled in from INFO, DATA, BANK,
+    /// RATE and auth.
+    pub fn nsf(&self) -> &Nsf<'n> {
+        &self.nsf
+    }
+
+    pub fn metadata(&self) -> &NsfMetadata<'n> {
+        &self.metadata
+    }
+}
+
+// A list of zero terminated strings.
+fn strings(data: &[u8]) -> Vec<&[u8]> {
+    let data = if data.last() == Some(&0x00) {
+        &data[..data.len() - 1]
+    } else {
+        data
+    };
+
+    if data.is_empty() {
+        Vec::new()
+    } else {
+        data.split(|&byte| byte == 0x00).collect()
+    }
+}
+
+fn milliseconds(data: &[u8]) -> Vec<i32> {
+    data.chunks(4)
+        .filter(|bytes| bytes.len() == 4)
+        .map(|bytes| {
+            (bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 |
+                 (bytes[3] as u32) << 24) as i32
+        })
+        .collect()
+}
+
+// Negative durations mean the player should use its default.
+fn duration(durations: &[i32], track: u8) -> Option<u32> {
+    durations
+        .get(track as usize)
+        .and_then(|&ms| if ms < 0 { None } else { Some(ms as u32) })
+}
+
+impl<'n> NsfMetadata<'n> {
+    fn from_chunks(chunks: &[Chunk<'n>]) -> NsfMetadata<'n> {
+        let mut metadata = NsfMetadata::default();
+
+        for chunk in chunks {
+            match chunk.id {
+                b"plst" => metadata.playlist = Some(chunk.data),
+                b"time" => metadata.times = milliseconds(chunk.data),
+                b"fade" => metadata.fades = milliseconds(chunk.data),
+                b"tlbl" => metadata.track_labels = strings(chunk.data),
+                b"auth" => metadata.authors = strings(chunk.data),
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+
+    /// Track numbers in the order they should be played, if not all of
+    /// them in order.
+    pub fn playlist(&self) -> Option<&'n [u8]> {
+        self.playlist
+    }
+
+    /// How long the 0-based track plays before fading out.
+    pub fn track_duration_ms(&self, track: u8) -> Option<u32> {
+        duration(&self.times, track)
+    }
+
+    /// How long the fade out of the 0-based track lasts.
+    pub fn track_fade_ms(&self, track: u8) -> Option<u32> {
+        duration(&self.fades, track)
+    }
+
+    pub fn track_label(&self, track: u8) -> Option<Cow<'n, str>> {
+        self.track_labels
+            .get(track as usize)
+            .map(|&label| header_string(label))
+    }
+
+    /// Title, artist, copyright and ripper from the auth chunk, any of which
+    /// may be missing.
+    pub fn author(&self, index: usize) -> Option<Cow<'n, str>> {
+        self.authors.get(index).map(|&author| header_string(author))
+    }
+
+    pub fn ripper(&self) -> Option<Cow<'n, str>> {
+        self.author(3)
+    }
+}
+
+impl<'n> Nsf<'n> {
+    /// The NSFe chunks NSF2 files may carry after the program data.
+    pub fn nsf2_metadata(&self) -> Option<NsfMetadata<'n>> {
+        self.metadata
+            .and_then(|metadata| parse_metadata_chunks(metadata).to_result().ok())
+            .map(|chunks| NsfMetadata::from_chunks(&chunks))
+    }
+}
+
+// Chunks with an upper case first letter must be understood by the reader.
+fn is_understood(chunk: &Chunk) -> bool {
+    !chunk.id[0].is_ascii_uppercase() ||
+        [&b"INFO"[..], b"DATA", b"BANK", b"RATE", b"NSF2"].contains(&chunk.id)
+}
+
+fn nsfe_from_chunks<'n>(chunks: &[Chunk<'n>]) -> Option<Nsfe<'n>> {
+    if !chunks.iter().all(is_understood) {
+        return None;
+    }
+
+    let find = |id: &[u8]| chunks.iter().find(|chunk| chunk.id == id).map(|chunk| chunk.data);
+
+    let info = match parse_info(find(b"INFO")?) {
+        ::nom::IResult::Done(_, info) => info,
+        _ => return None,
+    };
+
+    let mut bankswitch_init = [0; 8];
+    if let Some(bank) = find(b"BANK") {
+        let len = bank.len().min(8);
+        bankswitch_init[..len].copy_from_slice(&bank[..len]);
+    }
+
+    let rate = find(b"RATE").unwrap_or(&[]);
+    let speed = |offset: usize, default: u16| {
+        rate.get(offset..offset + 2)
+            .map_or(default, |bytes| bytes[0] as u16 | (bytes[1] as u16) << 8)
+    };
+
+    let metadata = NsfMetadata::from_chunks(chunks);
+    let author = |index: usize| metadata.authors.get(index).cloned().unwrap_or(&[]);
+
+    let nsf = Nsf {
+        version: 1,
+        total_songs: info.total_songs,
+        starting_song: info.starting_song.saturating_add(1),
+        load_addr: info.load_addr,
+        init_addr: info.init_addr,
+        play_addr: info.play_addr,
+        title: author(0),
+        artist: author(1),
+        copyright: author(2),
+        ntsc_speed: speed(0, DEFAULT_NTSC_SPEED),
+        bankswitch_init,
+        pal_speed: speed(2, DEFAULT_PAL_SPEED),
+        region: info.region,
+        expansion_chips: info.expansion_chips,
+        nsf2_flags: 0,
+        data: find(b"DATA")?,
+        metadata: None,
+    };
+
+    Some(Nsfe { nsf, metadata })
+}
+
+struct Info {
+    load_addr: u16,
+    init_addr: u16,
+    play_addr: u16,
+    region: u8,
+    expansion_chips: ExpansionChips,
+    total_songs: u8,
+    starting_song: u8,
+}
+
+named!(parse_info(&[u8]) -> Info,
+    do_parse!(
+        load_addr: le_u16 >>
+        init_addr: le_u16 >>
+        play_addr: le_u16 >>
+        region: le_u8 >>
+        expansion_chips: le_u8 >>
+        // Both were added later, and default to one song starting at 0.
+        total_songs: opt!(complete!(le_u8)) >>
+        starting_song: opt!(complete!(le_u8)) >>
+
+        (
+            Info {
+                load_addr,
+                init_addr,
+                play_addr,
+                region,
+                expansion_chips: ExpansionChips(expansion_chips),
+                total_songs: total_songs.unwrap_or(1),
+                starting_song: starting_song.unwrap_or(0),
+            }
+        )
+    )
+);
+
+named!(pub parse_nsfe(&[u8]) -> Nsfe<'_>,
+    do_parse!(
+        tag!(&b"NSFE"[..]) >>
+        chunks: many_till!(parse_chunk, parse_nend) >>
+        nsfe: expr_opt!(nsfe_from_chunks(&chunks.0)) >>
+
+        (nsfe)
+    )
+);
+
+named!(parse_nend(&[u8]) -> &[u8],
+    do_parse!(
+        tag!(&[0x00, 0x00, 0x00, 0x00][..]) >>
+        id: tag!(&b"NEND"[..]) >>
+
+        (id)
+    )
+);
+
+// NSF2 metadata has no NEND chunk, it simply ends with the file.
+named!(parse_metadata_chunks(&[u8]) -> Vec<Chunk<'_>>,
+    many0!(parse_chunk)
+);
+
+named!(parse_chunk(&[u8]) -> Chunk<'_>,
+    do_parse!(
+        length: le_u32 >>
+        id: take!(4) >>
+        data: take!(length) >>
+
+        (
+            Chunk {
+                id,
+                data,
+            }
+        )
+    )
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rom::Timing;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let len = data.len();
+        let mut chunk = vec![len as u8, (len >> 8) as u8, 0, 0];
+        chunk.extend_from_slice(id);
+        chunk.extend_from_slice(data);
+
+        chunk
+    }
+
+    fn nsfe_image(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut image = b"NSFE".to_vec();
+        for chunk in chunks {
+            image.extend_from_slice(chunk);
+        }
+        image.extend(chunk(b"NEND", &[]));
+
+        image
+    }
+
+    fn info() -> Vec<u8> {
+        chunk(b"INFO", &[0x00, 0x80, 0x03, 0x80, 0x06, 0x80, 0x01, 0x20, 0x03, 0x01])
+    }
+
+    #[test]
+    fn minimal() {
+        let image = nsfe_image(&[info(), chunk(b"DATA", &[0xea, 0x60])]);
+
+        let (rest, nsfe) = parse_nsfe(&image).unwrap();
+        let nsf = nsfe.nsf();
+
+        assert!(rest.is_empty());
+        assert_eq!(nsf.load_addr(), 0x8000);
+        assert_eq!(nsf.init_addr(), 0x8003);
+        assert_eq!(nsf.play_addr(), 0x8006);
+        assert_eq!(nsf.region(), Timing::Pal);
+        assert!(nsf.expansion_chips().contains(ExpansionChips::SUNSOFT_5B));
+        assert_eq!(nsf.total_songs(), 3);
+        assert_eq!(nsf.starting_song(), 2);
+        assert_eq!(nsf.ntsc_speed(), DEFAULT_NTSC_SPEED);
+        assert_eq!(nsf.bankswitch_init(), None);
+        assert_eq!(nsf.data(), &[0xea, 0x60][..]);
+        assert_eq!(nsf.title(), "");
+        assert_eq!(nsfe.metadata().playlist(), None);
+    }
+
+    #[test]
+    fn short_info() {
+        let image = nsfe_image(&[
+            chunk(b"INFO", &[0x00, 0x80, 0x03, 0x80, 0x06, 0x80, 0x00, 0x00]),
+            chunk(b"DATA", &[0x60]),
+        ]);
+
+        let (_, nsfe) = parse_nsfe(&image).unwrap();
+
+        assert_eq!(nsfe.nsf().total_songs(), 1);
+        assert_eq!(nsfe.nsf().starting_song(), 1);
+    }
+
+    #[test]
+    fn bank_and_rate() {
+        let image = nsfe_image(&[
+            info(),
+            chunk(b"DATA", &[0x60]),
+            chunk(b"BANK", &[0, 1, 2]),
+            chunk(b"RATE", &[0x1a, 0x41, 0x20, 0x4e]),
+        ]);
+
+        let (_, nsfe) = parse_nsfe(&image).unwrap();
+
+        assert_eq!(nsfe.nsf().bankswitch_init(), Some([0, 1, 2, 0, 0, 0, 0, 0]));
+        assert_eq!(nsfe.nsf().ntsc_speed(), 16666);
+        assert_eq!(nsfe.nsf().pal_speed(), 20000);
+    }
+
+    #[test]
+    fn metadata() {
+        let image = nsfe_image(&[
+            info(),
+            chunk(b"DATA", &[0x60]),
+            chunk(b"plst", &[2, 0, 1]),
+            chunk(b"time", &[0x30, 0x75, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff]),
+            chunk(b"fade", &[0xe8, 0x03, 0x00, 0x00]),
+            chunk(b"tlbl", b"Intro\0Overworld\0Boss\0"),
+            chunk(b"auth", b"Game\0Composer\0\0Ripper\0"),
+        ]);
+
+        let (_, nsfe) = parse_nsfe(&image).unwrap();
+        let metadata = nsfe.metadata();
+
+        assert_eq!(metadata.playlist(), Some(&[2, 0, 1][..]));
+        assert_eq!(metadata.track_duration_ms(0), Some(30000));
+        assert_eq!(metadata.track_duration_ms(1), None);
+        assert_eq!(metadata.track_duration_ms(2), None);
+        assert_eq!(metadata.track_fade_ms(0), Some(1000));
+        assert_eq!(metadata.track_fade_ms(1), None);
+        assert_eq!(metadata.track_label(1).unwrap(), "Overworld");
+        assert_eq!(metadata.track_label(3), None);
+        assert_eq!(metadata.ripper().unwrap(), "Ripper");
+        assert_eq!(nsfe.nsf().title(), "Game");
+        assert_eq!(nsfe.nsf().artist(), "Composer");
+        assert_eq!(nsfe.nsf().copyright(), "");
+    }
+
+    #[test]
+    fn unknown_optional_chunk() {
+        let image = nsfe_image(&[info(), chunk(b"DATA", &[0x60]), chunk(b"text", b"hi\0")]);
+
+        assert!(parse_nsfe(&image).is_done());
+    }
+
+    #[test]
+    fn unknown_mandatory_chunk() {
+        let image = nsfe_image(&[info(), chunk(b"DATA", &[0x60]), chunk(b"VRC7", &[0x00])]);
+
+        assert!(parse_nsfe(&image).is_err());
+    }
+
+    #[test]
+    fn missing_data() {
+        let image = nsfe_image(&[info()]);
+
+        assert!(parse_nsfe(&image).is_err());
+    }
+
+    #[test]
+    fn missing_nend() {
+        let mut image = nsfe_image(&[info(), chunk(b"DATA", &[0x60])]);
+        image.truncate(image.len() - 8);
+
+        assert!(parse_nsfe(&image).is_incomplete());
+    }
+
+    #[test]
+    fn nsf2_metadata() {
+        let mut image = b"NESM\x1a\x02\x02\x01".to_vec();
+        image.extend_from_slice(&[0x00; 0x7d - 8]);
+        image.extend_from_slice(&[0x01, 0x00, 0x00]);
+        image.push(0x60);
+        image.extend(chunk(b"tlbl", b"One\0Two\0"));
+
+        let (_, nsf) = ::rom::parse_nsf(&image).unwrap();
+        let metadata = nsf.nsf2_metadata().unwrap();
+
+        assert_eq!(metadata.track_label(1).unwrap(), "Two");
+    }
+}
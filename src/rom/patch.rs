@@ -0,0 +1,862 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nom::{be_u16, be_u24, be_u8, le_u32, ErrorKind, IResult, Needed};
+
+use super::hash::crc32;
+use super::RomError;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+// Source, target and patch CRC-32s, at the end of UPS and BPS patches.
+const FOOTER_SIZE: usize = 12;
+
+// Far beyond any real ROM, but keeps a corrupt size from exhausting memory.
+const MAX_TARGET_SIZE: u64 = 0x1000_0000;
+
+/// Extensions of the patches `find_sibling_patch` looks for, in order.
+pub const PATCH_EXTENSIONS: [&str; 3] = ["bps", "ups", "ips"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// Not an IPS, UPS or BPS patch, or a malformed one.
+    Invalid,
+    /// The patch was made for a ROM of another size.
+    SourceSize { expected: u64, actual: usize },
+    /// The patch was made for another ROM.
+    SourceChecksum { expected: u32, actual: u32 },
+    /// Applying the patch gave something else than what it was made from.
+    TargetChecksum { expected: u32, actual: u32 },
+    /// The patch itself is corrupt.
+    PatchChecksum { expected: u32, actual: u32 },
+    /// A record reads or writes past the end of the source or target.
+    OutOfBounds,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::Invalid => write!(fmt, "not a valid IPS, UPS or BPS patch"),
+            PatchError::SourceSize { expected, actual } => write!(
+                fmt,
+                "patch expects a {} byte rom, got {} bytes",
+                expected, actual
+            ),
+            PatchError::SourceChecksum { expected, actual } => write!(
+                fmt,
+                "patch expects a rom with CRC32 {:08x}, got {:08x}",
+                expected, actual
+            ),
+            PatchError::TargetChecksum { expected, actual } => write!(
+                fmt,
+                "patched rom should have CRC32 {:08x}, got {:08x}",
+                expected, actual
+            ),
+            PatchError::PatchChecksum { expected, actual } => write!(
+                fmt,
+                "patch is corrupt: expected CRC32 {:08x}, got {:08x}",
+                expected, actual
+            ),
+            PatchError::OutOfBounds => write!(fmt, "patch reads or writes out of bounds"),
+        }
+    }
+}
+
+impl error::Error for PatchError {}
+
+fn check_crc(
+    expected: u32,
+    actual: u32,
+    error: fn(u32, u32) -> PatchError,
+) -> Result<(), PatchError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(error(expected, actual))
+    }
+}
+
+fn target_size(size: u64) -> Result<usize, PatchError> {
+    if size <= MAX_TARGET_SIZE {
+        Ok(size as usize)
+    } else {
+        Err(PatchError::OutOfBounds)
+    }
+}
+
+/// An IPS, UPS or BPS patch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch<'p> {
+    Ips(Ips<'p>),
+    Ups(Ups<'p>),
+    Bps(Bps<'p>),
+}
+
+impl<'p> Patch<'p> {
+    /// Parses a patch of any supported format, told apart by its magic number.
+    pub fn from_bytes(data: &'p [u8]) -> Result<Patch<'p>, PatchError> {
+        let patch = if data.starts_with(IPS_MAGIC) {
+            parse_ips(data).map(Patch::Ips)
+        } else if data.starts_with(UPS_MAGIC) {
+            parse_ups(data).map(Patch::Ups)
+        } else if data.starts_with(BPS_MAGIC) {
+            parse_bps(data).map(Patch::Bps)
+        } else {
+            return Err(PatchError::Invalid);
+        };
+
+        match patch {
+            IResult::Done(_, patch) => Ok(patch),
+            _ => Err(PatchError::Invalid),
+        }
+    }
+
+    /// Applies the patch to a raw ROM image, header included, checking the
+    /// CRC-32s of UPS and BPS patches.
+    pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>, PatchError> {
+        match *self {
+            Patch::Ips(ref ips) => Ok(ips.apply(source)),
+            Patch::Ups(ref ups) => ups.apply(source),
+            Patch::Bps(ref bps) => bps.apply(source),
+        }
+    }
+}
+
+/// Looks for a patch named after the ROM, such as `game.bps` next to
+/// `game.nes`.
+pub fn find_sibling_patch<P: AsRef<Path>>(rom_path: P) -> Option<PathBuf> {
+    let rom_path = rom_path.as_ref();
+
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path != rom_path && path.is_file())
+}
+
+/// Reads a ROM image and soft-patches it with its sibling patch, if any.
+/// Also returns the path of the patch which was applied.
+pub fn read_patched<P: AsRef<Path>>(path: P) -> Result<(Vec<u8>, Option<PathBuf>), RomError> {
+    let data = fs::read(&path)?;
+
+    match find_sibling_patch(&path) {
+        Some(patch_path) => {
+            let patch = fs::read(&patch_path)?;
+            let patched = Patch::from_bytes(&patch)?.apply(&data)?;
+
+            Ok((patched, Some(patch_path)))
+        }
+        None => Ok((data, None)),
+    }
+}
+
+/// An IPS patch, with the RLE records and truncation of Lunar IPS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ips<'p> {
+    records: Vec<IpsRecord<'p>>,
+    truncate: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IpsRecord<'p> {
+    Data { offset: u32, data: &'p [u8] },
+    /// `len` copies of `value`.
+    Rle { offset: u32, len: u16, value: u8 },
+}
+
+impl<'p> Ips<'p> {
+    pub fn records(&self) -> &[IpsRecord<'p>] {
+        &self.records
+    }
+
+    /// The size the patched ROM is cut down to.
+    pub fn truncate(&self) -> Option<u32> {
+        self.truncate
+    }
+
+    /// IPS has no checksums, so any source is patched, growing it with
+    /// zeros when records write past its end.
+    pub fn apply(&self, source: &[u8]) -> Vec<u8> {
+        let mut target = source.to_vec();
+
+        for record in &self.records {
+            let (offset, len) = match *record {
+                IpsRecord::Data { offset, data } => (offset as usize, data.len()),
+                IpsRecord::Rle { offset, len, .. } => (offset as usize, len as usize),
+            };
+            if target.len() < offset + len {
+                target.resize(offset + len, 0x00);
+            }
+
+            match *record {
+                IpsRecord::Data { data, .. } => {
+                    target[offset..offset + len].copy_from_slice(data)
+                }
+                IpsRecord::Rle { value, .. } => {
+                    target[offset..offset + len].iter_mut().for_each(|byte| *byte = value)
+                }
+            }
+        }
+
+        if let Some(len) = self.truncate {
+            target.truncate(len as usize);
+        }
+
+        target
+    }
+}
+
+/// A UPS patch: runs of bytes XORed into the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ups<'p> {
+    source_size: u64,
+    target_size: u64,
+    hunks: Vec<UpsHunk<'p>>,
+    source_crc: u32,
+    target_crc: u32,
+    patch_crc: u32,
+    actual_patch_crc: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct UpsHunk<'p> {
+    skip: u64,
+    xor: &'p [u8],
+}
+
+impl<'p> Ups<'p> {
+    pub fn source_size(&self) -> u64 {
+        self.source_size
+    }
+
+    pub fn target_size(&self) -> u64 {
+        self.target_size
+    }
+
+    pub fn source_crc(&self) -> u32 {
+        self.source_crc
+    }
+
+    pub fn target_crc(&self) -> u32 {
+        self.target_crc
+    }
+
+    pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>, PatchError> {
+        check_crc(self.patch_crc, self.actual_patch_crc, |expected, actual| {
+            PatchError::PatchChecksum { expected, actual }
+        })?;
+        if source.len() as u64 != self.source_size {
+            return Err(PatchError::SourceSize {
+                expected: self.source_size,
+                actual: source.len(),
+            });
+        }
+        check_crc(self.source_crc, crc32(source), |expected, actual| {
+            PatchError::SourceChecksum { expected, actual }
+        })?;
+
+        let target_size = target_size(self.target_size)?;
+        let mut target = source.to_vec();
+        target.resize(target_size, 0x00);
+
+        // Bytes past the end of the target are dropped, as by the reference
+        // implementation.
+        let mut offset: u64 = 0;
+        for hunk in &self.hunks {
+            offset = offset.checked_add(hunk.skip).ok_or(PatchError::OutOfBounds)?;
+            for &xor in hunk.xor {
+                if offset < target_size as u64 {
+                    target[offset as usize] ^= xor;
+                }
+                offset = offset.checked_add(1).ok_or(PatchError::OutOfBounds)?;
+            }
+            // The terminating zero stands for an unchanged byte.
+            offset = offset.checked_add(1).ok_or(PatchError::OutOfBounds)?;
+        }
+
+        check_crc(self.target_crc, crc32(&target), |expected, actual| {
+            PatchError::TargetChecksum { expected, actual }
+        })?;
+
+        Ok(target)
+    }
+}
+
+/// A BPS patch: the target built from copies of the source, of itself, and
+/// of data in the patch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bps<'p> {
+    source_size: u64,
+    target_size: u64,
+    metadata: &'p [u8],
+    actions: Vec<BpsAction<'p>>,
+    source_crc: u32,
+    target_crc: u32,
+    patch_crc: u32,
+    actual_patch_crc: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BpsAction<'p> {
+    SourceRead(u64),
+    TargetRead(&'p [u8]),
+    SourceCopy { len: u64, offset: i64 },
+    TargetCopy { len: u64, offset: i64 },
+}
+
+impl<'p> Bps<'p> {
+    pub fn source_size(&self) -> u64 {
+        self.source_size
+    }
+
+    pub fn target_size(&self) -> u64 {
+        self.target_size
+    }
+
+    pub fn source_crc(&self) -> u32 {
+        self.source_crc
+    }
+
+    pub fn target_crc(&self) -> u32 {
+        self.target_crc
+    }
+
+    /// Usually XML, but the format leaves it up to the patcher.
+    pub fn metadata(&self) -> &'p [u8] {
+        self.metadata
+    }
+
+    pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>, PatchError> {
+        check_crc(self.patch_crc, self.actual_patch_crc, |expected, actual| {
+            PatchError::PatchChecksum { expected, actual }
+        })?;
+        if source.len() as u64 != self.source_size {
+            return Err(PatchError::SourceSize {
+                expected: self.source_size,
+                actual: source.len(),
+            });
+        }
+        check_crc(self.source_crc, crc32(source), |expected, actual| {
+            PatchError::SourceChecksum { expected, actual }
+        })?;
+
+        let target_size = target_size(self.target_size)?;
+        let mut target = Vec::with_capacity(target_size);
+        let mut source_offset: i64 = 0;
+        let mut target_offset: i64 = 0;
+
+        for action in &self.actions {
+            let len = match *action {
+                BpsAction::SourceRead(len) |
+                BpsAction::SourceCopy { len, .. } |
+                BpsAction::TargetCopy { len, .. } => len,
+                BpsAction::TargetRead(data) => data.len() as u64,
+            };
+            if len > (target_size - target.len()) as u64 {
+                return Err(PatchError::OutOfBounds);
+            }
+            let len = len as usize;
+
+            match *action {
+                BpsAction::SourceRead(_) => {
+                    let start = target.len();
+                    let data = source.get(start..start + len).ok_or(PatchError::OutOfBounds)?;
+                    target.extend_from_slice(data);
+                }
+                BpsAction::TargetRead(data) => target.extend_from_slice(data),
+                BpsAction::SourceCopy { offset, .. } => {
+                    source_offset = source_offset.saturating_add(offset);
+                    if source_offset < 0 {
+                        return Err(PatchError::OutOfBounds);
+                    }
+                    let start = source_offset as usize;
+                    let data = source.get(start..start + len).ok_or(PatchError::OutOfBounds)?;
+                    target.extend_from_slice(data);
+                    source_offset += len as i64;
+                }
+                BpsAction::TargetCopy { offset, .. } => {
+                    target_offset = target_offset.saturating_add(offset);
+                    if target_offset < 0 || target_offset as usize >= target.len() {
+                        return Err(PatchError::OutOfBounds);
+                    }
+                    // Byte by byte, as the copy may overlap what it writes.
+                    for _ in 0..len {
+                        let byte = target[target_offset as usize];
+                        target.push(byte);
+                        target_offset += 1;
+                    }
+                }
+            }
+        }
+
+        check_crc(self.target_crc, crc32(&target), |expected, actual| {
+            PatchError::TargetChecksum { expected, actual }
+        })?;
+
+        Ok(target)
+    }
+}
+
+// The variable length numbers of UPS and BPS: 7 bits per byte, least
+// significant first, the last byte flagged by bit 7.
+fn varint(input: &[u8]) -> IResult<&[u8], u64> {
+    let mut value: u64 = 0;
+    let mut shift: u64 = 1;
+
+    for (i, &byte) in input.iter().enumerate() {
+        value = match (byte as u64 & 0x7f).checked_mul(shift).and_then(|x| value.checked_add(x)) {
+            Some(value) => value,
+            None => return IResult::Error(error_position!(ErrorKind::Custom(0), input)),
+        };
+        if byte & 0x80 != 0 {
+            return IResult::Done(&input[i + 1..], value);
+        }
+
+        shift = match shift.checked_mul(0x80) {
+            Some(shift) if shift <= 1 << 56 => shift,
+            _ => return IResult::Error(error_position!(ErrorKind::Custom(0), input)),
+        };
+        value += shift;
+    }
+
+    IResult::Incomplete(Needed::Unknown)
+}
+
+// BPS copy offsets: the sign in bit 0, the magnitude in the others.
+fn signed_varint(input: &[u8]) -> IResult<&[u8], i64> {
+    varint(input).map(|value| {
+        let magnitude = (value >> 1) as i64;
+        if value & 1 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        }
+    })
+}
+
+// The source, target and patch CRC-32s.
+type Footer = (u32, u32, u32);
+
+named!(parse_footer(&[u8]) -> Footer,
+    tuple!(le_u32, le_u32, le_u32)
+);
+
+// UPS and BPS end with a footer rather than a terminator, so the body is
+// parsed on its own and must be used up entirely.
+fn parse_with_footer<'p, T, F>(
+    data: &'p [u8],
+    parse_body: F,
+) -> IResult<&'p [u8], (T, Footer)>
+where
+    F: Fn(&'p [u8]) -> IResult<&'p [u8], T>,
+{
+    if data.len() < FOOTER_SIZE {
+        return IResult::Incomplete(Needed::Size(FOOTER_SIZE));
+    }
+    let (body, footer) = data.split_at(data.len() - FOOTER_SIZE);
+
+    let (_, crcs) = try_parse!(footer, parse_footer);
+    match parse_body(body) {
+        IResult::Done([], body) => IResult::Done(&[], (body, crcs)),
+        IResult::Error(e) => IResult::Error(e),
+        _ => IResult::Error(error_position!(ErrorKind::Eof, data)),
+    }
+}
+
+named!(parse_ips_record(&[u8]) -> IpsRecord<'_>,
+    do_parse!(
+        offset: be_u24 >>
+        size: be_u16 >>
+        data: cond_with_error!(size != 0, take!(size)) >>
+        rle: cond_with_error!(size == 0, pair!(be_u16, be_u8)) >>
+
+        (
+            match (data, rle) {
+                (Some(data), _) => IpsRecord::Data { offset, data },
+                (_, Some((len, value))) => IpsRecord::Rle { offset, len, value },
+                _ => unreachable!(),
+            }
+        )
+    )
+);
+
+named!(pub parse_ips(&[u8]) -> Ips<'_>,
+    do_parse!(
+        tag!(IPS_MAGIC) >>
+        records: many_till!(call!(parse_ips_record), tag!(&b"EOF"[..])) >>
+        truncate: opt!(complete!(be_u24)) >>
+
+        (
+            Ips {
+                records: records.0,
+                truncate,
+            }
+        )
+    )
+);
+
+named!(parse_ups_hunk(&[u8]) -> UpsHunk<'_>,
+    do_parse!(
+        skip: varint >>
+        xor: take_until_and_consume!(&[0x00][..]) >>
+
+        (
+            UpsHunk {
+                skip,
+                xor,
+            }
+        )
+    )
+);
+
+named!(parse_ups_body(&[u8]) -> (u64, u64, Vec<UpsHunk<'_>>),
+    do_parse!(
+        tag!(UPS_MAGIC) >>
+        source_size: varint >>
+        target_size: varint >>
+        hunks: many0!(parse_ups_hunk) >>
+
+        (source_size, target_size, hunks)
+    )
+);
+
+pub fn parse_ups(data: &[u8]) -> IResult<&[u8], Ups<'_>> {
+    parse_with_footer(data, parse_ups_body).map(|(body, crcs)| {
+        let (source_size, target_size, hunks) = body;
+        let (source_crc, target_crc, patch_crc) = crcs;
+
+        Ups {
+            source_size,
+            target_size,
+            hunks,
+            source_crc,
+            target_crc,
+            patch_crc,
+            actual_patch_crc: crc32(&data[..data.len() - 4]),
+        }
+    })
+}
+
+fn parse_bps_action(input: &[u8]) -> IResult<&[u8], BpsAction<'_>> {
+    let (input, data) = try_parse!(input, varint);
+    let len = (data >> 2) + 1;
+
+    match data & 0x03 {
+        0 => IResult::Done(input, BpsAction::SourceRead(len)),
+        1 => take!(input, len).map(BpsAction::TargetRead),
+        2 => signed_varint(input).map(|offset| BpsAction::SourceCopy { len, offset }),
+        _ => signed_varint(input).map(|offset| BpsAction::TargetCopy { len, offset }),
+    }
+}
+
+named!(parse_bps_body(&[u8]) -> (u64, u64, &[u8], Vec<BpsAction<'_>>),
+    do_parse!(
+        tag!(BPS_MAGIC) >>
+        source_size: varint >>
+        target_size: varint >>
+        metadata_size: varint >>
+        metadata: take!(metadata_size) >>
+        actions: many0!(parse_bps_action) >>
+
+        (source_size, target_size, metadata, actions)
+    )
+);
+
+pub fn parse_bps(data: &[u8]) -> IResult<&[u8], Bps<'_>> {
+    parse_with_footer(data, parse_bps_body).map(|(body, crcs)| {
+        let (source_size, target_size, metadata, actions) = body;
+        let (source_crc, target_crc, patch_crc) = crcs;
+
+        Bps {
+            source_size,
+            target_size,
+            metadata,
+            actions,
+            source_crc,
+            target_crc,
+            patch_crc,
+            actual_patch_crc: crc32(&data[..data.len() - 4]),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode_varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte | 0x80);
+                return bytes;
+            }
+            bytes.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn push_u32(data: &mut Vec<u8>, value: u32) {
+        data.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8,
+                                 (value >> 24) as u8]);
+    }
+
+    // Appends the source, target and patch CRC-32s.
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        push_u32(&mut patch, crc32(source));
+        push_u32(&mut patch, crc32(target));
+        let patch_crc = crc32(&patch);
+        push_u32(&mut patch, patch_crc);
+
+        patch
+    }
+
+    mod ips {
+        use super::*;
+
+        fn ips_patch() -> Vec<u8> {
+            let mut patch = b"PATCH".to_vec();
+            patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xaa, 0xbb]);
+            patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x03, 0xcc]);
+            patch.extend_from_slice(b"EOF");
+
+            patch
+        }
+
+        #[test]
+        fn parse() {
+            let patch = ips_patch();
+            let (_, ips) = parse_ips(&patch).unwrap();
+
+            assert_eq!(
+                ips.records(),
+                &[
+                    IpsRecord::Data { offset: 1, data: &[0xaa, 0xbb] },
+                    IpsRecord::Rle { offset: 6, len: 3, value: 0xcc },
+                ][..]
+            );
+            assert_eq!(ips.truncate(), None);
+        }
+
+        #[test]
+        fn apply_grows_source() {
+            let patch = ips_patch();
+            let (_, ips) = parse_ips(&patch).unwrap();
+
+            assert_eq!(
+                ips.apply(&[0x00; 4]),
+                vec![0x00, 0xaa, 0xbb, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xcc]
+            );
+        }
+
+        #[test]
+        fn truncation() {
+            let mut patch = ips_patch();
+            patch.extend_from_slice(&[0x00, 0x00, 0x02]);
+            let (_, ips) = parse_ips(&patch).unwrap();
+
+            assert_eq!(ips.truncate(), Some(2));
+            assert_eq!(ips.apply(&[0x11; 16]), vec![0x11, 0xaa]);
+        }
+
+        #[test]
+        fn missing_eof() {
+            let mut patch = ips_patch();
+            patch.truncate(patch.len() - 3);
+
+            assert_eq!(Patch::from_bytes(&patch), Err(PatchError::Invalid));
+        }
+    }
+
+    mod ups {
+        use super::*;
+
+        fn ups_patch(source: &[u8], target: &[u8]) -> Vec<u8> {
+            let mut patch = b"UPS1".to_vec();
+            patch.extend(encode_varint(source.len() as u64));
+            patch.extend(encode_varint(target.len() as u64));
+
+            let mut offset = 0;
+            let mut i = 0;
+            while i < target.len() {
+                let byte = |i: usize| source.get(i).cloned().unwrap_or(0) ^ target[i];
+                if byte(i) == 0 {
+                    i += 1;
+                    continue;
+                }
+                patch.extend(encode_varint((i - offset) as u64));
+                while i < target.len() && byte(i) != 0 {
+                    patch.push(byte(i));
+                    i += 1;
+                }
+                patch.push(0x00);
+                i += 1;
+                offset = i;
+            }
+
+            with_footer(patch, source, target)
+        }
+
+        #[test]
+        fn apply() {
+            let source = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+            let target = [0x01, 0xff, 0xfe, 0x04, 0x05, 0x60, 0x70, 0x80];
+            let patch = ups_patch(&source, &target);
+
+            let patch = Patch::from_bytes(&patch).unwrap();
+
+            assert_eq!(patch.apply(&source), Ok(target.to_vec()));
+        }
+
+        #[test]
+        fn apply_shrinks_source() {
+            let source = [0x01, 0x02, 0x03, 0x04];
+            let target = [0x01, 0x20];
+            let patch = ups_patch(&source, &target);
+
+            assert_eq!(Patch::from_bytes(&patch).unwrap().apply(&source), Ok(target.to_vec()));
+        }
+
+        #[test]
+        fn wrong_source() {
+            let source = [0x01, 0x02, 0x03, 0x04];
+            let patch = ups_patch(&source, &[0x01, 0x20, 0x03, 0x04]);
+            let patch = Patch::from_bytes(&patch).unwrap();
+
+            assert_eq!(
+                patch.apply(&[0x01, 0x02, 0x03]),
+                Err(PatchError::SourceSize { expected: 4, actual: 3 })
+            );
+            assert_eq!(
+                patch.apply(&[0x01, 0x02, 0x03, 0x05]),
+                Err(PatchError::SourceChecksum {
+                    expected: crc32(&source),
+                    actual: crc32(&[0x01, 0x02, 0x03, 0x05]),
+                })
+            );
+        }
+
+        #[test]
+        fn corrupt_patch() {
+            let source = [0x01, 0x02, 0x03, 0x04];
+            let mut patch = ups_patch(&source, &[0x01, 0x20, 0x03, 0x04]);
+            patch[7] ^= 0x01;
+
+            match Patch::from_bytes(&patch).unwrap().apply(&source) {
+                Err(PatchError::PatchChecksum { .. }) => {}
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn offset_overflow() {
+            let source = [0x01, 0x02, 0x03, 0x04];
+            let mut patch = b"UPS1".to_vec();
+            patch.extend(encode_varint(4));
+            patch.extend(encode_varint(4));
+            for _ in 0..4 {
+                patch.extend(encode_varint(1 << 62));
+                patch.extend_from_slice(&[0x01, 0x00]);
+            }
+            let patch = with_footer(patch, &source, &source);
+
+            assert_eq!(
+                Patch::from_bytes(&patch).unwrap().apply(&source),
+                Err(PatchError::OutOfBounds)
+            );
+        }
+
+        #[test]
+        fn truncated() {
+            assert!(parse_ups(b"UPS1").is_incomplete());
+        }
+    }
+
+    mod bps {
+        use super::*;
+
+        fn bps_patch(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+            let mut patch = b"BPS1".to_vec();
+            patch.extend(encode_varint(source.len() as u64));
+            patch.extend(encode_varint(target.len() as u64));
+            patch.extend(encode_varint(3));
+            patch.extend_from_slice(b"xml");
+            patch.extend_from_slice(actions);
+
+            with_footer(patch, source, target)
+        }
+
+        fn action(command: u64, len: u64) -> Vec<u8> {
+            encode_varint((len - 1) << 2 | command)
+        }
+
+        fn offset(offset: i64) -> Vec<u8> {
+            encode_varint((offset.unsigned_abs()) << 1 | (offset < 0) as u64)
+        }
+
+        #[test]
+        fn apply() {
+            let source = b"abcdefgh";
+            let target = b"abcXYhhhhab";
+            let mut actions = action(0, 3);
+            actions.extend(action(1, 2));
+            actions.extend_from_slice(b"XY");
+            actions.extend(action(2, 1));
+            actions.extend(offset(7));
+            actions.extend(action(3, 3));
+            actions.extend(offset(5));
+            actions.extend(action(2, 2));
+            actions.extend(offset(-8));
+            let patch = bps_patch(source, target, &actions);
+
+            let patch = Patch::from_bytes(&patch).unwrap();
+
+            match patch {
+                Patch::Bps(ref bps) => assert_eq!(bps.metadata(), b"xml"),
+                _ => panic!("not a BPS patch"),
+            }
+            assert_eq!(patch.apply(source), Ok(target.to_vec()));
+        }
+
+        #[test]
+        fn wrong_target() {
+            let source = b"abcd";
+            let patch = bps_patch(source, b"abce", &action(0, 4));
+
+            match Patch::from_bytes(&patch).unwrap().apply(source) {
+                Err(PatchError::TargetChecksum { .. }) => {}
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn out_of_bounds() {
+            let source = b"abcd";
+            let mut actions = action(2, 2);
+            actions.extend(offset(3));
+            let patch = bps_patch(source, b"dx", &actions);
+
+            assert_eq!(
+                Patch::from_bytes(&patch).unwrap().apply(source),
+                Err(PatchError::OutOfBounds)
+            );
+        }
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for &value in &[0, 1, 0x7f, 0x80, 0x407f, 0x4080, 0xdead_beef] {
+            assert_eq!(varint(&encode_varint(value)), IResult::Done(&[][..], value));
+        }
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert_eq!(Patch::from_bytes(b"NES\x1a"), Err(PatchError::Invalid));
+    }
+}
@@ -0,0 +1,421 @@
+use std::str;
+
+use nom::le_u32;
+
+use super::nes2::rom_size_fields;
+use super::{Flag10, Flag6, Flag7, Flag9, Header, Mirroring, Nes2Header, Timing, TvSystem};
+use super::{CHR_ROM_BANK_SIZE, PRG_RAM_BANK_SIZE, PRG_ROM_BANK_SIZE};
+
+#[derive(Debug, PartialEq)]
+pub struct Unif<'n> {
+    revision: u32,
+    chunks: Vec<Chunk<'n>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chunk<'n> {
+    id: &'n [u8],
+    data: &'n [u8],
+}
+
+/// Mapper numbers of UNIF boards, without their "NES-", "HVC-" or "UNL-"
+/// prefix.
+const BOARDS: &[(&str, u16)] = &[
+    ("NROM", 0),
+    ("NROM-128", 0),
+    ("NROM-256", 0),
+    ("RROM", 0),
+    ("SAROM", 1),
+    ("SBROM", 1),
+    ("SCROM", 1),
+    ("SEROM", 1),
+    ("SFROM", 1),
+    ("SGROM", 1),
+    ("SHROM", 1),
+    ("SJROM", 1),
+    ("SKROM", 1),
+    ("SLROM", 1),
+    ("SL1ROM", 1),
+    ("SNROM", 1),
+    ("SOROM", 1),
+    ("SUROM", 1),
+    ("SXROM", 1),
+    ("UNROM", 2),
+    ("UOROM", 2),
+    ("CNROM", 3),
+    ("TBROM", 4),
+    ("TEROM", 4),
+    ("TFROM", 4),
+    ("TGROM", 4),
+    ("TKROM", 4),
+    ("TLROM", 4),
+    ("TL1ROM", 4),
+    ("TR1ROM", 4),
+    ("TSROM", 4),
+    ("TVROM", 4),
+    ("ELROM", 5),
+    ("EKROM", 5),
+    ("ETROM", 5),
+    ("EWROM", 5),
+    ("AMROM", 7),
+    ("ANROM", 7),
+    ("AOROM", 7),
+    ("PNROM", 9),
+    ("PEEOROM", 9),
+    ("FJROM", 10),
+    ("FKROM", 10),
+    ("CPROM", 13),
+    ("BNROM", 34),
+    ("GNROM", 66),
+    ("MHROM", 66),
+    ("TQROM", 119),
+    ("CC-21", 27),
+    ("D1038", 59),
+    ("H2288", 123),
+    ("LH32", 125),
+    ("SA-NROM", 143),
+    ("Sachen-8259D", 137),
+    ("Sachen-8259B", 138),
+    ("Sachen-8259C", 139),
+    ("Sachen-8259A", 141),
+    ("KS7032", 142),
+    ("SA-72007", 145),
+    ("SA-016-1M", 146),
+    ("TC-U01-1.5M", 147),
+    ("SA-0037", 148),
+    ("SA-0036", 149),
+    ("70in1", 236),
+    ("603-5052", 238),
+    ("SHERO", 262),
+    ("KOF97", 263),
+    ("GS-2004", 283),
+    ("190in1", 300),
+    ("SMB2J", 304),
+    ("EDU2000", 329),
+    ("DREAMTECH01", 521),
+    ("T-230", 529),
+    ("AX5705", 530),
+];
+
+const BOARD_PREFIXES: &[&str] = &["NES-", "HVC-", "UNL-", "BMC-", "BTL-"];
+
+/// Mapper number of a UNIF board name such as "NES-SNROM".
+pub fn board_mapper(board: &str) -> Option<u16> {
+    let name = BOARD_PREFIXES
+        .iter()
+        .find(|prefix| board.starts_with(*prefix))
+        .map_or(board, |prefix| &board[prefix.len()..]);
+
+    BOARDS
+        .iter()
+        .find(|&&(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|&(_, mapper)| mapper)
+}
+
+// UNIF strings are zero terminated, but not every dumper wrote the zero.
+fn null_terminated(data: &[u8]) -> &[u8] {
+    let end = data.iter().position(|&byte| byte == 0x00).unwrap_or(data.len());
+
+    &data[..end]
+}
+
+impl<'n> Chunk<'n> {
+    /// Four ASCII characters such as "MAPR" or "PRG0".
+    pub fn id(&self) -> &'n [u8] {
+        self.id
+    }
+
+    pub fn data(&self) -> &'n [u8] {
+        self.data
+    }
+}
+
+impl<'n> Unif<'n> {
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    pub fn chunks(&self) -> &[Chunk<'n>] {
+        &self.chunks
+    }
+
+    /// Data of the first chunk with the given id.
+    pub fn chunk(&self, id: &[u8]) -> Option<&'n [u8]> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.id == id)
+            .map(|chunk| chunk.data)
+    }
+
+    /// Board name from the MAPR chunk, e.g. "NES-SNROM".
+    pub fn board_name(&self) -> Option<&'n str> {
+        self.chunk(b"MAPR")
+            .and_then(|data| str::from_utf8(null_terminated(data)).ok())
+    }
+
+    pub fn mapper_number(&self) -> Option<u16> {
+        self.board_name().and_then(board_mapper)
+    }
+
+    /// Game title from the NAME chunk.
+    pub fn name(&self) -> Option<&'n str> {
+        self.chunk(b"NAME")
+            .and_then(|data| str::from_utf8(null_terminated(data)).ok())
+    }
+
+    /// Hardwired mirroring from the MIRR chunk. One-screen and mapper
+    /// controlled mirroring are left to the mapper, and read as horizontal.
+    pub fn mirroring(&self) -> Mirroring {
+        match self.chunk(b"MIRR").and_then(|data| data.first()) {
+            Some(&1) => Mirroring::Vertical,
+            Some(&4) => Mirroring::FourScreen,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.chunk(b"BATR")
+            .map_or(false, |data| data.first().map_or(true, |&byte| byte != 0))
+    }
+
+    pub fn tv_system(&self) -> TvSystem {
+        match self.chunk(b"TVCI").and_then(|data| data.first()) {
+            Some(&1) => TvSystem::PAL,
+            _ => TvSystem::NTSC,
+        }
+    }
+
+    /// PRG0 to PRGF concatenated in that order.
+    pub fn prg_rom(&self) -> Vec<u8> {
+        self.rom(b'P', b'R', b'G')
+    }
+
+    /// CHR0 to CHRF concatenated in that order, empty with CHR RAM.
+    pub fn chr_rom(&self) -> Vec<u8> {
+        self.rom(b'C', b'H', b'R')
+    }
+
+    fn rom(&self, a: u8, b: u8, c: u8) -> Vec<u8> {
+        let mut rom = Vec::new();
+
+        for digit in b"0123456789ABCDEF" {
+            if let Some(data) = self.chunk(&[a, b, c, *digit]) {
+                rom.extend_from_slice(data);
+            }
+        }
+
+        rom
+    }
+
+    /// An NES 2.0 header describing the same cartridge, `None` if the ROM
+    /// sizes cannot be expressed in one.
+    pub(super) fn to_header(
+        &self,
+        mapper: u16,
+        prg_rom_bytes: usize,
+        chr_rom_bytes: usize,
+    ) -> Option<Header> {
+        let (prg_lsb, prg_msb) = rom_size_fields(prg_rom_bytes, PRG_ROM_BANK_SIZE)?;
+        let (chr_lsb, chr_msb) = rom_size_fields(chr_rom_bytes, CHR_ROM_BANK_SIZE)?;
+
+        let mirroring = self.mirroring();
+        let tv_system = self.tv_system();
+        let timing = match self.chunk(b"TVCI").and_then(|data| data.first()) {
+            Some(&1) => Timing::Pal,
+            Some(&2) => Timing::MultipleRegion,
+            _ => Timing::Ntsc,
+        };
+        let chr_ram_bytes = if chr_rom_bytes == 0 {
+            CHR_ROM_BANK_SIZE
+        } else {
+            0
+        };
+
+        let mut nes2 = Nes2Header::from_ines(
+            PRG_RAM_BANK_SIZE,
+            self.has_battery(),
+            chr_ram_bytes,
+            timing,
+            0,
+        );
+        nes2.set_rom_size_msb(prg_msb, chr_msb);
+
+        let mut header = Header {
+            prg_rom_size: prg_lsb,
+            chr_rom_size: chr_lsb,
+            flag6: Flag6 {
+                mirroring: if mirroring == Mirroring::Vertical {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                },
+                contains_battery_backed_prg_ram: self.has_battery(),
+                has_trainer: false,
+                igore_mirroring_control: mirroring == Mirroring::FourScreen,
+                lower_nybble_of_mapper_number: 0,
+            },
+            flag7: Flag7 {
+                vs_unisystem: false,
+                has_play_choice_10: false,
+                is_nes_2_0: true,
+                upper_nybble_of_mapper_numbe: 0,
+            },
+            prg_ram_size: 0,
+            flag9: Flag9 { tv_system },
+            flag10: Flag10::default(),
+            nes2: Some(nes2),
+        };
+        header.set_mapper_number(mapper);
+
+        Some(header)
+    }
+}
+
+named!(pub parse_unif(&[u8]) -> Unif<'_>,
+    do_parse!(
+        tag!(&b"UNIF"[..]) >>
+        revision: le_u32 >>
+        take!(24) >>
+        chunks: many0!(parse_chunk) >>
+
+        (
+            Unif {
+                revision,
+                chunks,
+            }
+        )
+    )
+);
+
+named!(parse_chunk(&[u8]) -> Chunk<'_>,
+    do_parse!(
+        id: take!(4) >>
+        data: length_bytes!(le_u32) >>
+
+        (
+            Chunk {
+                id,
+                data,
+            }
+        )
+    )
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unif_image(chunks: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut image = b"UNIF".to_vec();
+        image.extend_from_slice(&[7, 0, 0, 0]);
+        image.extend_from_slice(&[0x00; 24]);
+
+        for &(id, data) in chunks {
+            let len = data.len();
+            image.extend_from_slice(id);
+            image.extend_from_slice(&[len as u8, (len >> 8) as u8, (len >> 16) as u8, 0]);
+            image.extend_from_slice(data);
+        }
+
+        image
+    }
+
+    #[test]
+    fn should_be_parsed_correctly() {
+        let image = unif_image(&[(b"MAPR", b"NES-SNROM\0"), (b"PRG0", &[0xaa; 4])]);
+
+        let (rest, unif) = parse_unif(&image).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(unif.revision(), 7);
+        assert_eq!(unif.chunks().len(), 2);
+        assert_eq!(unif.chunks()[1].id(), b"PRG0");
+        assert_eq!(unif.board_name(), Some("NES-SNROM"));
+        assert_eq!(unif.mapper_number(), Some(1));
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        let mut image = unif_image(&[(b"PRG0", &[0xaa; 16])]);
+        image.truncate(image.len() - 1);
+
+        assert!(parse_unif(&image).is_incomplete());
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut image = unif_image(&[]);
+        image[0] = b'N';
+
+        assert!(parse_unif(&image).is_err());
+    }
+
+    #[test]
+    fn rom_chunks_are_ordered() {
+        let image = unif_image(&[
+            (b"PRG1", &[0x02, 0x02]),
+            (b"CHR0", &[0x10]),
+            (b"PRG0", &[0x01]),
+            (b"PRGA", &[0x0a]),
+        ]);
+        let (_, unif) = parse_unif(&image).unwrap();
+
+        assert_eq!(unif.prg_rom(), vec![0x01, 0x02, 0x02, 0x0a]);
+        assert_eq!(unif.chr_rom(), vec![0x10]);
+    }
+
+    #[test]
+    fn flags() {
+        let image = unif_image(&[(b"MIRR", &[1]), (b"BATR", &[1]), (b"TVCI", &[1])]);
+        let (_, unif) = parse_unif(&image).unwrap();
+
+        assert_eq!(unif.mirroring(), Mirroring::Vertical);
+        assert!(unif.has_battery());
+        assert_eq!(unif.tv_system(), TvSystem::PAL);
+    }
+
+    #[test]
+    fn default_flags() {
+        let image = unif_image(&[(b"MIRR", &[5])]);
+        let (_, unif) = parse_unif(&image).unwrap();
+
+        assert_eq!(unif.mirroring(), Mirroring::Horizontal);
+        assert!(!unif.has_battery());
+        assert_eq!(unif.tv_system(), TvSystem::NTSC);
+    }
+
+    #[test]
+    fn board_names() {
+        assert_eq!(board_mapper("NES-NROM-256"), Some(0));
+        assert_eq!(board_mapper("HVC-TLROM"), Some(4));
+        assert_eq!(board_mapper("UNL-Sachen-8259A"), Some(141));
+        assert_eq!(board_mapper("BMC-70in1"), Some(236));
+        assert_eq!(board_mapper("NES-UxROM"), None);
+    }
+
+    #[test]
+    fn to_header() {
+        let image = unif_image(&[(b"MIRR", &[4]), (b"BATR", &[1])]);
+        let (_, unif) = parse_unif(&image).unwrap();
+
+        let header = unif.to_header(0x101, 2 * 16384, 0).unwrap();
+
+        assert_eq!(header.mapper_number(), 0x101);
+        assert_eq!(header.mirroring(), Mirroring::FourScreen);
+        assert_eq!(header.prg_rom_bytes(), 2 * 16384);
+        assert_eq!(header.chr_rom_bytes(), 0);
+        assert_eq!(header.chr_ram_bytes(), 8192);
+        assert_eq!(header.prg_nvram_bytes(), 8192);
+    }
+
+    #[test]
+    fn to_header_with_odd_prg_size() {
+        let image = unif_image(&[]);
+        let (_, unif) = parse_unif(&image).unwrap();
+
+        let header = unif.to_header(0, 8192, 8192).unwrap();
+
+        assert_eq!(header.prg_rom_bytes(), 8192);
+        assert_eq!(header.chr_rom_bytes(), 8192);
+    }
+}
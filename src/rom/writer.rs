@@ -0,0 +1,285 @@
+use super::{Flag10, Header, INes, Mirroring, Nes2Header, Timing, TvSystem};
+
+// Byte 7 bits 2-3 of a NES 2.0 header.
+const NES_2_0_IDENTIFIER: u8 = 0b0000_1000;
+
+// The discrete boards whose submapper 2 means bus conflicts: UxROM, CNROM,
+// AxROM and BNROM.
+const BUS_CONFLICT_MAPPERS: [u16; 4] = [2, 3, 7, 34];
+
+impl Header {
+    /// The 16 bytes of the header as they appear in the file.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..4].copy_from_slice(&[b'N', b'E', b'S', 0x1a]);
+        bytes[4] = self.prg_rom_size;
+        bytes[5] = self.chr_rom_size;
+
+        let flag6 = &self.flag6;
+        bytes[6] = (flag6.lower_nybble_of_mapper_number << 4) |
+            ((flag6.igore_mirroring_control as u8) << 3) |
+            ((flag6.has_trainer as u8) << 2) |
+            ((flag6.contains_battery_backed_prg_ram as u8) << 1) |
+            (flag6.mirroring == Mirroring::Vertical) as u8;
+
+        let flag7 = &self.flag7;
+        bytes[7] = flag7.upper_nybble_of_mapper_numbe | ((flag7.has_play_choice_10 as u8) << 1) |
+            flag7.vs_unisystem as u8;
+
+        match self.nes2 {
+            Some(ref nes2) => {
+                bytes[7] |= NES_2_0_IDENTIFIER;
+                bytes[8..].copy_from_slice(&nes2.to_bytes());
+            }
+            None => {
+                bytes[8] = self.prg_ram_size;
+                bytes[9] = (self.flag9.tv_system == TvSystem::PAL) as u8;
+                let flag10 = &self.flag10;
+                bytes[10] = ((flag10.has_bus_conflicts as u8) << 5) |
+                    ((!flag10.has_prg_ram as u8) << 4) | flag10.tv_system_bits;
+            }
+        }
+
+        bytes
+    }
+
+    /// Switches to the NES 2.0 format, keeping everything an iNES header
+    /// can express. Does nothing if the header already is NES 2.0.
+    pub fn upgrade_to_nes2(&mut self) {
+        if self.nes2.is_some() {
+            return;
+        }
+
+        let timing = match (self.flag10.tv_system(), self.flag9.tv_system) {
+            (Timing::MultipleRegion, _) => Timing::MultipleRegion,
+            (_, TvSystem::NTSC) => Timing::Ntsc,
+            (_, TvSystem::PAL) => Timing::Pal,
+        };
+
+        let mut nes2 = Nes2Header::from_ines(
+            self.prg_ram_bytes(),
+            self.has_battery(),
+            self.chr_ram_bytes(),
+            timing,
+            0,
+        );
+        if self.flag10.has_bus_conflicts && BUS_CONFLICT_MAPPERS.contains(&self.mapper_number()) {
+            nes2.set_submapper(2);
+        }
+
+        self.nes2 = Some(nes2);
+        self.flag7.is_nes_2_0 = true;
+        self.prg_ram_size = 0;
+        self.flag10 = Flag10::default();
+    }
+}
+
+pub(super) fn write_image(
+    header: &Header,
+    trainer: Option<&[u8]>,
+    prg_rom: &[u8],
+    chr_rom: &[u8],
+    play_choice_inst_rom: Option<&[u8]>,
+    play_choice_prom: Option<&[u8]>,
+    misc_rom: Option<&[u8]>,
+) -> Vec<u8> {
+    let mut image = header.to_bytes().to_vec();
+
+    let sections = [
+        trainer,
+        Some(prg_rom),
+        Some(chr_rom),
+        play_choice_inst_rom,
+        play_choice_prom,
+        misc_rom,
+    ];
+    for section in sections.iter().filter_map(|&section| section) {
+        image.extend_from_slice(section);
+    }
+
+    image
+}
+
+impl<'n> INes<'n> {
+    /// Serializes the image back into the iNES or NES 2.0 file format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        write_image(
+            &self.header,
+            self.trainer,
+            self.prg_rom_data,
+            self.chr_rom(),
+            self.play_choice_inst_rom,
+            self.play_choice_prom,
+            self.misc_rom_data,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::parse_ines;
+
+    fn ines_image(flag6: u8, flag7: u8, tail: [u8; 8], data_size: usize) -> Vec<u8> {
+        let mut image = vec![b'N', b'E', b'S', 0x1a, 0x01, 0x01, flag6, flag7];
+        image.extend_from_slice(&tail);
+        image.extend((0..data_size).map(|i| i as u8));
+
+        image
+    }
+
+    fn assert_round_trip(image: &[u8]) {
+        let (_, ines) = parse_ines(image).unwrap();
+
+        assert_eq!(ines.to_bytes(), image);
+    }
+
+    #[test]
+    fn ines_round_trip() {
+        assert_round_trip(&ines_image(
+            0b0001_0011,
+            0b0100_0000,
+            [2, 1, 0x10, 0, 0, 0, 0, 0],
+            16384 + 8192,
+        ));
+    }
+
+    #[test]
+    fn four_screen_round_trip() {
+        assert_round_trip(&ines_image(0b0000_1000, 0, [0; 8], 16384 + 8192));
+    }
+
+    #[test]
+    fn trainer_round_trip() {
+        assert_round_trip(&ines_image(0b0000_0100, 0, [0; 8], 512 + 16384 + 8192));
+    }
+
+    #[test]
+    fn play_choice_round_trip() {
+        assert_round_trip(&ines_image(0, 0b0000_0010, [0; 8], 16384 + 8192 + 8192));
+    }
+
+    #[test]
+    fn play_choice_prom_round_trip() {
+        assert_round_trip(&ines_image(0, 0b0000_0010, [0; 8], 16384 + 8192 + 8192 + 32));
+    }
+
+    #[test]
+    fn flag10_round_trip() {
+        assert_round_trip(&ines_image(0, 0, [0, 0, 0b0011_0010, 0, 0, 0, 0, 0], 16384 + 8192));
+    }
+
+    #[test]
+    fn flag10_dual_compatible_round_trip() {
+        assert_round_trip(&ines_image(0, 0, [0, 0, 0b0000_0011, 0, 0, 0, 0, 0], 16384 + 8192));
+    }
+
+    #[test]
+    fn nes2_round_trip() {
+        assert_round_trip(&ines_image(
+            0b0100_0001,
+            0b0001_1001,
+            [0x21, 0, 0x70, 0x07, 0x01, 0x15, 0, 0x01],
+            16384 + 8192,
+        ));
+    }
+
+    #[test]
+    fn upgrade_to_nes2() {
+        let image = ines_image(0b0001_0010, 0, [2, 1, 0, 0, 0, 0, 0, 0], 16384 + 8192);
+        let (_, ines) = parse_ines(&image).unwrap();
+        let mut header = ines.header().clone();
+
+        header.upgrade_to_nes2();
+
+        assert!(header.flag7().is_nes_2_0());
+        assert_eq!(header.mapper_number(), 1);
+        assert_eq!(header.submapper(), Some(0));
+        assert_eq!(header.prg_ram_bytes(), 0);
+        assert_eq!(header.prg_nvram_bytes(), 2 * 8192);
+        assert_eq!(header.timing(), Timing::Pal);
+        assert_eq!(header.tv_system(), TvSystem::PAL);
+        assert_eq!(
+            header.to_bytes()[6..],
+            [0b0001_0010, 0b0000_1000, 0, 0, 0x80, 0, 1, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn upgrade_keeps_flag10() {
+        // UxROM with bus conflicts, dual compatible.
+        let image = ines_image(0b0010_0000, 0, [0, 0, 0b0010_0011, 0, 0, 0, 0, 0], 16384 + 8192);
+        let (_, ines) = parse_ines(&image).unwrap();
+        let mut header = ines.header().clone();
+
+        header.upgrade_to_nes2();
+        let mut upgraded = header.to_bytes().to_vec();
+        upgraded.extend_from_slice(&image[16..]);
+        let (_, ines) = parse_ines(&upgraded).unwrap();
+
+        assert_eq!(ines.header().submapper(), Some(2));
+        assert_eq!(ines.header().timing(), Timing::MultipleRegion);
+    }
+
+    #[test]
+    fn upgrade_keeps_chr_ram() {
+        let mut image = ines_image(0, 0, [0; 8], 16384);
+        image[5] = 0;
+        let (_, ines) = parse_ines(&image).unwrap();
+        let mut header = ines.header().clone();
+
+        header.upgrade_to_nes2();
+
+        assert_eq!(header.chr_ram_bytes(), 8192);
+        assert_eq!(header.prg_ram_bytes(), 8192);
+    }
+
+    #[test]
+    fn set_fields() {
+        let image = ines_image(0, 0, [0; 8], 16384 + 8192);
+        let (_, ines) = parse_ines(&image).unwrap();
+        let mut header = ines.header().clone();
+
+        header.set_mapper_number(0x42);
+        header.set_mirroring(Mirroring::FourScreen);
+        header.set_battery(true);
+
+        assert_eq!(header.to_bytes()[6..8], [0b0010_1010, 0b0100_0000]);
+        assert_eq!(header.nes2(), None);
+
+        header.set_mirroring(Mirroring::Vertical);
+
+        assert_eq!(header.to_bytes()[6], 0b0010_0011);
+    }
+
+    #[test]
+    fn large_mapper_number_upgrades() {
+        let image = ines_image(0, 0, [0; 8], 16384 + 8192);
+        let (_, ines) = parse_ines(&image).unwrap();
+        let mut header = ines.header().clone();
+
+        header.set_mapper_number(0x123);
+
+        assert!(header.nes2().is_some());
+        assert_eq!(header.mapper_number(), 0x123);
+        assert_eq!(header.to_bytes()[8], 0x01);
+    }
+
+    #[test]
+    fn set_battery_moves_nes2_prg_ram() {
+        let image = ines_image(0, 0, [0; 8], 16384 + 8192);
+        let (_, ines) = parse_ines(&image).unwrap();
+        let mut header = ines.header().clone();
+        header.upgrade_to_nes2();
+
+        header.set_battery(true);
+
+        assert_eq!(header.prg_ram_bytes(), 0);
+        assert_eq!(header.prg_nvram_bytes(), 8192);
+
+        header.set_battery(false);
+
+        assert_eq!(header.prg_ram_bytes(), 8192);
+        assert_eq!(header.prg_nvram_bytes(), 0);
+    }
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "alloc")]
+
+extern crate rustnes;
+
+use rustnes::cpu::{Flag, RamInit, Register, SubroutineCall, RESET_VECTOR};
+use rustnes::{Bus, Memory, RP2A03};
+
+/// 2 KB of RAM mirrored up to $1FFF and a 32 KB ROM at $8000, the bare
+/// minimum of a console.
+struct MirroredBus {
+    ram: [u8; 0x800],
+    rom: Vec<u8>,
+}
+
+impl MirroredBus {
+    fn new(rom: Vec<u8>) -> MirroredBus {
+        MirroredBus {
+            ram: [0x00; 0x800],
+            rom,
+        }
+    }
+}
+
+impl Bus for MirroredBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        if addr < 0x2000 {
+            self.ram[addr as usize & 0x7ff] = val;
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1fff => self.ram[addr as usize & 0x7ff],
+            0x8000..=0xffff => self.rom[(addr - 0x8000) as usize],
+            _ => 0x00,
+        }
+    }
+
+    fn init_ram(&mut self, ram_init: &RamInit) {
+        ram_init.fill(&mut self.ram);
+    }
+}
+
+#[test]
+fn power_on_with_custom_bus() {
+    let mut rom = vec![0xea; 0x8000];
+    rom[(RESET_VECTOR - 0x8000) as usize] = 0x00;
+    rom[(RESET_VECTOR - 0x8000) as usize + 1] = 0x80;
+
+    let mut cpu = RP2A03::with_bus(MirroredBus::new(rom));
+    cpu.power_on(&RamInit::Filled);
+
+    assert_eq!(cpu.registers.pc, 0x8000);
+    assert_eq!(cpu.registers.sp, 0xfd);
+    assert_eq!(cpu.memory.peek(0x1800), 0xff);
+
+    cpu.execute();
+
+    assert_eq!(cpu.registers.pc, 0x8001);
+}
+
+#[test]
+fn call_routine_through_custom_bus() {
+    // LDA $0802 (mirror of $0002); RTS
+    let mut rom = vec![0x00; 0x8000];
+    rom[..4].copy_from_slice(&[0xad, 0x02, 0x08, 0x60]);
+
+    let mut cpu = RP2A03::with_bus(MirroredBus::new(rom));
+    cpu.power_on(&RamInit::Zeroed);
+
+    let call = SubroutineCall {
+        memory: vec![(0x0002, 0x42)],
+        ..SubroutineCall::new(0x8000)
+    };
+    let result = cpu.call(&call).unwrap();
+
+    assert_eq!(result.registers.a, 0x42);
+    assert_eq!(result.cycles, 4 + 6);
+}
+
+#[test]
+fn snapshot_diff_of_one_instruction() {
+    let mut cpu = RP2A03::new();
+    let mut memory = Memory::new();
+    // LDA #$80
+    memory.write(0x0000, 0xa9);
+    memory.write(0x0001, 0x80);
+    cpu.memory = memory;
+
+    let before = cpu.snapshot();
+    cpu.execute();
+    let diff = before.diff(&cpu.snapshot());
+
+    assert_eq!(diff.changed_registers(), vec![Register::A, Register::Pc]);
+    assert_eq!(diff.changed_flags(), vec![Flag::Sign]);
+    assert!(diff.memory.is_empty());
+}
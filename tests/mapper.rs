@@ -0,0 +1,166 @@
+#![cfg(feature = "std")]
+
+extern crate rustnes;
+
+use rustnes::mapper::{self, CartridgeBus, Mirroring};
+use rustnes::rom::Cartridge;
+use rustnes::{Bus, RP2A03};
+
+/// PRG ROM banks filled with their number, and `chr` as CHR ROM, in 8 KB
+/// banks.
+fn ines_image(mapper: u8, prg_banks: u8, chr: &[u8]) -> Vec<u8> {
+    let chr_banks = (chr.len() / 8192) as u8;
+    let mut image = vec![b'N', b'E', b'S', 0x1a, prg_banks, chr_banks, mapper << 4, mapper & 0xf0];
+    image.extend_from_slice(&[0x00; 8]);
+    for bank in 0..prg_banks {
+        image.extend(vec![bank; 16384]);
+    }
+    image.extend_from_slice(chr);
+
+    image
+}
+
+#[test]
+fn nrom_from_cartridge() {
+    let cartridge = Cartridge::from_bytes(&ines_image(0, 1, &[0x55; 8192])).unwrap();
+
+    let mut nrom = mapper::from_cartridge(&cartridge).unwrap();
+
+    assert_eq!(nrom.cpu_read(0xfffc), 0x00);
+    assert_eq!(nrom.ppu_read(0x0000), 0x55);
+    assert_eq!(nrom.mirroring(), Mirroring::Horizontal);
+    assert!(!nrom.irq());
+}
+
+#[test]
+fn supported_mappers_from_cartridge() {
+    for &mapper in &[1, 2, 3, 4, 5, 7, 9, 10, 11, 21, 22, 23, 24, 25, 26, 34, 66] {
+        let cartridge = Cartridge::from_bytes(&ines_image(mapper, 2, &[0x55; 8192])).unwrap();
+
+        assert!(mapper::from_cartridge(&cartridge).is_ok(), "mapper {}", mapper);
+    }
+}
+
+#[test]
+fn uxrom_switches_banks() {
+    let cartridge = Cartridge::from_bytes(&ines_image(2, 4, &[])).unwrap();
+    let mut uxrom = mapper::from_cartridge(&cartridge).unwrap();
+
+    uxrom.cpu_write(0x8000, 2);
+
+    assert_eq!(uxrom.cpu_read(0x8000), 2);
+    assert_eq!(uxrom.cpu_read(0xc000), 3);
+}
+
+#[test]
+fn mmc1_ignores_second_write_of_asl() {
+    let cartridge = Cartridge::from_bytes(&ines_image(1, 16, &[0x55; 8192])).unwrap();
+    let mut cpu = RP2A03::with_bus(CartridgeBus::new(mapper::from_cartridge(&cartridge).unwrap()));
+    cpu.registers.pc = 0x0200;
+
+    // ASL $E000 five times. The last bank reads $0F, so each writes $0F
+    // back and then $1E.
+    for i in 0..5 {
+        let addr = 0x0200 + i * 3;
+        cpu.memory.write(addr, 0x0e);
+        cpu.memory.write(addr + 1, 0x00);
+        cpu.memory.write(addr + 2, 0xe0);
+    }
+
+    for _ in 0..5 {
+        cpu.execute();
+    }
+
+    // Only the $0Fs reached the shift register, loading PRG bank 15.
+    assert_eq!(cpu.memory.peek(0x8000), 15);
+}
+
+#[test]
+fn mmc3_irq_reaches_cpu() {
+    let cartridge = Cartridge::from_bytes(&ines_image(4, 2, &[0x55; 8192])).unwrap();
+    let mut cpu = RP2A03::with_bus(CartridgeBus::new(mapper::from_cartridge(&cartridge).unwrap()));
+    cpu.registers.pc = 0x0200;
+    cpu.registers.sp = 0xfd;
+    cpu.registers.set_interrupt_disable_flag(false);
+
+    cpu.memory.write(0xc000, 1);
+    cpu.memory.write(0xc001, 0);
+    cpu.memory.write(0xe001, 0);
+    for _ in 0..2 {
+        cpu.memory.mapper_mut().ppu_read(0x0000);
+        cpu.memory.tick(85);
+        cpu.memory.mapper_mut().ppu_read(0x1000);
+        cpu.memory.tick(28);
+    }
+    assert!(cpu.memory.irq());
+
+    cpu.execute();
+
+    // The IRQ vector is in the last bank, filled with its number.
+    assert_eq!(cpu.registers.pc, 0x0101);
+    assert!(cpu.registers.interrupt_disable_flag());
+}
+
+#[test]
+fn vrc4_irq_counts_cpu_cycles() {
+    let cartridge = Cartridge::from_bytes(&ines_image(21, 2, &[0x55; 8192])).unwrap();
+    let mut cpu = RP2A03::with_bus(CartridgeBus::new(mapper::from_cartridge(&cartridge).unwrap()));
+    cpu.registers.pc = 0x0200;
+    cpu.registers.set_interrupt_disable_flag(true);
+    cpu.memory.write(0x0200, 0xea);
+    cpu.memory.write(0x0201, 0xea);
+
+    // Latch $FD in two nibbles, then enable the counter in cycle mode.
+    cpu.memory.write(0xf000, 0x0d);
+    cpu.memory.write(0xf002, 0x0f);
+    cpu.memory.write(0xf004, 0x06);
+
+    cpu.execute();
+    assert!(!cpu.memory.irq());
+
+    cpu.execute();
+    assert!(cpu.memory.irq());
+}
+
+#[test]
+fn mmc5_stays_in_frame_while_the_ppu_fetches() {
+    let cartridge = Cartridge::from_bytes(&ines_image(5, 2, &[0x55; 8192])).unwrap();
+    let mut bus = CartridgeBus::new(mapper::from_cartridge(&cartridge).unwrap());
+    let vram = [0x00; 0x0800];
+
+    // The repeated fetch at the end of a line starts the frame.
+    for _ in 0..3 {
+        bus.mapper_mut().nametable_read(0x2002, &vram);
+    }
+    assert_eq!(bus.read(0x5204) & 0x40, 0x40);
+
+    // The PPU stepped between the cycles, a fetch every 3 dots or so.
+    for tile in 0..32 {
+        bus.mapper_mut().nametable_read(0x2000 + tile, &vram);
+        bus.tick(1);
+    }
+    assert_eq!(bus.read(0x5204) & 0x40, 0x40);
+
+    bus.tick(3);
+    assert_eq!(bus.read(0x5204) & 0x40, 0x00);
+}
+
+#[test]
+fn mmc2_and_mmc4_latch_differently() {
+    // CHR ROM in 4 KB banks filled with their number.
+    let chr: Vec<u8> = (0..8).flat_map(|bank| vec![bank; 4096]).collect();
+
+    let latched_bank = |mapper_number| {
+        let cartridge = Cartridge::from_bytes(&ines_image(mapper_number, 2, &chr)).unwrap();
+        let mut mapper = mapper::from_cartridge(&cartridge).unwrap();
+        mapper.cpu_write(0xb000, 1);
+        mapper.cpu_write(0xc000, 2);
+
+        // The last byte of tile $FD, which only the MMC4 latches on.
+        mapper.ppu_read(0x0fdf);
+        mapper.ppu_read(0x0000)
+    };
+
+    assert_eq!(latched_bank(9), 2);
+    assert_eq!(latched_bank(10), 1);
+}
@@ -0,0 +1,207 @@
+#![cfg(feature = "std")]
+
+extern crate rustnes;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+use rustnes::rom;
+
+fn ines_image(prg_banks: u8, chr_banks: u8) -> Vec<u8> {
+    let mut image = vec![b'N', b'E', b'S', 0x1a, prg_banks, chr_banks];
+    image.extend_from_slice(&[0x00; 10]);
+    image.extend(vec![0xaa; 16384 * prg_banks as usize]);
+    image.extend(vec![0x55; 8192 * chr_banks as usize]);
+
+    image
+}
+
+#[test]
+fn parse_ines_image() {
+    let image = ines_image(2, 1);
+
+    assert!(rom::parse_ines(&image).is_done());
+}
+
+#[test]
+fn parse_ines_with_bad_magic() {
+    let mut image = ines_image(1, 1);
+    image[3] = 0x00;
+
+    assert!(rom::parse_ines(&image).is_err());
+}
+
+#[test]
+fn parse_truncated_ines() {
+    let mut image = ines_image(2, 1);
+    image.truncate(16 + 16384);
+
+    assert!(rom::parse_ines(&image).is_incomplete());
+}
+
+#[test]
+fn ines_accessors() {
+    let mut image = ines_image(2, 1);
+    image[6] = 0b0011_0011;
+    image[7] = 0b0001_0000;
+
+    let (_, ines) = rom::parse_ines(&image).unwrap();
+
+    assert_eq!(ines.mapper_number(), 0x13);
+    assert_eq!(ines.mirroring(), rom::Mirroring::Vertical);
+    assert!(ines.has_battery());
+    assert_eq!(ines.prg_rom().len(), 2 * 16384);
+    assert_eq!(ines.chr_rom().len(), 8192);
+}
+
+#[test]
+fn nes2_accessors() {
+    let mut image = ines_image(2, 1);
+    image[7] = 0b0000_1000;
+    image[8] = 0x21;
+    image[10] = 0x70;
+
+    let (_, ines) = rom::parse_ines(&image).unwrap();
+    let header = ines.header();
+
+    assert_eq!(ines.mapper_number(), 0x100);
+    assert_eq!(header.submapper(), Some(2));
+    assert_eq!(header.prg_nvram_bytes(), 8192);
+    assert_eq!(header.timing(), rom::Timing::Ntsc);
+}
+
+#[test]
+fn cartridge_from_path() {
+    let path = env::temp_dir().join("rustnes-cartridge-from-path.nes");
+    File::create(&path).unwrap().write_all(&ines_image(1, 1)).unwrap();
+
+    let cartridge = rom::Cartridge::from_path(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(cartridge.prg_rom().len(), 16384);
+    assert_eq!(cartridge.chr_rom().len(), 8192);
+}
+
+#[test]
+fn cartridge_from_missing_path() {
+    let path = env::temp_dir().join("rustnes-does-not-exist.nes");
+
+    match rom::Cartridge::from_path(&path) {
+        Err(rom::RomError::Io(_)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn write_round_trip() {
+    let mut image = ines_image(2, 1);
+    image[6] = 0b0100_0011;
+    image[8] = 0x01;
+
+    let (_, ines) = rom::parse_ines(&image).unwrap();
+
+    assert_eq!(ines.to_bytes(), image);
+    assert_eq!(rom::Cartridge::from(ines).to_bytes(), image);
+}
+
+#[test]
+fn parse_disk_dude_header() {
+    let mut image = ines_image(1, 1);
+    image[6] = 0b0100_0000;
+    image[7..16].copy_from_slice(b"DiskDude!");
+
+    assert!(rom::parse_ines(&image).is_err());
+
+    let (_, (ines, warnings)) = rom::parse_ines_lenient(&image).unwrap();
+
+    assert_eq!(ines.mapper_number(), 4);
+    assert_eq!(
+        warnings,
+        vec![rom::HeaderWarning::RipperTag("DiskDude!".to_string())]
+    );
+}
+
+#[test]
+fn unif_cartridge() {
+    let mut image = b"UNIF\x04\0\0\0".to_vec();
+    image.extend_from_slice(&[0x00; 24]);
+    image.extend_from_slice(b"MAPR\x0a\0\0\0NES-CNROM\0");
+    image.extend_from_slice(b"PRG0\0\x40\0\0");
+    image.extend(vec![0xaa; 16384]);
+    image.extend_from_slice(b"CHR0\0\x20\0\0");
+    image.extend(vec![0x55; 8192]);
+
+    let (_, unif) = rom::parse_unif(&image).unwrap();
+
+    assert_eq!(unif.board_name(), Some("NES-CNROM"));
+
+    let cartridge = rom::Cartridge::from_unif(&unif).unwrap();
+
+    assert_eq!(cartridge.mapper_number(), 3);
+    assert_eq!(cartridge.prg_rom().len(), 16384);
+    assert_eq!(cartridge.chr_rom().len(), 8192);
+}
+
+#[test]
+fn read_patched_with_sibling_ips() {
+    let rom_path = env::temp_dir().join("rustnes-sibling-patch.nes");
+    let patch_path = env::temp_dir().join("rustnes-sibling-patch.ips");
+    File::create(&rom_path).unwrap().write_all(&ines_image(1, 1)).unwrap();
+
+    // Mapper 1, then an RLE record filling the first 4 bytes of PRG ROM.
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x01, 0x10]);
+    patch.extend_from_slice(&[0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x04, 0xea]);
+    patch.extend_from_slice(b"EOF");
+    File::create(&patch_path).unwrap().write_all(&patch).unwrap();
+
+    let result = rom::read_patched(&rom_path);
+    fs::remove_file(&rom_path).unwrap();
+    fs::remove_file(&patch_path).unwrap();
+
+    let (data, applied) = result.unwrap();
+    let cartridge = rom::Cartridge::from_bytes(&data).unwrap();
+
+    assert_eq!(applied, Some(patch_path));
+    assert_eq!(cartridge.mapper_number(), 1);
+    assert_eq!(cartridge.prg_rom()[..5], [0xea, 0xea, 0xea, 0xea, 0xaa]);
+}
+
+#[test]
+fn cartridge_from_path_with_sibling_ips() {
+    let rom_path = env::temp_dir().join("rustnes-from-path-patch.nes");
+    let patch_path = env::temp_dir().join("rustnes-from-path-patch.ips");
+    File::create(&rom_path).unwrap().write_all(&ines_image(1, 1)).unwrap();
+
+    // Mapper 1.
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x01, 0x10]);
+    patch.extend_from_slice(b"EOF");
+    File::create(&patch_path).unwrap().write_all(&patch).unwrap();
+
+    let result = rom::Cartridge::from_path(&rom_path);
+    fs::remove_file(&rom_path).unwrap();
+    fs::remove_file(&patch_path).unwrap();
+
+    assert_eq!(result.unwrap().mapper_number(), 1);
+}
+
+#[test]
+fn database_corrects_header() {
+    let mut image = ines_image(1, 1);
+    image[6] = 0b0001_0000;
+    let mut cartridge = rom::Cartridge::from_bytes(&image).unwrap();
+
+    let mut data = vec![0xaa; 16384];
+    data.extend(vec![0x55; 8192]);
+    let line = format!("crc32={:08x} mapper=4 mirroring=vertical", rom::crc32(&data));
+    let database = rom::Database::parse(&line).unwrap();
+
+    let discrepancies = cartridge.apply_database(&database).unwrap();
+
+    assert_eq!(cartridge.crc32(), rom::crc32(&data));
+    assert_eq!(discrepancies.len(), 2);
+    assert_eq!(cartridge.mapper_number(), 4);
+    assert_eq!(cartridge.mirroring(), rom::Mirroring::Vertical);
+}